@@ -0,0 +1,50 @@
+// The service worker of the web build. It shows Web Push messages as notifications while the app is not visible,
+// and opens the room of a notification when it is clicked.
+//
+// A push message is JSON: { "room": "<uuid>", "title": "...", "body": "..." }. Without a push server, the app posts
+// the same message to the worker with { "type": "push", "message": ... }, see src/push.rs.
+
+self.addEventListener("install", () => self.skipWaiting());
+self.addEventListener("activate", (event) => event.waitUntil(self.clients.claim()));
+
+async function notify(message, always) {
+    const clients = await self.clients.matchAll({ type: "window", includeUncontrolled: true });
+
+    // A visible app shows new messages itself.
+    if (!always && clients.some((client) => client.visibilityState === "visible")) {
+        return;
+    }
+
+    await self.registration.showNotification(message.title ?? "dx-chat", {
+        body: message.body ?? "",
+        tag: message.room,
+        data: message,
+    });
+}
+
+self.addEventListener("push", (event) => {
+    event.waitUntil(notify(event.data ? event.data.json() : {}, false));
+});
+
+self.addEventListener("message", (event) => {
+    if (event.data?.type === "push") {
+        event.waitUntil(notify(event.data.message, true));
+    }
+});
+
+self.addEventListener("notificationclick", (event) => {
+    const room = event.notification.data?.room;
+
+    event.notification.close();
+
+    event.waitUntil((async () => {
+        const clients = await self.clients.matchAll({ type: "window", includeUncontrolled: true });
+
+        if (clients.length > 0) {
+            await clients[0].focus();
+            clients[0].postMessage({ type: "open-room", room });
+        } else {
+            await self.clients.openWindow(room ? `/blog/${room}` : "/");
+        }
+    })());
+});
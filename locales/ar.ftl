@@ -0,0 +1,211 @@
+# Chat
+room-not-found = الغرفة غير موجودة
+history-beginning = هذه بداية #{ $name }
+unknown-user = مجهول
+attach-files = إرفاق ملفات
+remove-preview = إزالة المعاينة
+show-preview = اعرض معاينة { $host }
+send-message = إرسال
+composer-too-long = يمكن أن تحتوي الرسالة على { $max } حرف كحد أقصى
+composer-long-paste = لقد ألصقت { $count } حرفًا. هل تريد الاحتفاظ بها؟
+composer-keep = احتفظ
+composer-undo = تراجع
+composer-editing = أنت تعدّل رسالتك
+composer-cancel-edit = إلغاء
+message-edited = (معدّلة)
+typing-one = { $name } يكتب…
+typing-two = { $first } و{ $second } يكتبان…
+typing-several = عدة أشخاص يكتبون…
+delivery-pending = جارٍ الإرسال…
+delivery-queued = في انتظار الاتصال
+delivery-disconnected = غير متصل
+offline-banner = أنت غير متصل. ستُرسل الرسائل عند عودة الاتصال.
+delivery-sent = أُرسلت
+delivery-delivered = سُلّمت
+delivery-failed = لم تُرسل
+delivery-retry = أعد المحاولة
+crypto-failed = تعذّر تشفير الرسالة أو فك تشفيرها
+crypto-unknown-device = الرسالة من جهاز غير معروف
+crypto-bad-signature = التوقيع لا يطابق الجهاز
+crypto-no-key = لم يُشارك مفتاح هذه الرسالة مع هذا الجهاز
+seen-by = شاهدها { $names }
+seen-by-count = شاهدها { $count } أشخاص
+reaction-add = تفاعل بـ { $emoji }
+reaction-remove = اسحب تفاعلك { $emoji }
+messages-in = الرسائل في #{ $name }
+message-announcement = { $author }: { $content }
+scroll-to-latest = الانتقال إلى أحدث رسالة
+composer-label = رسالة إلى #{ $room }
+remove-attachment = إزالة { $name }
+skip-to-content = الانتقال إلى المحتوى
+rooms = الغرف
+attachment-unavailable = { $name } لم يعد متاحًا
+attachment-too-large = حجم { $name } أكبر من { $limit }
+attachment-store-failed = تعذّر حفظ { $name }: { $error }
+
+# Sidebar
+unsent-draft = مسودة غير مرسلة
+unread-messages =
+    { $count ->
+        [zero] لا توجد رسائل غير مقروءة
+        [one] رسالة واحدة غير مقروءة
+        [two] رسالتان غير مقروءتين
+        [few] { $count } رسائل غير مقروءة
+       *[other] { $count } رسالة غير مقروءة
+    }
+settings = الإعدادات
+settings-language = اللغة
+
+# Search
+search-label = ابحث في الرسائل
+search-placeholder = ابحث، مثلًا from:yoda in:starwars has:link
+search-results =
+    { $count ->
+        [zero] لا توجد نتائج
+        [one] نتيجة واحدة
+        [two] نتيجتان
+        [few] { $count } نتائج
+       *[other] { $count } نتيجة
+    }
+search-showing = (تُعرض أحدث { $shown })
+
+# Times
+time-just-now = الآن
+time-minutes-ago =
+    { $minutes ->
+        [one] منذ دقيقة
+        [two] منذ دقيقتين
+        [few] منذ { $minutes } دقائق
+       *[other] منذ { $minutes } دقيقة
+    }
+time-today-at = اليوم الساعة { $time }
+time-yesterday-at = أمس الساعة { $time }
+time-weekday-at = { $weekday } الساعة { $time }
+day-today = اليوم
+day-yesterday = أمس
+
+# Commands
+command-me = صف ما تفعله
+command-shrug = أضف ¯\_(ツ)_/¯ إلى رسالتك
+command-topic = عيّن موضوع الغرفة أو امسحه
+command-join = انضم إلى غرفة وأنشئها إن لم تكن موجودة
+command-leave = غادر الغرفة
+command-nick = غيّر اسمك الظاهر
+command-invite = ادعُ شخصًا إلى الغرفة
+command-usage = الاستخدام: { $usage }
+command-unknown = أمر غير معروف /{ $name }
+command-not-a-command = ليس أمرًا
+change-not-allowed = لا يحق للمرسل إجراء هذا التغيير على الغرفة
+room-gone = الغرفة لم تعد موجودة
+user-not-found = لا يوجد مستخدم باسم { $name }
+already-in-room = { $name } موجود في الغرفة بالفعل
+notice-topic-cleared = مسح { $name } الموضوع
+notice-topic-changed = غيّر { $name } الموضوع إلى «{ $topic }»
+notice-joined = انضم { $name } إلى الغرفة
+notice-left = غادر { $name } الغرفة
+notice-nick = أصبح { $old } يُعرف باسم { $new }
+notice-invited = دعا { $name } { $invitee }
+
+# Settings
+settings-appearance = المظهر
+settings-theme = السمة
+theme-system = حسب النظام
+theme-light = فاتح
+theme-dark = داكن
+theme-custom-themes = سمات مخصصة
+theme-new = سمة جديدة
+theme-default-name = سمتي
+theme-name = الاسم
+theme-dark-base = مبنية على السمة الداكنة
+theme-accent = اللون المميز
+theme-own-bubble = رسائلك
+theme-other-bubble = رسائل الآخرين
+theme-delete = حذف السمة
+settings-font-size = حجم الخط
+font-size-small = صغير
+font-size-medium = متوسط
+font-size-large = كبير
+settings-compact = قائمة رسائل مضغوطة
+settings-composer = محرر الرسائل
+settings-send-key = إرسال الرسائل باستخدام
+send-key-enter = Enter (و Shift+Enter لسطر جديد)
+send-key-ctrl-enter = Ctrl+Enter (و Enter لسطر جديد)
+send-key-button = زر الإرسال (و Enter لسطر جديد)
+settings-confirm-paste = اسأل قبل الاحتفاظ بالنصوص الملصقة الطويلة جدًا
+settings-read-receipts = أرسل إيصالات القراءة واعرضها
+settings-auto-previews = اعرض معاينات الروابط في رسائل الآخرين دون سؤال
+settings-notifications = الإشعارات
+settings-notifications-enabled = إظهار إشعارات للرسائل الجديدة
+settings-notifications-sound = تشغيل صوت
+settings-notifications-mentions = فقط عند الإشارة إليّ
+settings-quiet-hours = عدم الإزعاج كل يوم
+settings-quiet-from = من
+settings-quiet-until = حتى
+settings-room-levels = الإشعار برسائل الغرف
+level-default = الافتراضي
+level-all = كل الرسائل
+level-mentions = الإشارات فقط
+level-muted = مكتومة
+notification-title = { $author } في #{ $room }
+notification-open = فتح
+browser-notifications-default = يطلب هذا المتصفح الإذن قبل عرض الإشعارات.
+browser-notifications-granted = يعرض هذا المتصفح الإشعارات.
+browser-notifications-denied = الإشعارات محظورة في إعدادات المتصفح.
+browser-notifications-allow = السماح بالإشعارات
+browser-notifications-test = إرسال إشعار تجريبي
+push-test-title = إشعار تجريبي
+push-test-body = الإشعارات تعمل.
+settings-language-time = اللغة والوقت
+settings-timestamps = أوقات الرسائل
+timestamps-relative = نسبية، مثل "منذ 5 دقائق"
+timestamps-exact = الوقت الدقيق
+settings-clock = الساعة
+clock-auto = تلقائي
+clock-12 = نظام 12 ساعة
+clock-24 = نظام 24 ساعة
+
+# Shortcuts
+
+shortcuts = اختصارات لوحة المفاتيح
+shortcuts-with-key = اختصارات لوحة المفاتيح ({ $key })
+shortcuts-customize = تغيير الاختصارات
+shortcut-quick-switcher = الانتقال إلى غرفة
+shortcut-previous-room = الغرفة السابقة
+shortcut-next-room = الغرفة التالية
+shortcut-close-dialog = إغلاق نافذة أو إلغاء التعديل
+shortcut-show-help = عرض اختصارات لوحة المفاتيح
+shortcut-edit-last-message = تعديل رسالتك الأخيرة
+shortcut-record = اضغط المفاتيح الجديدة
+shortcut-reset = إعادة التعيين
+quick-switcher-placeholder = انتقل إلى غرفة
+quick-switcher-empty = لا توجد غرف مطابقة
+close = إغلاق
+
+# Presence
+
+status-online = متصل
+status-away = بعيد
+status-do-not-disturb = عدم الإزعاج
+status-offline = غير متصل
+status-appear-offline = الظهور بمظهر غير متصل
+status-change = تغيير حالتك
+status-text = ما هي حالتك؟
+status-clear = مسح الحالة
+status-clear-after = مسح الحالة بعد
+status-clear-never = عدم المسح
+status-clear-hour = المسح بعد ساعة
+status-clear-four-hours = المسح بعد 4 ساعات
+status-clear-day = المسح بعد يوم
+status-until = حتى { $time }
+profile-devices = الأجهزة
+profile-verify-help = تُشفَّر الرسائل لكل جهاز من هذه الأجهزة. للتأكد من أن لا أحد يقرأ في الطريق، قارن أرقام الجهاز بالأرقام الظاهرة على ذلك الجهاز، وجهًا لوجه أو في مكالمة. إن تطابقت فاعتمده كجهاز موثّق.
+profile-no-devices = لا توجد أجهزة بعد. لا يمكن تشفير الرسائل لهذا المستخدم.
+profile-this-device = هذا الجهاز
+profile-device = الجهاز { $id }
+profile-fingerprint = البصمة
+profile-key-changed = مفتاح هذا الجهاز ليس المفتاح الذي وثّقته. ربما أُعيد تثبيته، أو ربما ينتحل أحدهم صفته. قارن الأرقام من جديد قبل توثيقه.
+profile-verify = وثّق الجهاز
+profile-unverify = ألغِ التوثيق
+trust-verified = موثّق
+trust-unverified = غير موثّق
+trust-changed = تغيّر المفتاح
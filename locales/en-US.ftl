@@ -0,0 +1,203 @@
+# Chat
+room-not-found = Room not found
+history-beginning = This is the beginning of #{ $name }
+unknown-user = Unknown
+attach-files = Attach files
+remove-preview = Remove preview
+show-preview = Show preview of { $host }
+send-message = Send
+composer-too-long = Messages can be at most { $max } characters long
+composer-long-paste = You pasted { $count } characters. Keep them?
+composer-keep = Keep
+composer-undo = Undo
+composer-editing = Editing your message
+composer-cancel-edit = Cancel
+message-edited = (edited)
+typing-one = { $name } is typing…
+typing-two = { $first } and { $second } are typing…
+typing-several = Several people are typing…
+delivery-pending = Sending…
+delivery-queued = Waiting for connection
+delivery-disconnected = Not connected
+offline-banner = You are offline. Messages will be sent when the connection is back.
+delivery-sent = Sent
+delivery-delivered = Delivered
+delivery-failed = Not sent
+delivery-retry = Retry
+crypto-failed = The message could not be encrypted or decrypted
+crypto-unknown-device = The message is from an unknown device
+crypto-bad-signature = The signature does not match the device
+crypto-no-key = The key for this message has not been shared with this device
+seen-by = Seen by { $names }
+seen-by-count = Seen by { $count } people
+reaction-add = React with { $emoji }
+reaction-remove = Take back your { $emoji } reaction
+messages-in = Messages in #{ $name }
+message-announcement = { $author }: { $content }
+scroll-to-latest = Scroll to the latest message
+composer-label = Message #{ $room }
+remove-attachment = Remove { $name }
+skip-to-content = Skip to content
+rooms = Rooms
+attachment-unavailable = { $name } is no longer available
+attachment-too-large = { $name } is larger than { $limit }
+attachment-store-failed = Could not store { $name }: { $error }
+
+# Sidebar
+unsent-draft = Unsent draft
+unread-messages =
+    { $count ->
+        [one] 1 unread message
+       *[other] { $count } unread messages
+    }
+settings = Settings
+settings-language = Language
+
+# Search
+search-label = Search messages
+search-placeholder = Search, e.g. from:yoda in:starwars has:link
+search-results =
+    { $count ->
+        [one] 1 result
+       *[other] { $count } results
+    }
+search-showing = (showing the newest { $shown })
+
+# Times
+time-just-now = just now
+time-minutes-ago =
+    { $minutes ->
+        [one] 1 min ago
+       *[other] { $minutes } min ago
+    }
+time-today-at = today at { $time }
+time-yesterday-at = yesterday at { $time }
+time-weekday-at = { $weekday } at { $time }
+day-today = Today
+day-yesterday = Yesterday
+
+# Commands
+command-me = Describe what you are doing
+command-shrug = Append ¯\_(ツ)_/¯ to your message
+command-topic = Set or clear the topic of the room
+command-join = Join a room, creating it if it does not exist
+command-leave = Leave the room
+command-nick = Change your display name
+command-invite = Invite someone to the room
+command-usage = Usage: { $usage }
+command-unknown = Unknown command /{ $name }
+command-not-a-command = Not a command
+change-not-allowed = The sender may not make this change to the room
+room-gone = The room no longer exists
+user-not-found = No user named { $name }
+already-in-room = { $name } is already in the room
+notice-topic-cleared = { $name } cleared the topic
+notice-topic-changed = { $name } changed the topic to "{ $topic }"
+notice-joined = { $name } joined the room
+notice-left = { $name } left the room
+notice-nick = { $old } is now known as { $new }
+notice-invited = { $name } invited { $invitee }
+
+# Settings
+settings-appearance = Appearance
+settings-theme = Theme
+theme-system = Follow the system
+theme-light = Light
+theme-dark = Dark
+theme-custom-themes = Custom themes
+theme-new = New theme
+theme-default-name = My theme
+theme-name = Name
+theme-dark-base = Based on the dark theme
+theme-accent = Accent
+theme-own-bubble = Your messages
+theme-other-bubble = Others' messages
+theme-delete = Delete theme
+settings-font-size = Font size
+font-size-small = Small
+font-size-medium = Medium
+font-size-large = Large
+settings-compact = Compact message list
+settings-composer = Composer
+settings-send-key = Send messages with
+send-key-enter = Enter (Shift+Enter for a new line)
+send-key-ctrl-enter = Ctrl+Enter (Enter for a new line)
+send-key-button = The send button (Enter for a new line)
+settings-confirm-paste = Ask before keeping very long pastes
+settings-read-receipts = Send and show read receipts
+settings-auto-previews = Show link previews in messages from others without asking
+settings-notifications = Notifications
+settings-notifications-enabled = Show notifications for new messages
+settings-notifications-sound = Play a sound
+settings-notifications-mentions = Only when I am mentioned
+settings-quiet-hours = Do not disturb every day
+settings-quiet-from = From
+settings-quiet-until = Until
+settings-room-levels = Notify about messages in rooms
+level-default = Default
+level-all = All messages
+level-mentions = Mentions only
+level-muted = Muted
+notification-title = { $author } in #{ $room }
+notification-open = Open
+browser-notifications-default = This browser asks before showing notifications.
+browser-notifications-granted = This browser shows notifications.
+browser-notifications-denied = Notifications are blocked in the browser settings.
+browser-notifications-allow = Allow notifications
+browser-notifications-test = Send a test notification
+push-test-title = Test notification
+push-test-body = Notifications work.
+settings-language-time = Language and time
+settings-timestamps = Message times
+timestamps-relative = Relative, like "5 min ago"
+timestamps-exact = Exact
+settings-clock = Clock
+clock-auto = Automatic
+clock-12 = 12-hour
+clock-24 = 24-hour
+
+# Shortcuts
+
+shortcuts = Keyboard shortcuts
+shortcuts-with-key = Keyboard shortcuts ({ $key })
+shortcuts-customize = Change shortcuts
+shortcut-quick-switcher = Switch to a room
+shortcut-previous-room = Previous room
+shortcut-next-room = Next room
+shortcut-close-dialog = Close a dialog or cancel editing
+shortcut-show-help = Show keyboard shortcuts
+shortcut-edit-last-message = Edit your last message
+shortcut-record = Press the new keys
+shortcut-reset = Reset
+quick-switcher-placeholder = Go to a room
+quick-switcher-empty = No rooms match
+close = Close
+
+# Presence
+
+status-online = Online
+status-away = Away
+status-do-not-disturb = Do not disturb
+status-offline = Offline
+status-appear-offline = Appear offline
+status-change = Change your status
+status-text = What's your status?
+status-clear = Clear status
+status-clear-after = Clear the status after
+status-clear-never = Don't clear
+status-clear-hour = Clear after an hour
+status-clear-four-hours = Clear after 4 hours
+status-clear-day = Clear after a day
+status-until = Until { $time }
+profile-devices = Devices
+profile-verify-help = Messages are encrypted for each of these devices. To make sure nobody is reading along, compare the numbers of a device with the ones shown on that device, in person or on a call. If they match, mark it as verified.
+profile-no-devices = No devices yet. Messages cannot be encrypted for this user.
+profile-this-device = This device
+profile-device = Device { $id }
+profile-fingerprint = Fingerprint
+profile-key-changed = The key of this device is not the one you verified. It may have been reinstalled, or someone may be posing as it. Compare the numbers again before you verify it.
+profile-verify = Mark as verified
+profile-unverify = Remove verification
+trust-verified = Verified
+trust-unverified = Not verified
+trust-changed = Key changed
@@ -0,0 +1,199 @@
+# Chat
+room-not-found = Huonetta ei löytynyt
+history-beginning = Tästä alkaa #{ $name }
+unknown-user = Tuntematon
+attach-files = Liitä tiedostoja
+remove-preview = Poista esikatselu
+show-preview = Näytä sivun { $host } esikatselu
+send-message = Lähetä
+composer-too-long = Viestissä voi olla enintään { $max } merkkiä
+composer-long-paste = Liitit { $count } merkkiä. Säilytetäänkö ne?
+composer-keep = Säilytä
+composer-undo = Kumoa
+composer-editing = Muokkaat viestiäsi
+composer-cancel-edit = Peruuta
+message-edited = (muokattu)
+typing-one = { $name } kirjoittaa…
+typing-two = { $first } ja { $second } kirjoittavat…
+typing-several = Useat kirjoittavat…
+delivery-pending = Lähetetään…
+delivery-queued = Odottaa yhteyttä
+delivery-disconnected = Ei yhteyttä
+offline-banner = Olet offline-tilassa. Viestit lähetetään, kun yhteys palaa.
+delivery-sent = Lähetetty
+delivery-delivered = Toimitettu
+delivery-failed = Ei lähetetty
+delivery-retry = Yritä uudelleen
+crypto-failed = Viestin salaus tai salauksen purku epäonnistui
+crypto-unknown-device = Viesti on tuntemattomalta laitteelta
+crypto-bad-signature = Allekirjoitus ei vastaa laitetta
+crypto-no-key = Tämän viestin avainta ei ole jaettu tälle laitteelle
+seen-by = Nähnyt: { $names }
+seen-by-count = Nähnyt { $count } henkilöä
+reaction-add = Reagoi: { $emoji }
+reaction-remove = Peru reaktiosi { $emoji }
+messages-in = Viestit huoneessa #{ $name }
+message-announcement = { $author }: { $content }
+scroll-to-latest = Vieritä uusimpaan viestiin
+composer-label = Viesti huoneeseen #{ $room }
+remove-attachment = Poista { $name }
+skip-to-content = Siirry sisältöön
+rooms = Huoneet
+attachment-unavailable = { $name } ei ole enää saatavilla
+attachment-too-large = { $name } on suurempi kuin { $limit }
+attachment-store-failed = Tiedostoa { $name } ei voitu tallentaa: { $error }
+
+# Sidebar
+unsent-draft = Lähettämätön luonnos
+unread-messages =
+    { $count ->
+        [one] 1 lukematon viesti
+       *[other] { $count } lukematonta viestiä
+    }
+settings = Asetukset
+settings-language = Kieli
+
+# Search
+search-label = Hae viesteistä
+search-placeholder = Hae, esim. from:yoda in:starwars has:link
+search-results =
+    { $count ->
+        [one] 1 tulos
+       *[other] { $count } tulosta
+    }
+search-showing = (näytetään { $shown } uusinta)
+
+# Times
+time-just-now = juuri nyt
+time-minutes-ago = { $minutes } min sitten
+time-today-at = tänään klo { $time }
+time-yesterday-at = eilen klo { $time }
+time-weekday-at = { $weekday } klo { $time }
+day-today = Tänään
+day-yesterday = Eilen
+
+# Commands
+command-me = Kerro mitä teet
+command-shrug = Lisää viestin perään ¯\_(ツ)_/¯
+command-topic = Aseta tai poista huoneen aihe
+command-join = Liity huoneeseen ja luo se tarvittaessa
+command-leave = Poistu huoneesta
+command-nick = Vaihda näyttönimesi
+command-invite = Kutsu joku huoneeseen
+command-usage = Käyttö: { $usage }
+command-unknown = Tuntematon komento /{ $name }
+command-not-a-command = Ei komento
+change-not-allowed = Lähettäjä ei saa tehdä tätä muutosta huoneeseen
+room-gone = Huonetta ei ole enää olemassa
+user-not-found = Käyttäjää { $name } ei löytynyt
+already-in-room = { $name } on jo huoneessa
+notice-topic-cleared = { $name } tyhjensi aiheen
+notice-topic-changed = { $name } vaihtoi aiheeksi ”{ $topic }”
+notice-joined = { $name } liittyi huoneeseen
+notice-left = { $name } poistui huoneesta
+notice-nick = { $old } on nyt { $new }
+notice-invited = { $name } kutsui käyttäjän { $invitee }
+
+# Settings
+settings-appearance = Ulkoasu
+settings-theme = Teema
+theme-system = Järjestelmän mukaan
+theme-light = Vaalea
+theme-dark = Tumma
+theme-custom-themes = Omat teemat
+theme-new = Uusi teema
+theme-default-name = Oma teema
+theme-name = Nimi
+theme-dark-base = Pohjana tumma teema
+theme-accent = Korostusväri
+theme-own-bubble = Omat viestit
+theme-other-bubble = Muiden viestit
+theme-delete = Poista teema
+settings-font-size = Tekstin koko
+font-size-small = Pieni
+font-size-medium = Keskikokoinen
+font-size-large = Suuri
+settings-compact = Tiivis viestilista
+settings-composer = Viestikenttä
+settings-send-key = Lähetä viestit näppäimellä
+send-key-enter = Enter (Shift+Enter lisää rivinvaihdon)
+send-key-ctrl-enter = Ctrl+Enter (Enter lisää rivinvaihdon)
+send-key-button = Lähetä-painike (Enter lisää rivinvaihdon)
+settings-confirm-paste = Kysy ennen hyvin pitkien liitosten säilyttämistä
+settings-read-receipts = Lähetä ja näytä lukukuittaukset
+settings-auto-previews = Näytä muiden viestien linkkien esikatselut kysymättä
+settings-notifications = Ilmoitukset
+settings-notifications-enabled = Näytä ilmoitukset uusista viesteistä
+settings-notifications-sound = Toista ääni
+settings-notifications-mentions = Vain kun minut mainitaan
+settings-quiet-hours = Älä häiritse joka päivä
+settings-quiet-from = Alkaen
+settings-quiet-until = Asti
+settings-room-levels = Ilmoita huoneiden viesteistä
+level-default = Oletus
+level-all = Kaikki viestit
+level-mentions = Vain maininnat
+level-muted = Mykistetty
+notification-title = { $author } huoneessa #{ $room }
+notification-open = Avaa
+browser-notifications-default = Selain kysyy luvan ennen ilmoitusten näyttämistä.
+browser-notifications-granted = Selain näyttää ilmoitukset.
+browser-notifications-denied = Ilmoitukset on estetty selaimen asetuksista.
+browser-notifications-allow = Salli ilmoitukset
+browser-notifications-test = Lähetä testi-ilmoitus
+push-test-title = Testi-ilmoitus
+push-test-body = Ilmoitukset toimivat.
+settings-language-time = Kieli ja aika
+settings-timestamps = Viestien ajat
+timestamps-relative = Suhteellinen, esim. "5 min sitten"
+timestamps-exact = Tarkka
+settings-clock = Kello
+clock-auto = Automaattinen
+clock-12 = 12 tunnin
+clock-24 = 24 tunnin
+
+# Shortcuts
+
+shortcuts = Pikanäppäimet
+shortcuts-with-key = Pikanäppäimet ({ $key })
+shortcuts-customize = Muuta pikanäppäimiä
+shortcut-quick-switcher = Siirry huoneeseen
+shortcut-previous-room = Edellinen huone
+shortcut-next-room = Seuraava huone
+shortcut-close-dialog = Sulje ikkuna tai peru muokkaus
+shortcut-show-help = Näytä pikanäppäimet
+shortcut-edit-last-message = Muokkaa viimeisintä viestiäsi
+shortcut-record = Paina uusia näppäimiä
+shortcut-reset = Palauta
+quick-switcher-placeholder = Siirry huoneeseen
+quick-switcher-empty = Yksikään huone ei vastaa hakua
+close = Sulje
+
+# Presence
+
+status-online = Paikalla
+status-away = Poissa
+status-do-not-disturb = Älä häiritse
+status-offline = Poissa linjalta
+status-appear-offline = Näy poissa linjalta
+status-change = Vaihda tilaasi
+status-text = Mikä on tilasi?
+status-clear = Tyhjennä tila
+status-clear-after = Tyhjennä tila kuluttua
+status-clear-never = Älä tyhjennä
+status-clear-hour = Tyhjennä tunnin kuluttua
+status-clear-four-hours = Tyhjennä 4 tunnin kuluttua
+status-clear-day = Tyhjennä päivän kuluttua
+status-until = { $time } asti
+profile-devices = Laitteet
+profile-verify-help = Viestit salataan jokaiselle näistä laitteista. Varmistaaksesi, ettei kukaan lue välissä, vertaa laitteen numeroita siinä laitteessa näkyviin numeroihin kasvokkain tai puhelussa. Jos ne täsmäävät, merkitse laite vahvistetuksi.
+profile-no-devices = Ei vielä laitteita. Viestejä ei voi salata tälle käyttäjälle.
+profile-this-device = Tämä laite
+profile-device = Laite { $id }
+profile-fingerprint = Sormenjälki
+profile-key-changed = Tämän laitteen avain ei ole se, jonka vahvistit. Laite on ehkä asennettu uudelleen, tai joku voi esiintyä sen nimissä. Vertaa numerot uudelleen ennen kuin vahvistat sen.
+profile-verify = Merkitse vahvistetuksi
+profile-unverify = Poista vahvistus
+trust-verified = Vahvistettu
+trust-unverified = Ei vahvistettu
+trust-changed = Avain vaihtunut
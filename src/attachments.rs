@@ -0,0 +1,321 @@
+//! The attachments module contains the files shared in messages. A message only carries the [`Attachment`] metadata,
+//! the file contents live in a [`BlobStore`] and are referenced by a [`BlobRef`].
+//!
+//! The contents travel to the other members inside the encrypted change that posts the message: [`pack`] puts them
+//! into the attachment before it is sealed, and [`unpack`] moves them into the blob store of the recipient.
+//!
+//! Desktop and mobile keep blobs as files in a directory, the web keeps them in memory for the lifetime of the page.
+//! Blobs are not encrypted at rest: anyone who can read the directory can read the files.
+
+use std::{io, rc::Rc};
+
+use base64::Engine;
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::i18n::t;
+
+/// Files larger than this are rejected when attaching.
+pub const MAX_ATTACHMENT_SIZE: usize = 25 * 1024 * 1024;
+
+/// A reference to the contents of an attachment in a [`BlobStore`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BlobRef(pub Uuid);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    pub mime: String,
+    pub size: u64,
+    pub blob: BlobRef,
+    /// The contents, base64 encoded, while the attachment travels to the other members.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contents: Option<String>,
+}
+
+impl Attachment {
+    pub fn is_image(&self) -> bool {
+        self.mime.starts_with("image/")
+    }
+}
+
+pub trait BlobStore {
+    fn put(&self, bytes: &[u8]) -> io::Result<BlobRef>;
+    fn get(&self, blob: &BlobRef) -> io::Result<Vec<u8>>;
+    fn delete(&self, blob: &BlobRef) -> io::Result<()>;
+}
+
+/// Stores every blob as a file named after its reference in `root`.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct DirectoryStore {
+    root: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DirectoryStore {
+    pub fn new(root: impl Into<std::path::PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl BlobStore for DirectoryStore {
+    fn put(&self, bytes: &[u8]) -> io::Result<BlobRef> {
+        let blob = BlobRef(Uuid::new_v4());
+
+        std::fs::create_dir_all(&self.root)?;
+        std::fs::write(self.root.join(blob.0.to_string()), bytes)?;
+
+        Ok(blob)
+    }
+
+    fn get(&self, blob: &BlobRef) -> io::Result<Vec<u8>> {
+        std::fs::read(self.root.join(blob.0.to_string()))
+    }
+
+    fn delete(&self, blob: &BlobRef) -> io::Result<()> {
+        std::fs::remove_file(self.root.join(blob.0.to_string()))
+    }
+}
+
+/// Keeps blobs in memory, for the web where there is no file system to write to.
+#[cfg(target_arch = "wasm32")]
+#[derive(Default)]
+pub struct MemoryStore {
+    blobs: std::cell::RefCell<std::collections::HashMap<BlobRef, Vec<u8>>>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl BlobStore for MemoryStore {
+    fn put(&self, bytes: &[u8]) -> io::Result<BlobRef> {
+        let blob = BlobRef(Uuid::new_v4());
+
+        self.blobs.borrow_mut().insert(blob, bytes.to_vec());
+
+        Ok(blob)
+    }
+
+    fn get(&self, blob: &BlobRef) -> io::Result<Vec<u8>> {
+        self.blobs.borrow()
+            .get(blob)
+            .cloned()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn delete(&self, blob: &BlobRef) -> io::Result<()> {
+        self.blobs.borrow_mut()
+            .remove(blob)
+            .map(|_| ())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_store() -> Rc<dyn BlobStore> {
+    Rc::new(DirectoryStore::new(crate::storage::data_dir().join("blobs")))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn default_store() -> Rc<dyn BlobStore> {
+    Rc::new(MemoryStore::default())
+}
+
+pub static BLOBS: GlobalSignal<Rc<dyn BlobStore>> = Global::new(default_store);
+
+/// Put `bytes` into the blob store and describe them as an attachment.
+pub fn attach(name: &str, mime: Option<&str>, bytes: &[u8]) -> Result<Attachment, String> {
+    if bytes.len() > MAX_ATTACHMENT_SIZE {
+        return Err(t!("attachment-too-large", name = name, limit = format_size(MAX_ATTACHMENT_SIZE as u64)));
+    }
+
+    let blob = BLOBS.peek().put(bytes)
+        .map_err(|err| t!("attachment-store-failed", name = name, error = err.to_string()))?;
+
+    Ok(Attachment {
+        name: name.to_string(),
+        mime: mime
+            .filter(|mime| !mime.is_empty())
+            .unwrap_or_else(|| guess_mime(name))
+            .to_string(),
+        size: bytes.len() as u64,
+        blob,
+        contents: None,
+    })
+}
+
+/// Put the contents of `attachment` into it, so that they are sent along with it.
+pub fn pack(attachment: &mut Attachment) -> Result<(), String> {
+    let bytes = BLOBS.peek().get(&attachment.blob)
+        .map_err(|err| t!("attachment-store-failed", name = attachment.name.clone(), error = err.to_string()))?;
+
+    attachment.contents = Some(base64::engine::general_purpose::STANDARD.encode(bytes));
+
+    Ok(())
+}
+
+/// Move the contents that came along with `attachment` into the blob store, and refer to them there.
+pub fn unpack(attachment: &mut Attachment) -> Result<(), String> {
+    let Some(contents) = attachment.contents.take() else {
+        return Err(t!("attachment-unavailable", name = attachment.name.clone()));
+    };
+
+    let bytes = base64::engine::general_purpose::STANDARD.decode(contents)
+        .map_err(|_| t!("attachment-unavailable", name = attachment.name.clone()))?;
+
+    if bytes.len() != attachment.size as usize {
+        return Err(t!("attachment-unavailable", name = attachment.name.clone()));
+    }
+
+    attachment.blob = BLOBS.peek().put(&bytes)
+        .map_err(|err| t!("attachment-store-failed", name = attachment.name.clone(), error = err.to_string()))?;
+
+    Ok(())
+}
+
+/// Remove the contents of an attachment that was taken off a message before it was sent.
+pub fn discard(attachment: &Attachment) {
+    let _ = BLOBS.peek().delete(&attachment.blob);
+}
+
+/// A `data:` URL with the attachment contents, usable as an image source or a download link on every platform.
+pub fn data_url(attachment: &Attachment) -> Option<String> {
+    let bytes = BLOBS.peek().get(&attachment.blob).ok()?;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+
+    Some(format!("data:{};base64,{encoded}", attachment.mime))
+}
+
+/// Guess the mime type from the file extension for platforms that only give us a file name.
+pub fn guess_mime(name: &str) -> &'static str {
+    let extension = name.rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "txt" | "md" => "text/plain",
+        "json" => "application/json",
+        "zip" => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut value = size as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{size} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Sends the name, the type and the base64 contents of every file pasted into the composer. The paste events the app
+/// gets do not carry files on any platform, but the page of every platform has them.
+const PASTE_JS: &str = r#"
+    if (window.dxPaste) {
+        document.removeEventListener("paste", window.dxPaste);
+    }
+
+    window.dxPaste = async (event) => {
+        if (!event.target.closest?.("[data-composer]")) {
+            return;
+        }
+
+        // Taken before the first await, the clipboard data is emptied once the event is over.
+        const files = [...(event.clipboardData?.files ?? [])];
+
+        for (const file of files) {
+            const bytes = new Uint8Array(await file.arrayBuffer());
+            let binary = "";
+
+            for (let start = 0; start < bytes.length; start += 0x8000) {
+                binary += String.fromCharCode(...bytes.subarray(start, start + 0x8000));
+            }
+
+            dioxus.send([file.name, file.type, btoa(binary)]);
+        }
+    };
+
+    document.addEventListener("paste", window.dxPaste);
+
+    // Stays pending so that the channel to the app stays open.
+    await new Promise(() => {});
+"#;
+
+/// Run `on_file` with the name, the type and the contents of every file pasted into the composer, the element marked
+/// with `data-composer`. Called by the composer.
+pub fn use_pasted_files(on_file: impl FnMut((String, String, Vec<u8>)) + 'static) {
+    let on_file = use_callback(on_file);
+
+    use_future(move || {
+        let mut listener = document::eval(PASTE_JS);
+
+        async move {
+            while let Ok((name, mime, encoded)) = listener.recv::<(String, String, String)>().await {
+                match base64::engine::general_purpose::STANDARD.decode(encoded) {
+                    Ok(bytes) => on_file.call((name, mime, bytes)),
+                    Err(error) => dioxus::logger::tracing::warn!("The pasted file {name} could not be read: {error}"),
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directory_store_round_trip() {
+        let root = std::env::temp_dir().join(format!("dx-chat-blobs-{}", Uuid::new_v4()));
+        let store = DirectoryStore::new(&root);
+
+        let blob = store.put(b"hello").unwrap();
+        assert_eq!(store.get(&blob).unwrap(), b"hello");
+
+        store.delete(&blob).unwrap();
+        assert_eq!(store.get(&blob).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(store.delete(&blob).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn directory_store_missing_blob() {
+        let root = std::env::temp_dir().join(format!("dx-chat-blobs-{}", Uuid::new_v4()));
+        let store = DirectoryStore::new(&root);
+
+        let missing = BlobRef(Uuid::new_v4());
+        assert_eq!(store.get(&missing).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn directory_store_keeps_blobs_apart() {
+        let root = std::env::temp_dir().join(format!("dx-chat-blobs-{}", Uuid::new_v4()));
+        let store = DirectoryStore::new(&root);
+
+        let first = store.put(b"first").unwrap();
+        let second = store.put(b"second").unwrap();
+        assert_ne!(first, second);
+
+        store.delete(&first).unwrap();
+        assert_eq!(store.get(&second).unwrap(), b"second");
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
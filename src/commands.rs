@@ -0,0 +1,476 @@
+//! The commands module implements slash commands. A line typed into the composer that starts with `/` is parsed into
+//! a command name and its arguments and dispatched to the matching [`Command`] instead of being sent as a message.
+//! A line starting with `//` is sent as a regular message with the first slash removed.
+//!
+//! Messages posted by commands, notices included, are sent like any other message, with
+//! [`transport::send`](crate::transport::send). Notices are sent as the id of a Fluent message and its arguments,
+//! and translated by every member when they are shown.
+//!
+//! The built-in commands are registered when [`COMMANDS`] is first used, additional ones can be added with
+//! [`register`].
+
+use std::{collections::BTreeMap, rc::Rc};
+
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::crdt::InfoOp;
+use crate::i18n::t;
+use crate::{storage, transport};
+use crate::{room_id, Author, Message, MessageKind, Room, RoomInfo, Route, AUTHORS, ROOMS};
+
+/// What a command is being run against: the room whose composer it was typed into and the local user.
+pub struct CommandContext {
+    pub room: Uuid,
+    pub user: Signal<Author>,
+}
+
+/// What the composer should do after a command ran successfully.
+pub enum Outcome {
+    Done,
+    Navigate(Route),
+}
+
+pub type Handler = Rc<dyn Fn(&CommandContext, &str) -> Result<Outcome, String>>;
+pub type Completer = Rc<dyn Fn(&CommandContext, &str) -> Vec<String>>;
+
+#[derive(Clone)]
+pub struct Command {
+    pub name: String,
+    pub usage: String,
+    /// A message id that is translated when the description is shown, or the description itself.
+    pub description: String,
+    pub run: Handler,
+    pub complete: Option<Completer>,
+}
+
+impl Command {
+    pub fn new(
+        name: &str,
+        usage: &str,
+        description: &str,
+        run: impl Fn(&CommandContext, &str) -> Result<Outcome, String> + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            usage: usage.to_string(),
+            description: description.to_string(),
+            run: Rc::new(run),
+            complete: None,
+        }
+    }
+
+    /// Offer completions for the argument of the command. The completer receives the argument typed so far and
+    /// returns the full argument values that match it.
+    pub fn with_completion(mut self, complete: impl Fn(&CommandContext, &str) -> Vec<String> + 'static) -> Self {
+        self.complete = Some(Rc::new(complete));
+        self
+    }
+}
+
+/// A completion offered while typing a command. Picking it replaces the composer text with `completion`.
+#[derive(Clone, PartialEq)]
+pub struct Suggestion {
+    pub label: String,
+    pub detail: String,
+    pub completion: String,
+}
+
+#[derive(Default)]
+pub struct Commands(BTreeMap<String, Command>);
+
+impl Commands {
+    pub fn with_builtins() -> Self {
+        let mut commands = Self::default();
+
+        for command in builtins() {
+            commands.register(command);
+        }
+
+        commands
+    }
+
+    /// Add a command, replacing any command with the same name.
+    pub fn register(&mut self, command: Command) {
+        self.0.insert(command.name.clone(), command);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.0.get(name)
+    }
+
+    /// Run the command on `line`. Fails if the line is not a command, the command is unknown or the command itself
+    /// fails.
+    pub fn dispatch(&self, ctx: &CommandContext, line: &str) -> Result<Outcome, String> {
+        let Some((name, args)) = parse(line) else {
+            return Err(t!("command-not-a-command"));
+        };
+
+        let Some(command) = self.get(name) else {
+            return Err(t!("command-unknown", name = name));
+        };
+
+        (command.run)(ctx, args)
+    }
+
+    /// The completions for a partially typed command line: command names while the name is being typed and the
+    /// command's argument completions after it.
+    pub fn suggestions(&self, ctx: &CommandContext, line: &str) -> Vec<Suggestion> {
+        let Some((name, args)) = parse(line) else {
+            return Vec::new();
+        };
+
+        if !line.contains(char::is_whitespace) {
+            return self.0.values()
+                .filter(|command| command.name.starts_with(name))
+                .map(|command| Suggestion {
+                    label: command.usage.clone(),
+                    detail: t!(&command.description),
+                    completion: format!("/{} ", command.name),
+                })
+                .collect();
+        }
+
+        let Some(command) = self.get(name) else {
+            return Vec::new();
+        };
+
+        let Some(complete) = &command.complete else {
+            return Vec::new();
+        };
+
+        complete(ctx, args).into_iter()
+            .filter(|value| value != args)
+            .map(|value| Suggestion {
+                completion: format!("/{name} {value}"),
+                label: value,
+                detail: t!(&command.description),
+            })
+            .collect()
+    }
+}
+
+pub static COMMANDS: GlobalSignal<Commands> = Global::new(Commands::with_builtins);
+
+/// Register a custom command so that it can be used from every room's composer.
+#[allow(dead_code)]
+pub fn register(command: Command) {
+    COMMANDS.write().register(command);
+}
+
+/// Split a command line into the command name and its trimmed arguments. Returns `None` for lines that are not
+/// commands, including `//` escaped lines.
+pub fn parse(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix('/')?;
+
+    if rest.starts_with('/') {
+        return None;
+    }
+
+    let (name, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+    Some((name, args.trim()))
+}
+
+fn room(ctx: &CommandContext) -> Result<Room, String> {
+    ROOMS.peek()
+        .get(&ctx.room)
+        .copied()
+        .ok_or_else(|| t!("room-gone"))
+}
+
+fn post(ctx: &CommandContext, content: String, kind: MessageKind) -> Result<(), String> {
+    let author = ctx.user.peek().id;
+
+    transport::send(room(ctx)?, Message::new(author, content, kind));
+
+    Ok(())
+}
+
+fn notice(ctx: &CommandContext, id: &str, args: &[(&str, &str)]) -> Result<(), String> {
+    let author = ctx.user.peek().id;
+
+    transport::send(room(ctx)?, Message::notice(author, id, args));
+
+    Ok(())
+}
+
+fn builtins() -> Vec<Command> {
+    vec![
+        Command::new("me", "/me <action>", "command-me", |ctx, args| {
+            if args.is_empty() {
+                return Err(t!("command-usage", usage = "/me <action>"));
+            }
+
+            post(ctx, args.to_string(), MessageKind::Emote)?;
+
+            Ok(Outcome::Done)
+        }),
+        Command::new("shrug", "/shrug [message]", "command-shrug", |ctx, args| {
+            let content = format!("{args} ¯\\_(ツ)_/¯").trim_start().to_string();
+
+            post(ctx, content, MessageKind::Text)?;
+
+            Ok(Outcome::Done)
+        }),
+        Command::new("topic", "/topic [topic]", "command-topic", |ctx, args| {
+            let name = ctx.user.peek().username.clone();
+
+            room(ctx)?.change_info(ctx.user.peek().id, InfoOp::Topic((!args.is_empty()).then(|| args.to_string())));
+
+            if args.is_empty() {
+                notice(ctx, "notice-topic-cleared", &[("name", &name)])?;
+            } else {
+                notice(ctx, "notice-topic-changed", &[("name", &name), ("topic", args)])?;
+            }
+
+            Ok(Outcome::Done)
+        }),
+        Command::new("join", "/join <room>", "command-join", |ctx, args| {
+            if args.is_empty() {
+                return Err(t!("command-usage", usage = "/join <room>"));
+            }
+
+            let (user, name) = {
+                let user = ctx.user.peek();
+                (user.id, user.username.clone())
+            };
+
+            let existing = ROOMS.peek().values()
+                .find(|room| room.info.peek().name.eq_ignore_ascii_case(args))
+                .copied();
+
+            let room = existing.unwrap_or_else(|| {
+                let room = Room::new(room_id(args), RoomInfo::new(args.to_string(), None, Default::default()));
+
+                ROOMS.write().insert(room.id, room);
+
+                room
+            });
+
+            if !room.info.peek().members.contains(&user) {
+                room.change_info(user, InfoOp::Member { user, joined: true });
+                transport::send(room, Message::notice(user, "notice-joined", &[("name", &name)]));
+            }
+
+            Ok(Outcome::Navigate(Route::Chat { id: room.id }))
+        })
+        .with_completion(|_, args| {
+            ROOMS.read().values()
+                .map(|room| room.info.read().name.clone())
+                .filter(|name| starts_with_ignore_case(name, args))
+                .collect()
+        }),
+        Command::new("leave", "/leave", "command-leave", |ctx, _| {
+            let name = ctx.user.peek().username.clone();
+
+            notice(ctx, "notice-left", &[("name", &name)])?;
+
+            let user = ctx.user.peek().id;
+            room(ctx)?.change_info(user, InfoOp::Member { user, joined: false });
+
+            Ok(Outcome::Navigate(Route::Home {}))
+        }),
+        Command::new("nick", "/nick <name>", "command-nick", |ctx, args| {
+            if args.is_empty() {
+                return Err(t!("command-usage", usage = "/nick <name>"));
+            }
+
+            let mut user = ctx.user;
+            let old = std::mem::replace(&mut user.write().username, args.to_string());
+            let id = user.peek().id;
+
+            storage::save("username", &args);
+
+            // The other members see the new name in every room they share with the user.
+            let rooms: Vec<Room> = ROOMS.peek().values()
+                .filter(|room| room.info.peek().members.contains(&id))
+                .copied()
+                .collect();
+
+            for room in rooms {
+                room.change_info(id, InfoOp::Name { user: id, name: args.to_string() });
+            }
+
+            notice(ctx, "notice-nick", &[("old", &old), ("new", args)])?;
+
+            Ok(Outcome::Done)
+        }),
+        Command::new("invite", "/invite <user>", "command-invite", |ctx, args| {
+            if args.is_empty() {
+                return Err(t!("command-usage", usage = "/invite <user>"));
+            }
+
+            let Some(invitee) = AUTHORS.read().values()
+                .find(|author| author.username.eq_ignore_ascii_case(args))
+                .map(|author| (author.id, author.username.clone()))
+            else {
+                return Err(t!("user-not-found", name = args));
+            };
+
+            let room = room(ctx)?;
+
+            if room.info.peek().members.contains(&invitee.0) {
+                return Err(t!("already-in-room", name = invitee.1));
+            }
+
+            room.change_info(ctx.user.peek().id, InfoOp::Member { user: invitee.0, joined: true });
+
+            let name = ctx.user.peek().username.clone();
+
+            notice(ctx, "notice-invited", &[("name", &name), ("invitee", &invitee.1)])?;
+
+            Ok(Outcome::Done)
+        })
+        .with_completion(|ctx, args| {
+            let members = room(ctx).map(|room| room.info.read().members.clone()).unwrap_or_default();
+
+            AUTHORS.read().values()
+                .filter(|author| !members.contains(&author.id))
+                .filter(|author| starts_with_ignore_case(&author.username, args))
+                .map(|author| author.username.clone())
+                .collect()
+        }),
+    ]
+}
+
+fn starts_with_ignore_case(value: &str, prefix: &str) -> bool {
+    value.to_lowercase().starts_with(&prefix.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::{Language, LANGUAGE};
+    use crate::outbox::OUTBOX;
+    use crate::testing;
+    use crate::transport::Delivery;
+
+    #[test]
+    fn parses_command_lines() {
+        assert_eq!(parse("/me  waves  "), Some(("me", "waves")));
+        assert_eq!(parse("/leave"), Some(("leave", "")));
+        assert_eq!(parse("//not a command"), None);
+        assert_eq!(parse("hello"), None);
+    }
+
+    /// Run `line` in a new room as Anakin, who writes English, and return the messages it posted as they read in
+    /// `language`.
+    fn run(language: Language, line: &str) -> Vec<String> {
+        testing::runtime().run(|| {
+            *LANGUAGE.write() = Language::English;
+
+            let user = Signal::new_in_scope(
+                Author { id: Uuid::new_v4(), username: "Anakin".into(), avatar: None, bio: None },
+                ScopeId::ROOT,
+            );
+            let room = Room::new(room_id("Starwars"), RoomInfo::new("Starwars".into(), None, Default::default()));
+            ROOMS.write().insert(room.id, room);
+
+            let ctx = CommandContext { room: room.id, user };
+            assert!(Commands::with_builtins().dispatch(&ctx, line).is_ok());
+
+            *LANGUAGE.write() = language;
+
+            let texts = room.messages.peek().values().map(Message::text).collect();
+            texts
+        })
+    }
+
+    #[test]
+    fn notices_are_translated_when_read() {
+        assert_eq!(run(Language::English, "/topic Sand"), [r#"Anakin changed the topic to "Sand""#]);
+        assert_eq!(run(Language::Finnish, "/topic"), ["Anakin tyhjensi aiheen"]);
+        assert_eq!(run(Language::English, "/nick Vader"), ["Anakin is now known as Vader"]);
+        assert_eq!(run(Language::Arabic, "/leave"), ["غادر Anakin الغرفة"]);
+        assert_eq!(run(Language::Finnish, "/me waves"), ["waves"]);
+    }
+
+    #[test]
+    fn notices_are_sent_untranslated() {
+        let notice = testing::runtime()
+            .run(|| Message::notice(Uuid::new_v4(), "notice-nick", &[("old", "Anakin"), ("new", "Vader")]));
+        let sent: Message = serde_json::from_str(&serde_json::to_string(&notice).unwrap()).unwrap();
+
+        assert_eq!(sent.content, "notice-nick");
+        assert_eq!(sent.args, BTreeMap::from([("new".into(), "Vader".into()), ("old".into(), "Anakin".into())]));
+    }
+
+    #[test]
+    fn nick_is_kept_and_sent() {
+        testing::runtime().run(|| {
+            let user = Signal::new_in_scope(
+                Author { id: Uuid::new_v4(), username: "Anakin".into(), avatar: None, bio: None },
+                ScopeId::ROOT,
+            );
+            let id = user.peek().id;
+            let rooms = ["Starwars", "Batman"].map(|name| {
+                let room = Room::new(room_id(name), RoomInfo::new(name.into(), None, [id].into()));
+                ROOMS.write().insert(room.id, room);
+                room
+            });
+            let elsewhere = Room::new(room_id("Hulk"), RoomInfo::new("Hulk".into(), None, Default::default()));
+            ROOMS.write().insert(elsewhere.id, elsewhere);
+
+            let ctx = CommandContext { room: rooms[0].id, user };
+            assert!(Commands::with_builtins().dispatch(&ctx, "/nick Vader").is_ok());
+
+            assert_eq!(user.peek().username, "Vader");
+            assert_eq!(storage::load::<String>("username").as_deref(), Some("Vader"));
+
+            for room in rooms {
+                assert_eq!(room.info.peek().names.get(&id).map(String::as_str), Some("Vader"));
+            }
+
+            assert!(elsewhere.info.peek().names.is_empty());
+            // The name in both rooms and the notice.
+            assert_eq!(OUTBOX.peek().len(), 3);
+        });
+    }
+
+    #[test]
+    fn posts_are_sent() {
+        testing::runtime().run(|| {
+            let user = Signal::new_in_scope(
+                Author { id: Uuid::new_v4(), username: "Anakin".into(), avatar: None, bio: None },
+                ScopeId::ROOT,
+            );
+            let ctx = CommandContext { room: room_id("Starwars"), user };
+            let commands = Commands::with_builtins();
+
+            assert!(commands.dispatch(&ctx, "/join Starwars").is_ok());
+            assert!(commands.dispatch(&ctx, "/me waves").is_ok());
+
+            let room = ROOMS.peek()[&room_id("Starwars")];
+            let messages = room.messages.peek();
+            let outbox = OUTBOX.peek();
+
+            // Joining is a change of its own.
+            assert_eq!(messages.len(), 2);
+            assert_eq!(outbox.len(), 3);
+
+            for message in messages.values() {
+                assert_eq!(message.delivery, Delivery::Pending);
+                assert!(outbox.iter().any(|outgoing| outgoing.envelope.client_id == message.client_id));
+            }
+        });
+    }
+
+    #[test]
+    fn reports_usage_and_unknown_commands() {
+        testing::runtime().run(|| {
+            *LANGUAGE.write() = Language::English;
+
+            let user = Signal::new_in_scope(
+                Author { id: Uuid::new_v4(), username: "Anakin".into(), avatar: None, bio: None },
+                ScopeId::ROOT,
+            );
+            let ctx = CommandContext { room: Uuid::new_v4(), user };
+            let commands = Commands::with_builtins();
+
+            assert_eq!(commands.dispatch(&ctx, "/me").err().as_deref(), Some("Usage: /me <action>"));
+            assert_eq!(commands.dispatch(&ctx, "/dance").err().as_deref(), Some("Unknown command /dance"));
+            assert_eq!(commands.dispatch(&ctx, "/shrug").err().as_deref(), Some("The room no longer exists"));
+        });
+    }
+}
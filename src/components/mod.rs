@@ -3,3 +3,6 @@
 //! component  to be used in our app.
 
 
+
+mod presence;
+pub use presence::PresenceDot;
@@ -0,0 +1,27 @@
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::i18n::t;
+use crate::presence;
+
+/// A dot in the corner of an avatar with the color of the user's status. The avatar needs to be positioned, like
+/// daisyUI's `avatar` is. Re-renders only when the user's presence changes.
+#[component]
+pub fn PresenceDot(user: Uuid) -> Element {
+    let presence = use_memo(move || presence::of(user));
+    let presence = presence.read();
+
+    let label = match &presence.text {
+        Some(text) => format!("{}: {text}", t!(presence.status.label())),
+        None => t!(presence.status.label()),
+    };
+
+    rsx! {
+        span {
+            class: "absolute bottom-0 end-0 w-3 h-3 rounded-full ring-2 ring-base-100 {presence.status.color()}",
+            role: "img",
+            title: "{label}",
+            aria_label: "{label}",
+        }
+    }
+}
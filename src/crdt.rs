@@ -0,0 +1,627 @@
+//! The crdt module lets the same room be changed on several devices at once, even while they are offline, and
+//! merges the changes so that every device ends up with the same room no matter in which order they arrive.
+//!
+//! Every change is an operation stamped with a [`Stamp`] from a hybrid logical clock: the wall time, a counter for
+//! changes within the same millisecond and the replica, the device, that made it. Stamps order every change the same
+//! way on every replica. A room keeps the operations it has applied in a log and derives its state from them:
+//!
+//! - Messages are added once, posting a message that is already there does nothing.
+//! - The content of a message, the topic of a room, whether someone is a member and the name they go by are
+//!   registers in which the write with the greatest stamp wins.
+//! - Reactions are such a register for every emoji and user.
+//! - Hidden link previews stay hidden.
+//!
+//! Only the author of a message edits it or hides its previews. The edits and hides of anyone else are kept in the
+//! log, so that every replica has the same one, but never change the message. Who may change a room at all is up to
+//! its members, see [`RoomInfo::allows`].
+//!
+//! Operations on messages that have not arrived yet are kept in the log and applied when the message arrives, so
+//! applying the operations of another replica in any order gives the same [`Messages`] and [`RoomInfo`]. Merging
+//! two replicas is applying each other's operations: [`Messages::ops`] and [`RoomInfo::ops`] export them, and
+//! [`Messages::merge`] and [`RoomInfo::merge`] apply those of another replica.
+//!
+//! A change made on this replica is a [`Change`], which is sent to the other members of the room like a message, see
+//! [`transport::change`](crate::transport::change).
+
+use std::collections::{btree_map, BTreeMap};
+
+use chrono::{DateTime, Utc};
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::transport::Delivery;
+use crate::{storage, Message, RoomInfo};
+
+/// When, and where, a change was made. The default stamp is the one of the state a room is created with, every
+/// change comes after it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Stamp {
+    /// Milliseconds since the Unix epoch, as far as the clocks of the replicas agree.
+    pub millis: i64,
+    /// Orders the stamps made within the same millisecond.
+    pub counter: u32,
+    /// The replica that made the stamp, which breaks the remaining ties.
+    pub replica: Uuid,
+}
+
+/// This replica. Every installation of the app, desktop or web, is a replica of its own.
+pub static REPLICA: GlobalSignal<Uuid> = Global::new(|| {
+    storage::load("replica").unwrap_or_else(|| {
+        let replica = Uuid::new_v4();
+        storage::save("replica", &replica);
+        replica
+    })
+});
+
+/// The greatest stamp made or seen by this replica.
+static LATEST: GlobalSignal<Stamp> = Global::new(Stamp::default);
+
+impl Stamp {
+    /// A new stamp, greater than any made or seen before even if the wall clock went back.
+    pub fn now() -> Self {
+        let latest = *LATEST.peek();
+        let millis = Utc::now().timestamp_millis();
+
+        let stamp = if millis > latest.millis {
+            Stamp { millis, counter: 0, replica: *REPLICA.peek() }
+        } else {
+            Stamp { millis: latest.millis, counter: latest.counter + 1, replica: *REPLICA.peek() }
+        };
+
+        *LATEST.write() = stamp;
+
+        stamp
+    }
+
+    /// The stamp of a change made at `time` by this replica, for messages written before the clock existed.
+    pub fn at(time: DateTime<Utc>) -> Self {
+        Stamp { millis: time.timestamp_millis(), counter: 0, replica: *REPLICA.peek() }
+    }
+
+    /// The wall time of the stamp.
+    pub fn time(self) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(self.millis).unwrap_or_default()
+    }
+}
+
+/// Move the clock past a stamp made by another replica, so that changes made here after seeing it come after it.
+pub fn observe(stamp: Stamp) {
+    if stamp > *LATEST.peek() {
+        *LATEST.write() = stamp;
+    }
+}
+
+/// A change to the messages of a room.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MessageOp {
+    Post(Box<Message>),
+    Edit { message: Stamp, author: Uuid, content: String },
+    React { message: Stamp, user: Uuid, emoji: String, on: bool },
+    HidePreviews { message: Stamp, author: Uuid },
+}
+
+impl MessageOp {
+    /// The message the operation changes.
+    pub fn target(&self) -> Stamp {
+        match self {
+            MessageOp::Post(message) => message.id,
+            MessageOp::Edit { message, .. }
+            | MessageOp::React { message, .. }
+            | MessageOp::HidePreviews { message, .. } => *message,
+        }
+    }
+}
+
+/// A change to the [`RoomInfo`] of a room.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InfoOp {
+    Topic(Option<String>),
+    Member { user: Uuid, joined: bool },
+    /// The name `user` goes by, which they set with `/nick` in every room they are a member of.
+    Name { user: Uuid, name: String },
+}
+
+/// A change to a room, to its messages or its info.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RoomOp {
+    Message(MessageOp),
+    Info(InfoOp),
+}
+
+impl From<MessageOp> for RoomOp {
+    fn from(op: MessageOp) -> Self {
+        RoomOp::Message(op)
+    }
+}
+
+impl From<InfoOp> for RoomOp {
+    fn from(op: InfoOp) -> Self {
+        RoomOp::Info(op)
+    }
+}
+
+/// An operation as it is sent to the other replicas: with its stamp, the user who made it, and an id picked by the
+/// client that made it, so that sending it more than once does not duplicate it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub room: Uuid,
+    pub stamp: Stamp,
+    pub author: Uuid,
+    pub client_id: Uuid,
+    pub op: RoomOp,
+}
+
+impl Change {
+    /// A change `author` makes to `room` now.
+    pub fn new(room: Uuid, author: Uuid, op: RoomOp) -> Self {
+        Self { room, stamp: Stamp::now(), author, client_id: Uuid::new_v4(), op }
+    }
+
+    /// The change that posts `message` to `room`.
+    pub fn post(room: Uuid, message: &Message) -> Self {
+        Self {
+            room,
+            stamp: message.id,
+            author: message.author,
+            client_id: message.client_id,
+            op: RoomOp::Message(MessageOp::Post(Box::new(message.clone()))),
+        }
+    }
+}
+
+/// Who reacted to a message with what. For every emoji and user, the reaction with the greatest stamp wins.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Reactions(BTreeMap<(String, Uuid), (Stamp, bool)>);
+
+impl Reactions {
+    fn set(&mut self, emoji: String, user: Uuid, on: bool, stamp: Stamp) {
+        let current = self.0.entry((emoji, user)).or_insert((stamp, on));
+
+        if stamp > current.0 {
+            *current = (stamp, on);
+        }
+    }
+
+    /// Whether `user` reacted with `emoji`.
+    pub fn contains(&self, emoji: &str, user: Uuid) -> bool {
+        self.0.get(&(emoji.to_string(), user)).is_some_and(|(_, on)| *on)
+    }
+
+    /// Every emoji reacted with, how many reacted with it and whether `user` is one of them.
+    pub fn summary(&self, user: Uuid) -> Vec<(String, usize, bool)> {
+        let mut summary: Vec<(String, usize, bool)> = Vec::new();
+
+        for ((emoji, reactor), _) in self.0.iter().filter(|(_, (_, on))| *on) {
+            match summary.last_mut() {
+                Some((last, count, mine)) if last == emoji => {
+                    *count += 1;
+                    *mine |= *reactor == user;
+                }
+                _ => summary.push((emoji.clone(), 1, *reactor == user)),
+            }
+        }
+
+        summary
+    }
+}
+
+/// The messages of a room, derived from the operations applied to it. Read like a map from message id to message,
+/// in the order of the ids.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Messages {
+    log: BTreeMap<Stamp, MessageOp>,
+    messages: BTreeMap<Stamp, Message>,
+}
+
+impl Messages {
+    /// Apply the operation with the given stamp. Returns whether it was new.
+    pub fn apply(&mut self, stamp: Stamp, op: MessageOp) -> bool {
+        let btree_map::Entry::Vacant(entry) = self.log.entry(stamp) else {
+            return false;
+        };
+
+        let op = entry.insert(op);
+
+        match op {
+            MessageOp::Post(message) => {
+                if self.messages.contains_key(&message.id) {
+                    return false;
+                }
+
+                let id = message.id;
+                let mut message = Message::clone(message);
+
+                // Catch up with the operations that arrived before the message.
+                for (stamp, op) in self.log.iter().filter(|(_, op)| op.target() == id) {
+                    change(&mut message, *stamp, op);
+                }
+
+                self.messages.insert(id, message);
+            }
+            op => {
+                if let Some(message) = self.messages.get_mut(&op.target()) {
+                    change(message, stamp, op);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// The operations applied so far, in the order of their stamps.
+    pub fn ops(&self) -> btree_map::Iter<'_, Stamp, MessageOp> {
+        self.log.iter()
+    }
+
+    /// Apply the operations of another replica. Returns whether any of them was new.
+    pub fn merge(&mut self, other: &Messages) -> bool {
+        other.ops().fold(false, |new, (stamp, op)| self.apply(*stamp, op.clone()) || new)
+    }
+
+    /// Set how far a message of the user got. Delivery is kept by this replica only.
+    pub fn set_delivery(&mut self, id: Stamp, delivery: Delivery) {
+        if let Some(message) = self.messages.get_mut(&id) {
+            message.delivery = delivery;
+        }
+    }
+
+    pub fn get(&self, id: &Stamp) -> Option<&Message> {
+        self.messages.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn keys(&self) -> btree_map::Keys<'_, Stamp, Message> {
+        self.messages.keys()
+    }
+
+    pub fn values(&self) -> btree_map::Values<'_, Stamp, Message> {
+        self.messages.values()
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<'_, Stamp, Message> {
+        self.messages.iter()
+    }
+
+    pub fn last_key_value(&self) -> Option<(&Stamp, &Message)> {
+        self.messages.last_key_value()
+    }
+}
+
+/// Apply an operation other than posting to its message. Edits and hides by anyone but the author change nothing.
+fn change(message: &mut Message, stamp: Stamp, op: &MessageOp) {
+    match op {
+        MessageOp::Post(_) => {}
+        MessageOp::Edit { author, .. } | MessageOp::HidePreviews { author, .. } if *author != message.author => {}
+        MessageOp::Edit { content, .. } => {
+            if message.edited.is_none_or(|edited| stamp > edited) {
+                message.content = content.clone();
+                message.edited = Some(stamp);
+            }
+        }
+        MessageOp::React { user, emoji, on, .. } => message.reactions.set(emoji.clone(), *user, *on, stamp),
+        MessageOp::HidePreviews { .. } => message.link_previews = false,
+    }
+}
+
+/// The stamps of the writes that decided the [`RoomInfo`] of a room.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InfoStamps {
+    topic: Stamp,
+    members: BTreeMap<Uuid, Stamp>,
+    names: BTreeMap<Uuid, Stamp>,
+}
+
+impl RoomInfo {
+    /// Whether `author` may make the change `op` to the room. Members may change anything, within what
+    /// [`Messages`] allows. Anyone may join, rooms are open to everyone who knows them, but only members invite others
+    /// and nobody removes or renames anyone but themselves.
+    pub fn allows(&self, author: Uuid, op: &RoomOp) -> bool {
+        match op {
+            RoomOp::Info(InfoOp::Member { user, joined: true }) if *user == author => true,
+            RoomOp::Info(InfoOp::Member { user, joined: false } | InfoOp::Name { user, .. }) if *user != author => false,
+            _ => self.members.contains(&author),
+        }
+    }
+
+    /// Apply the operation with the given stamp. Returns whether it changed the room info.
+    pub fn apply(&mut self, stamp: Stamp, op: InfoOp) -> bool {
+        match op {
+            InfoOp::Topic(topic) => {
+                if stamp <= self.stamps.topic {
+                    return false;
+                }
+
+                self.stamps.topic = stamp;
+                self.topic = topic;
+            }
+            InfoOp::Member { user, joined } => {
+                let current = self.stamps.members.entry(user).or_default();
+
+                if stamp <= *current {
+                    return false;
+                }
+
+                *current = stamp;
+
+                if joined {
+                    self.members.insert(user);
+                } else {
+                    self.members.remove(&user);
+                }
+            }
+            InfoOp::Name { user, name } => {
+                let current = self.stamps.names.entry(user).or_default();
+
+                if stamp <= *current {
+                    return false;
+                }
+
+                *current = stamp;
+                self.names.insert(user, name);
+            }
+        }
+
+        true
+    }
+
+    /// The writes that decided the info, as operations. Writes that lost to a later one are gone, they would not
+    /// change anything anyway.
+    pub fn ops(&self) -> Vec<(Stamp, InfoOp)> {
+        let topic = (self.stamps.topic != Stamp::default())
+            .then(|| (self.stamps.topic, InfoOp::Topic(self.topic.clone())));
+        let members = self.stamps.members.iter()
+            .map(|(user, stamp)| (*stamp, InfoOp::Member { user: *user, joined: self.members.contains(user) }));
+        let names = self.names.iter()
+            .map(|(user, name)| (self.stamps.names[user], InfoOp::Name { user: *user, name: name.clone() }));
+
+        topic.into_iter().chain(members).chain(names).collect()
+    }
+
+    /// Apply the writes of another replica. Returns whether any of them changed the room info.
+    pub fn merge(&mut self, other: &RoomInfo) -> bool {
+        other.ops().into_iter().fold(false, |changed, (stamp, op)| self.apply(stamp, op) || changed)
+    }
+}
+
+/// Property tests: replicas that applied the same operations, in whatever order and however often, are the same.
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::MessageKind;
+
+    /// What the generated operations are made of. There are few of each, so that operations often meet on the same
+    /// message, emoji or user.
+    const REPLICAS: [Uuid; 2] = [Uuid::from_u128(1), Uuid::from_u128(2)];
+    const USERS: [Uuid; 3] = [Uuid::from_u128(10), Uuid::from_u128(11), Uuid::from_u128(12)];
+    const EMOJI: [&str; 2] = ["👍", "🎉"];
+    const MESSAGES: u32 = 3;
+    const MAX_OPS: usize = 30;
+
+    /// The id of message `index`, the stamp of its post.
+    fn id(index: u32) -> Stamp {
+        Stamp { millis: index.into(), counter: 0, replica: REPLICAS[0] }
+    }
+
+    fn message(index: u32) -> Message {
+        Message {
+            id: id(index),
+            author: USERS[0],
+            sent: DateTime::from_timestamp_millis(index.into()).unwrap(),
+            content: format!("Message {index}"),
+            kind: MessageKind::Text,
+            args: BTreeMap::new(),
+            attachments: Vec::new(),
+            link_previews: true,
+            edited: None,
+            reactions: Reactions::default(),
+            delivery: Delivery::default(),
+            client_id: Uuid::from_u128(index.into()),
+        }
+    }
+
+    /// Stamps `ops`. Every operation gets a stamp of its own, except posts, which are stamped with the id of their
+    /// message however often they are sent.
+    fn stamped<Op>(ops: Vec<(i64, usize, Op)>, post: impl Fn(&Op) -> Option<Stamp>) -> Vec<(Stamp, Op)> {
+        ops.into_iter()
+            .enumerate()
+            .map(|(index, (millis, replica, op))| {
+                let stamp = Stamp { millis, counter: index as u32 + 1, replica: REPLICAS[replica] };
+                (post(&op).unwrap_or(stamp), op)
+            })
+            .collect()
+    }
+
+    fn message_ops() -> impl Strategy<Value = Vec<(Stamp, MessageOp)>> {
+        let target = 0..MESSAGES;
+        let op = prop_oneof![
+            target.clone().prop_map(|index| MessageOp::Post(Box::new(message(index)))),
+            (target.clone(), 0..USERS.len(), "[ab]{1,2}").prop_map(|(index, author, content)| {
+                MessageOp::Edit { message: id(index), author: USERS[author], content }
+            }),
+            (target.clone(), 0..USERS.len(), 0..EMOJI.len(), any::<bool>()).prop_map(|(index, user, emoji, on)| {
+                MessageOp::React { message: id(index), user: USERS[user], emoji: EMOJI[emoji].into(), on }
+            }),
+            (target, 0..USERS.len()).prop_map(|(index, author)| {
+                MessageOp::HidePreviews { message: id(index), author: USERS[author] }
+            }),
+        ];
+
+        prop::collection::vec((0..10i64, 0..REPLICAS.len(), op), 0..MAX_OPS).prop_map(|ops| {
+            stamped(ops, |op| match op {
+                MessageOp::Post(message) => Some(message.id),
+                _ => None,
+            })
+        })
+    }
+
+    fn info_ops() -> impl Strategy<Value = Vec<(Stamp, InfoOp)>> {
+        let op = prop_oneof![
+            proptest::option::of("[ab]{1,2}").prop_map(InfoOp::Topic),
+            (0..USERS.len(), any::<bool>()).prop_map(|(user, joined)| InfoOp::Member { user: USERS[user], joined }),
+            (0..USERS.len(), "[ab]{1,2}").prop_map(|(user, name)| InfoOp::Name { user: USERS[user], name }),
+        ];
+
+        prop::collection::vec((0..10i64, 0..REPLICAS.len(), op), 0..MAX_OPS).prop_map(|ops| stamped(ops, |_| None))
+    }
+
+    fn messages(ops: &[(Stamp, MessageOp)]) -> Messages {
+        let mut messages = Messages::default();
+
+        for (stamp, op) in ops {
+            messages.apply(*stamp, op.clone());
+        }
+
+        messages
+    }
+
+    fn info(ops: &[(Stamp, InfoOp)]) -> RoomInfo {
+        let mut info = RoomInfo::new("Starwars".into(), None, BTreeSet::from([USERS[0]]));
+
+        for (stamp, op) in ops {
+            info.apply(*stamp, op.clone());
+        }
+
+        info
+    }
+
+    /// `ops` in their order and shuffled.
+    fn shuffled<T>(ops: impl Strategy<Value = Vec<T>>) -> impl Strategy<Value = (Vec<T>, Vec<T>)>
+    where
+        T: Clone + std::fmt::Debug,
+    {
+        ops.prop_flat_map(|ops| (Just(ops.clone()), Just(ops).prop_shuffle()))
+    }
+
+    /// Which of two replicas makes each operation.
+    fn replicas() -> impl Strategy<Value = Vec<bool>> {
+        prop::collection::vec(any::<bool>(), MAX_OPS)
+    }
+
+    /// `ops` split between the replicas the way `on_first` says.
+    fn split<T: Clone>(ops: &[T], on_first: &[bool]) -> (Vec<T>, Vec<T>) {
+        let (first, second): (Vec<_>, Vec<_>) = ops.iter().zip(on_first).partition(|(_, first)| **first);
+
+        (first.into_iter().map(|(op, _)| op.clone()).collect(), second.into_iter().map(|(op, _)| op.clone()).collect())
+    }
+
+    proptest! {
+        #[test]
+        fn message_ops_commute((ops, shuffled) in shuffled(message_ops())) {
+            prop_assert_eq!(messages(&ops), messages(&shuffled));
+        }
+
+        #[test]
+        fn message_ops_are_idempotent(ops in message_ops()) {
+            let twice: Vec<_> = ops.iter().chain(&ops).cloned().collect();
+
+            prop_assert_eq!(messages(&ops), messages(&twice));
+        }
+
+        #[test]
+        fn messages_converge(ops in message_ops(), on_first in replicas()) {
+            let (first, second) = split(&ops, &on_first);
+            let (mut first, mut second) = (messages(&first), messages(&second));
+
+            first.merge(&second);
+            second.merge(&first);
+
+            prop_assert_eq!(&first, &second);
+            prop_assert_eq!(first, messages(&ops));
+        }
+
+        #[test]
+        fn info_ops_commute((ops, shuffled) in shuffled(info_ops())) {
+            prop_assert_eq!(info(&ops), info(&shuffled));
+        }
+
+        #[test]
+        fn info_ops_are_idempotent(ops in info_ops()) {
+            let twice: Vec<_> = ops.iter().chain(&ops).cloned().collect();
+
+            prop_assert_eq!(info(&ops), info(&twice));
+        }
+
+        #[test]
+        fn info_converges(ops in info_ops(), on_first in replicas()) {
+            let (first, second) = split(&ops, &on_first);
+            let (mut first, mut second) = (info(&first), info(&second));
+
+            first.merge(&second);
+            second.merge(&first);
+
+            prop_assert_eq!(&first, &second);
+            prop_assert_eq!(first, info(&ops));
+        }
+    }
+
+    #[test]
+    fn the_latest_reaction_wins() {
+        let (early, late) = (Stamp { millis: 1, ..id(0) }, Stamp { millis: 2, ..id(0) });
+        let mut reactions = Reactions::default();
+
+        reactions.set("👍".into(), USERS[1], false, late);
+        reactions.set("👍".into(), USERS[1], true, early);
+        reactions.set("👍".into(), USERS[2], true, early);
+
+        assert!(!reactions.contains("👍", USERS[1]));
+        assert_eq!(reactions.summary(USERS[2]), [("👍".to_string(), 1, true)]);
+    }
+
+    #[test]
+    fn only_authors_change_their_messages() {
+        let stamp = |millis| Stamp { millis, counter: 0, replica: REPLICAS[1] };
+        let forged = |message| MessageOp::Edit { message, author: USERS[1], content: "Forged".into() };
+        let hide = |message| MessageOp::HidePreviews { message, author: USERS[1] };
+        let mut messages = Messages::default();
+
+        // Held until the message arrives, and checked then.
+        messages.apply(stamp(10), forged(id(0)));
+        messages.apply(stamp(11), hide(id(0)));
+        messages.apply(id(0), MessageOp::Post(Box::new(message(0))));
+
+        messages.apply(id(1), MessageOp::Post(Box::new(message(1))));
+        messages.apply(stamp(12), forged(id(1)));
+        messages.apply(stamp(13), hide(id(1)));
+        messages.apply(stamp(14), MessageOp::Edit { message: id(1), author: USERS[0], content: "Edited".into() });
+
+        assert_eq!(messages.get(&id(0)), Some(&message(0)));
+        assert_eq!(messages.get(&id(1)).map(|message| message.content.as_str()), Some("Edited"));
+        assert!(messages.get(&id(1)).is_some_and(|message| message.link_previews));
+    }
+
+    #[test]
+    fn members_change_rooms() {
+        let info = RoomInfo::new("Starwars".into(), None, BTreeSet::from([USERS[0]]));
+        let topic = RoomOp::Info(InfoOp::Topic(Some("Sand".into())));
+        let member = |user, joined| RoomOp::Info(InfoOp::Member { user, joined });
+        let name = |user| RoomOp::Info(InfoOp::Name { user, name: "Vader".into() });
+        let post = RoomOp::Message(MessageOp::Post(Box::new(message(0))));
+
+        let cases = [
+            (USERS[0], &topic, true),
+            (USERS[1], &topic, false),
+            (USERS[0], &post, true),
+            (USERS[1], &post, false),
+            (USERS[1], &member(USERS[1], true), true),
+            (USERS[0], &member(USERS[1], true), true),
+            (USERS[1], &member(USERS[2], true), false),
+            (USERS[0], &member(USERS[0], false), true),
+            (USERS[0], &member(USERS[1], false), false),
+            (USERS[0], &name(USERS[0]), true),
+            (USERS[0], &name(USERS[1]), false),
+            (USERS[1], &name(USERS[1]), false),
+        ];
+
+        for (author, op, allowed) in cases {
+            assert_eq!(info.allows(author, op), allowed, "{author} {op:?}");
+        }
+    }
+}
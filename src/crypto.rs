@@ -0,0 +1,904 @@
+//! The crypto module encrypts messages end to end, so that only the devices of the members of a room can read them.
+//! Neither the [`Transport`](crate::transport::Transport) nor the server behind it see more than who sent an
+//! [`Envelope`] to which room.
+//!
+//! Every device has keys of its own, [`DeviceKeys`]: an Ed25519 identity key that signs everything the device sends
+//! and an X25519 key that others encrypt to. The public halves are published as a [`Device`], whose
+//! [`fingerprint`](Device::fingerprint) users compare to verify each other.
+//!
+//! Messages, and the other changes to a room, are encrypted with sender keys, like Megolm: every device has a ratchet
+//! of its own in every room. Each message is encrypted with the next key of the ratchet and the ratchet moves on, so a
+//! key that leaks does not open earlier messages. The sender does not keep the keys it encrypted with, and a receiver
+//! drops the key of a message once it opened it, keeping only those of the messages that have not arrived yet. The ratchet is shared with the devices of the members as a
+//! [`KeyShare`], encrypted to each of them, and a new one is started when a device leaves the room or after
+//! [`ROTATE_AFTER`] messages, so that devices that left cannot read what comes next.
+//!
+//! The keys and ratchets are only kept in encrypted storage, see [`storage::save_secret`]. Values are encrypted with
+//! a key of the installation, [`seal_local`] and [`open_local`]. That key, [`LOCAL_KEY`], is not kept with them but in
+//! a [`keystore`]: the keychain of the system on desktop, and wrapped with a non-extractable WebCrypto key on the web.
+//! Mobile has no keystore yet, there the key is stored in the clear under `storage-key`, as it is wherever the
+//! keystore cannot be reached. The files attached to messages are not encrypted at rest at all, see
+//! [`attachments`](crate::attachments).
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use dioxus::prelude::*;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+use crate::crdt::{Change, InfoOp, MessageOp, RoomOp, Stamp};
+use crate::i18n::t;
+use crate::{storage, ROOMS, USER_ID};
+
+/// How many messages are encrypted with a ratchet before a new one is started.
+pub const ROTATE_AFTER: u32 = 100;
+
+/// How far a ratchet is moved forward to decrypt a message, at most. Keeps a forged index from costing too much.
+const MAX_SKIP: u32 = 10_000;
+
+/// How many keys of messages that have not arrived yet are kept for a ratchet. The oldest are dropped first.
+const MAX_SKIPPED: usize = 1_000;
+
+/// The secret keys of a device of `user`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceKeys {
+    pub device: Uuid,
+    pub user: Uuid,
+    identity: [u8; 32],
+    exchange: [u8; 32],
+}
+
+impl DeviceKeys {
+    pub fn generate(user: Uuid) -> Self {
+        Self {
+            device: Uuid::new_v4(),
+            user,
+            identity: SigningKey::generate(&mut OsRng).to_bytes(),
+            exchange: StaticSecret::random_from_rng(OsRng).to_bytes(),
+        }
+    }
+
+    fn signing_key(&self) -> SigningKey {
+        SigningKey::from_bytes(&self.identity)
+    }
+
+    /// The public keys of the device.
+    pub fn public(&self) -> Device {
+        let exchange = PublicKey::from(&StaticSecret::from(self.exchange)).to_bytes();
+
+        Device {
+            id: self.device,
+            user: self.user,
+            identity: self.signing_key().verifying_key().to_bytes(),
+            exchange,
+            signature: self.signing_key().sign(&exchange).to_bytes().to_vec(),
+        }
+    }
+}
+
+/// The public keys of a device of some user. The identity key signs the exchange key.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Device {
+    pub id: Uuid,
+    pub user: Uuid,
+    pub identity: [u8; 32],
+    pub exchange: [u8; 32],
+    pub signature: Vec<u8>,
+}
+
+impl Device {
+    /// Whether the identity key signed the exchange key.
+    pub fn is_valid(&self) -> bool {
+        verify(&self.identity, &self.exchange, &self.signature)
+    }
+
+    /// The identity key as 30 digits in groups of five, to be read out and compared.
+    pub fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(self.identity);
+
+        digest.chunks(5)
+            .take(6)
+            .map(|chunk| {
+                let value = chunk.iter().fold(0u64, |value, byte| value << 8 | u64::from(*byte));
+                format!("{:05}", value % 100_000)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// How far the user trusts a device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trust {
+    /// Its fingerprint was compared.
+    Verified,
+    Unverified,
+    /// It was verified, but its identity key is not the one that was.
+    Changed,
+}
+
+/// The keys of this device, for the local user. Made when the app first runs, and again if the stored ones belong to
+/// another user.
+pub static DEVICE: GlobalSignal<DeviceKeys> = Global::new(|| {
+    let user = *USER_ID.peek();
+
+    storage::load_secret("device-keys")
+        .filter(|keys: &DeviceKeys| keys.user == user)
+        .unwrap_or_else(|| {
+            let keys = DeviceKeys::generate(user);
+            storage::save_secret("device-keys", &keys);
+            keys
+        })
+});
+
+/// The devices of every user, by user.
+pub static DEVICES: GlobalSignal<HashMap<Uuid, Vec<Device>>> = Global::new(HashMap::new);
+
+/// The identity keys of the devices the user verified, by device.
+pub static VERIFIED: GlobalSignal<HashMap<Uuid, [u8; 32]>> = Global::new(|| storage::load_secret("verified").unwrap_or_default());
+
+/// Publish a device of some user. Devices whose identity key did not sign their exchange key are left out.
+pub fn add_device(device: Device) {
+    if !device.is_valid() {
+        return;
+    }
+
+    let mut devices = DEVICES.write();
+    let devices = devices.entry(device.user).or_default();
+
+    devices.retain(|known| known.id != device.id);
+    devices.push(device);
+}
+
+pub fn devices(user: Uuid) -> Vec<Device> {
+    DEVICES.read().get(&user).cloned().unwrap_or_default()
+}
+
+fn find_device(id: Uuid) -> Option<Device> {
+    DEVICES.peek().values().flatten().find(|device| device.id == id).cloned()
+}
+
+pub fn trust(device: &Device) -> Trust {
+    match VERIFIED.read().get(&device.id) {
+        Some(identity) if *identity == device.identity => Trust::Verified,
+        Some(_) => Trust::Changed,
+        None => Trust::Unverified,
+    }
+}
+
+/// Mark a device as verified, or no longer verified.
+pub fn set_verified(device: &Device, verified: bool) {
+    if verified {
+        VERIFIED.write().insert(device.id, device.identity);
+    } else {
+        VERIFIED.write().remove(&device.id);
+    }
+
+    storage::save_secret("verified", &*VERIFIED.peek());
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A step of a sender key ratchet.
+#[derive(Clone, Serialize, Deserialize)]
+struct Chain {
+    session: Uuid,
+    index: u32,
+    key: [u8; 32],
+}
+
+impl Chain {
+    fn new() -> Self {
+        let mut key = [0; 32];
+        OsRng.fill_bytes(&mut key);
+
+        Self { session: Uuid::new_v4(), index: 0, key }
+    }
+
+    fn derive(&self, label: u8) -> [u8; 32] {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&self.key).expect("HMAC takes keys of any length");
+        mac.update(&[label]);
+        mac.finalize().into_bytes().into()
+    }
+
+    /// The key of the message at this step.
+    fn message_key(&self) -> [u8; 32] {
+        self.derive(1)
+    }
+
+    /// Move to the next step. The key of this step cannot be derived from the next one.
+    fn advance(&mut self) {
+        self.key = self.derive(2);
+        self.index += 1;
+    }
+}
+
+/// The ratchet this device encrypts with in a room, and the devices it was shared with.
+#[derive(Clone, Serialize, Deserialize)]
+struct Outbound {
+    chain: Chain,
+    shared_with: BTreeSet<Uuid>,
+}
+
+/// A ratchet of another device, at the step of the next message, and the keys of the messages it moved past that
+/// have not arrived yet.
+#[derive(Clone, Serialize, Deserialize)]
+struct Inbound {
+    room: Uuid,
+    device: Uuid,
+    chain: Chain,
+    #[serde(default)]
+    skipped: BTreeMap<u32, [u8; 32]>,
+}
+
+impl Inbound {
+    /// Take the key of the message at step `index`. It is gone afterwards, every key opens one message only.
+    fn take_key(&mut self, index: u32) -> Option<[u8; 32]> {
+        if index < self.chain.index {
+            return self.skipped.remove(&index);
+        }
+
+        if index - self.chain.index > MAX_SKIP {
+            return None;
+        }
+
+        while self.chain.index < index {
+            self.skipped.insert(self.chain.index, self.chain.message_key());
+            self.chain.advance();
+        }
+
+        while self.skipped.len() > MAX_SKIPPED {
+            self.skipped.pop_first();
+        }
+
+        let key = self.chain.message_key();
+        self.chain.advance();
+
+        Some(key)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Sessions {
+    /// By room.
+    outbound: HashMap<Uuid, Outbound>,
+    /// By session, only those of other devices.
+    inbound: HashMap<Uuid, Inbound>,
+}
+
+static SESSIONS: GlobalSignal<Sessions> = Global::new(|| storage::load_secret("sessions").unwrap_or_default());
+
+fn save_sessions() {
+    storage::save_secret("sessions", &*SESSIONS.peek());
+}
+
+/// A message or other change as it travels: who sent it where in the clear, the [`RoomOp`] encrypted.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub room: Uuid,
+    pub id: Stamp,
+    pub client_id: Uuid,
+    pub author: Uuid,
+    pub device: Uuid,
+    pub session: Uuid,
+    pub index: u32,
+    pub ciphertext: Vec<u8>,
+    /// By the identity key of the device, over the header and the ciphertext.
+    pub signature: Vec<u8>,
+}
+
+impl Envelope {
+    fn header(&self) -> Vec<u8> {
+        let header = (self.room, self.id, self.client_id, self.author, self.device, self.session, self.index);
+
+        serde_json::to_vec(&header).unwrap_or_default()
+    }
+
+    fn signed(&self) -> Vec<u8> {
+        [self.header(), self.ciphertext.clone()].concat()
+    }
+}
+
+/// A sender key ratchet, encrypted to one device.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyShare {
+    pub room: Uuid,
+    pub from: Uuid,
+    pub to: Uuid,
+    /// The public half of the key agreed with the exchange key of `to`.
+    pub ephemeral: [u8; 32],
+    pub ciphertext: Vec<u8>,
+    /// By the identity key of `from`, over everything else.
+    pub signature: Vec<u8>,
+}
+
+impl KeyShare {
+    fn header(&self) -> Vec<u8> {
+        let header = (self.room, self.from, self.to, self.ephemeral);
+
+        serde_json::to_vec(&header).unwrap_or_default()
+    }
+
+    fn signed(&self) -> Vec<u8> {
+        [self.header(), self.ciphertext.clone()].concat()
+    }
+}
+
+/// Derive a ChaCha20-Poly1305 key and nonce from `secret`, for the purpose named by `info`.
+fn cipher(secret: &[u8], info: &[u8]) -> (ChaCha20Poly1305, [u8; 12]) {
+    let mut okm = [0; 44];
+
+    Hkdf::<Sha256>::new(None, secret)
+        .expand(info, &mut okm)
+        .expect("44 bytes is a valid HKDF-SHA256 output length");
+
+    let mut nonce = [0; 12];
+    nonce.copy_from_slice(&okm[32..]);
+
+    (ChaCha20Poly1305::new(Key::from_slice(&okm[..32])), nonce)
+}
+
+fn verify(identity: &[u8; 32], message: &[u8], signature: &[u8]) -> bool {
+    let Ok(key) = VerifyingKey::from_bytes(identity) else {
+        return false;
+    };
+
+    Signature::from_slice(signature).is_ok_and(|signature| key.verify(message, &signature).is_ok())
+}
+
+/// Act as the device `keys`, as if on an installation of its own: the ratchets this device encrypted with are not
+/// the ones of `keys`.
+#[cfg(test)]
+pub fn switch_device(keys: DeviceKeys) {
+    *DEVICE.write() = keys;
+    SESSIONS.write().outbound.clear();
+}
+
+/// Encrypt `change` for the devices of the members of its room. Returns the envelope and the key shares the devices
+/// that do not have the ratchet yet need to decrypt it.
+pub fn seal(change: &Change) -> Result<(Envelope, Vec<KeyShare>), String> {
+    let room = change.room;
+    let keys = DEVICE.peek().clone();
+    let members = ROOMS.peek()
+        .get(&room)
+        .map(|room| room.info.peek().members.clone())
+        .unwrap_or_default();
+    let recipients: Vec<Device> = members.iter()
+        .flat_map(|member| DEVICES.peek().get(member).cloned().unwrap_or_default())
+        .filter(|device| device.id != keys.device)
+        .collect();
+    let recipient_ids: BTreeSet<Uuid> = recipients.iter().map(|device| device.id).collect();
+
+    let mut sessions = SESSIONS.write();
+
+    // Devices that left must not read what comes next, and no ratchet is used forever.
+    let rotate = sessions.outbound.get(&room).is_none_or(|outbound| {
+        !outbound.shared_with.is_subset(&recipient_ids) || outbound.chain.index >= ROTATE_AFTER
+    });
+
+    if rotate {
+        sessions.outbound.insert(room, Outbound { chain: Chain::new(), shared_with: BTreeSet::new() });
+    }
+
+    let outbound = sessions.outbound.get_mut(&room).ok_or_else(|| t!("crypto-failed"))?;
+
+    let shares = recipients.iter()
+        .filter(|device| !outbound.shared_with.contains(&device.id))
+        .map(|device| share(&keys, room, &outbound.chain, device))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    outbound.shared_with.extend(shares.iter().map(|share| share.to));
+
+    let mut envelope = Envelope {
+        room,
+        id: change.stamp,
+        client_id: change.client_id,
+        author: change.author,
+        device: keys.device,
+        session: outbound.chain.session,
+        index: outbound.chain.index,
+        ciphertext: Vec::new(),
+        signature: Vec::new(),
+    };
+
+    let (cipher, nonce) = cipher(&outbound.chain.message_key(), b"dx-chat message");
+    let plaintext = serde_json::to_vec(&change.op).map_err(|_| t!("crypto-failed"))?;
+
+    envelope.ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: &plaintext, aad: &envelope.header() })
+        .map_err(|_| t!("crypto-failed"))?;
+    envelope.signature = keys.signing_key().sign(&envelope.signed()).to_bytes().to_vec();
+
+    outbound.chain.advance();
+
+    drop(sessions);
+    save_sessions();
+
+    Ok((envelope, shares))
+}
+
+/// Decrypt an envelope with a ratchet received before, after checking that it was signed by the device it claims
+/// to be from, and that the device belongs to its author. What the signed header says of the change wins over what
+/// the encrypted operation says, so nobody posts, edits or reacts in the name of someone else.
+pub fn open(envelope: &Envelope) -> Result<Change, String> {
+    let device = find_device(envelope.device)
+        .filter(|device| device.user == envelope.author)
+        .ok_or_else(|| t!("crypto-unknown-device"))?;
+
+    if !verify(&device.identity, &envelope.signed(), &envelope.signature) {
+        return Err(t!("crypto-bad-signature"));
+    }
+
+    // Moved on in a copy, which replaces the ratchet only once the message opened.
+    let mut inbound = SESSIONS.peek()
+        .inbound
+        .get(&envelope.session)
+        .filter(|inbound| inbound.room == envelope.room && inbound.device == envelope.device)
+        .cloned()
+        .ok_or_else(|| t!("crypto-no-key"))?;
+    let key = inbound.take_key(envelope.index).ok_or_else(|| t!("crypto-no-key"))?;
+
+    let (cipher, nonce) = cipher(&key, b"dx-chat message");
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: &envelope.ciphertext, aad: &envelope.header() })
+        .map_err(|_| t!("crypto-failed"))?;
+
+    SESSIONS.write().inbound.insert(envelope.session, inbound);
+    save_sessions();
+    let mut op: RoomOp = serde_json::from_slice(&plaintext).map_err(|_| t!("crypto-failed"))?;
+
+    match &mut op {
+        RoomOp::Message(MessageOp::Post(message)) => {
+            message.id = envelope.id;
+            message.author = envelope.author;
+            message.client_id = envelope.client_id;
+        }
+        RoomOp::Message(
+            MessageOp::Edit { author, .. } | MessageOp::HidePreviews { author, .. } | MessageOp::React { user: author, .. },
+        ) => *author = envelope.author,
+        RoomOp::Info(InfoOp::Name { user, .. }) => *user = envelope.author,
+        // Made by the author of the change, which is the one of the envelope.
+        RoomOp::Info(InfoOp::Topic(_) | InfoOp::Member { .. }) => {}
+    }
+
+    Ok(Change {
+        room: envelope.room,
+        stamp: envelope.id,
+        author: envelope.author,
+        client_id: envelope.client_id,
+        op,
+    })
+}
+
+/// Encrypt the ratchet `chain` of `room` to `device`, with a key agreed between a new ephemeral key and the exchange
+/// key of the device.
+fn share(keys: &DeviceKeys, room: Uuid, chain: &Chain, device: &Device) -> Result<KeyShare, String> {
+    let ephemeral = EphemeralSecret::random_from_rng(OsRng);
+    let public = PublicKey::from(&ephemeral).to_bytes();
+    let secret = ephemeral.diffie_hellman(&PublicKey::from(device.exchange));
+
+    let mut share = KeyShare {
+        room,
+        from: keys.device,
+        to: device.id,
+        ephemeral: public,
+        ciphertext: Vec::new(),
+        signature: Vec::new(),
+    };
+
+    let (cipher, nonce) = cipher(secret.as_bytes(), b"dx-chat key share");
+    let plaintext = serde_json::to_vec(chain).map_err(|_| t!("crypto-failed"))?;
+
+    share.ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: &plaintext, aad: &share.header() })
+        .map_err(|_| t!("crypto-failed"))?;
+    share.signature = keys.signing_key().sign(&share.signed()).to_bytes().to_vec();
+
+    Ok(share)
+}
+
+/// Take in a ratchet shared with this device, to decrypt the messages of its sender.
+pub fn receive(share: &KeyShare) -> Result<(), String> {
+    let keys = DEVICE.peek().clone();
+    let sender = find_device(share.from).ok_or_else(|| t!("crypto-unknown-device"))?;
+
+    if share.to != keys.device || !verify(&sender.identity, &share.signed(), &share.signature) {
+        return Err(t!("crypto-bad-signature"));
+    }
+
+    let secret = StaticSecret::from(keys.exchange).diffie_hellman(&PublicKey::from(share.ephemeral));
+    let (cipher, nonce) = cipher(secret.as_bytes(), b"dx-chat key share");
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: &share.ciphertext, aad: &share.header() })
+        .map_err(|_| t!("crypto-failed"))?;
+    let chain: Chain = serde_json::from_slice(&plaintext).map_err(|_| t!("crypto-failed"))?;
+
+    // A ratchet that is known already is kept as it is, sharing it again must not bring back keys that were used.
+    SESSIONS.write()
+        .inbound
+        .entry(chain.session)
+        .or_insert(Inbound { room: share.room, device: share.from, chain, skipped: BTreeMap::new() });
+    save_sessions();
+
+    Ok(())
+}
+
+/// The key values in storage are encrypted with. It is made when the app first runs and kept apart from them, see
+/// [`keystore`].
+static LOCAL_KEY: GlobalSignal<[u8; 32]> = Global::new(keystore::local_key);
+
+/// Make a new storage key and keep it under `storage-key`, in the clear, or take the one kept there. Where there is
+/// no keystore, and where it fails, that is all there is.
+fn key_in_the_clear() -> [u8; 32] {
+    storage::load("storage-key").unwrap_or_else(|| {
+        let mut key = [0; 32];
+        OsRng.fill_bytes(&mut key);
+        storage::save("storage-key", &key);
+        key
+    })
+}
+
+/// Take the storage key out of the clear. Kept in the clear before, it is moved into the keystore, and removed from
+/// where it was once it is kept there.
+#[cfg(any(target_arch = "wasm32", all(feature = "desktop", not(test))))]
+fn key_to_protect() -> [u8; 32] {
+    storage::load("storage-key").unwrap_or_else(|| {
+        let mut key = [0; 32];
+        OsRng.fill_bytes(&mut key);
+        key
+    })
+}
+
+/// The desktop keeps the storage key in the keychain of the system: the Keychain on macOS, the Credential Manager
+/// on Windows and the Secret Service on Linux.
+#[cfg(all(not(target_arch = "wasm32"), feature = "desktop", not(test)))]
+mod keystore {
+    use keyring::{Entry, Error};
+
+    use crate::storage;
+
+    pub fn local_key() -> [u8; 32] {
+        let entry = match Entry::new("dx-chat", "storage-key") {
+            Ok(entry) => entry,
+            Err(error) => return unprotected(error),
+        };
+
+        match entry.get_secret() {
+            Ok(secret) => match secret.try_into() {
+                Ok(key) => return key,
+                Err(_) => dioxus::logger::tracing::warn!("The storage key in the keychain is broken, it is replaced"),
+            },
+            Err(Error::NoEntry) => {}
+            Err(error) => return unprotected(error),
+        }
+
+        let key = super::key_to_protect();
+
+        match entry.set_secret(&key) {
+            Ok(()) => storage::remove("storage-key"),
+            Err(error) => {
+                dioxus::logger::tracing::warn!("The keychain refused the storage key, it stays in the clear: {error}");
+                storage::save("storage-key", &key);
+            }
+        }
+
+        key
+    }
+
+    fn unprotected(error: Error) -> [u8; 32] {
+        dioxus::logger::tracing::warn!("The storage key is kept in the clear, the keychain cannot be reached: {error}");
+
+        super::key_in_the_clear()
+    }
+}
+
+/// The web keeps the storage key wrapped with an AES-GCM key that the browser makes non-extractable and keeps in
+/// IndexedDB, so that scripts can use it but not read it. Unwrapping is asynchronous, so [`unlock`] runs before the
+/// app is launched.
+#[cfg(target_arch = "wasm32")]
+pub mod keystore {
+    use std::cell::Cell;
+
+    use base64::Engine;
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+
+    use crate::storage;
+
+    /// Opens the IndexedDB store of the wrapping key, and makes the key on first use. Two tabs may make one at the
+    /// same time, `add` keeps the first.
+    const WRAPPING_KEY_JS: &str = r#"
+        const request = (run) => new Promise((resolve, reject) => {
+            const request = run();
+            request.onsuccess = () => resolve(request.result);
+            request.onerror = () => reject(request.error);
+        });
+        const opening = indexedDB.open("dx-chat", 1);
+        opening.onupgradeneeded = () => opening.result.createObjectStore("keys");
+        const database = await request(() => opening);
+        const store = (mode) => database.transaction("keys", mode).objectStore("keys");
+
+        let key = await request(() => store("readonly").get("storage-key"));
+
+        if (!key) {
+            key = await crypto.subtle.generateKey({ name: "AES-GCM", length: 256 }, false, ["encrypt", "decrypt"]);
+            await request(() => store("readwrite").add(key, "storage-key")).catch(() => {});
+            key = await request(() => store("readonly").get("storage-key"));
+        }
+    "#;
+
+    /// Wraps `value`: a random IV followed by the ciphertext.
+    const WRAP_JS: &str = r#"
+        const iv = crypto.getRandomValues(new Uint8Array(12));
+        const ciphertext = await crypto.subtle.encrypt({ name: "AES-GCM", iv }, key, value);
+
+        return new Uint8Array([...iv, ...new Uint8Array(ciphertext)]);
+    "#;
+
+    const UNWRAP_JS: &str = r#"
+        const iv = value.slice(0, 12);
+        const plaintext = await crypto.subtle.decrypt({ name: "AES-GCM", iv }, key, value.slice(12));
+
+        return new Uint8Array(plaintext);
+    "#;
+
+    thread_local! {
+        static KEY: Cell<Option<[u8; 32]>> = const { Cell::new(None) };
+    }
+
+    /// Run `body` on `value` with the wrapping key.
+    async fn with_wrapping_key(body: &str, value: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let body = format!("return (async () => {{ {WRAPPING_KEY_JS} {body} }})();");
+        let run = js_sys::Function::new_with_args("value", &body);
+        let promise: js_sys::Promise = run.call1(&JsValue::NULL, &js_sys::Uint8Array::from(value))?.dyn_into()?;
+
+        Ok(js_sys::Uint8Array::new(&JsFuture::from(promise).await?).to_vec())
+    }
+
+    /// Unwrap the storage key, or make one and wrap it.
+    pub async fn unlock() {
+        let wrapped = storage::load::<String>("wrapped-storage-key")
+            .and_then(|wrapped| base64::engine::general_purpose::STANDARD.decode(wrapped).ok());
+
+        if let Some(wrapped) = wrapped {
+            match with_wrapping_key(UNWRAP_JS, &wrapped).await.map(<[u8; 32]>::try_from) {
+                Ok(Ok(key)) => return KEY.set(Some(key)),
+                // What was stored with it cannot be read anymore, it starts over with a new key.
+                _ => dioxus::logger::tracing::warn!("The storage key could not be unwrapped, a new one is made"),
+            }
+        }
+
+        let key = super::key_to_protect();
+
+        match with_wrapping_key(WRAP_JS, &key).await {
+            Ok(wrapped) => {
+                storage::save("wrapped-storage-key", &base64::engine::general_purpose::STANDARD.encode(wrapped));
+                storage::remove("storage-key");
+            }
+            Err(error) => {
+                dioxus::logger::tracing::warn!("The storage key could not be wrapped, it stays in the clear: {error:?}");
+                storage::save("storage-key", &key);
+            }
+        }
+
+        KEY.set(Some(key));
+    }
+
+    pub fn local_key() -> [u8; 32] {
+        KEY.get().unwrap_or_else(super::key_in_the_clear)
+    }
+}
+
+/// Mobile has no keystore yet, and tests keep the key next to what they store.
+#[cfg(all(not(target_arch = "wasm32"), any(not(feature = "desktop"), test)))]
+mod keystore {
+    pub fn local_key() -> [u8; 32] {
+        super::key_in_the_clear()
+    }
+}
+
+/// Encrypt a value to be stored under `name`. The nonce is stored in front of the ciphertext.
+pub fn seal_local(name: &str, plaintext: &[u8]) -> Option<Vec<u8>> {
+    let mut nonce = [0; 12];
+    OsRng.fill_bytes(&mut nonce);
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&*LOCAL_KEY.peek()));
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: name.as_bytes() }).ok()?;
+
+    Some([nonce.as_slice(), &ciphertext].concat())
+}
+
+/// Decrypt a value stored under `name`. Values moved to another name do not decrypt.
+pub fn open_local(name: &str, sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < 12 {
+        return None;
+    }
+
+    let (nonce, ciphertext) = sealed.split_at(12);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&*LOCAL_KEY.peek()));
+
+    cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: name.as_bytes() }).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{room_id, testing, Message, MessageKind, Room, RoomInfo};
+
+    /// The bytes written as hex in `hex`.
+    fn bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|index| u8::from_str_radix(&hex[index..index + 2], 16).unwrap()).collect()
+    }
+
+    // The expected values were computed with Python's hmac and hashlib, and ChaCha20Poly1305 of the cryptography
+    // package.
+
+    #[test]
+    fn ratchet_known_answers() {
+        let mut chain = Chain { session: Uuid::nil(), index: 0, key: [1; 32] };
+
+        assert_eq!(chain.message_key().to_vec(), bytes("cc6efb872c237f565ee82df42e4cab00098b13710395e3c6d29f2907d69e4f04"));
+
+        chain.advance();
+
+        assert_eq!(chain.index, 1);
+        assert_eq!(chain.key.to_vec(), bytes("c31d79abaf8f2150ee1cfe3dc732eed02a56f79647909bad055a831cb762e9a2"));
+        assert_eq!(chain.message_key().to_vec(), bytes("7f9b7eee1ac1776859f044f1ff38b8be2f1098100c357c2060305853f192ccd1"));
+    }
+
+    #[test]
+    fn cipher_known_answer() {
+        let (cipher, nonce) = cipher(&[2; 32], b"dx-chat message");
+        let payload = Payload { msg: b"Hello there", aad: b"header" };
+
+        assert_eq!(nonce.to_vec(), bytes("ed083fdebebeef7f0c3966e3"));
+        assert_eq!(
+            cipher.encrypt(Nonce::from_slice(&nonce), payload).unwrap(),
+            bytes("646e984b231979584dd5142785a3044566e34e682724fca28f5825"),
+        );
+    }
+
+    #[test]
+    fn inbound_keys_open_once() {
+        let chain = Chain { session: Uuid::nil(), index: 0, key: [1; 32] };
+        let keys: Vec<[u8; 32]> = (0..4)
+            .scan(chain.clone(), |chain, _| {
+                let key = chain.message_key();
+                chain.advance();
+                Some(key)
+            })
+            .collect();
+        let mut inbound = Inbound { room: Uuid::nil(), device: Uuid::nil(), chain, skipped: BTreeMap::new() };
+
+        // Out of order: the keys of the messages moved past are kept until they arrive.
+        assert_eq!(inbound.take_key(2), Some(keys[2]));
+        assert_eq!(inbound.skipped.keys().copied().collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(inbound.take_key(0), Some(keys[0]));
+        assert_eq!(inbound.take_key(3), Some(keys[3]));
+        assert_eq!(inbound.take_key(1), Some(keys[1]));
+
+        // Nothing opens twice, and nothing is left behind.
+        for index in 0..4 {
+            assert_eq!(inbound.take_key(index), None);
+        }
+
+        assert!(inbound.skipped.is_empty());
+        assert_eq!(inbound.chain.index, 4);
+        assert_eq!(inbound.take_key(4 + MAX_SKIP + 1), None);
+    }
+
+    /// In a room with the user and a friend, a message of the user sealed for the friend, and the keys of the friend.
+    fn sealed() -> (Envelope, Vec<KeyShare>, DeviceKeys) {
+        let user = *crate::USER_ID.peek();
+        let friend = DeviceKeys::generate(Uuid::new_v4());
+        add_device(DEVICE.peek().public());
+        add_device(friend.public());
+
+        let room = Room::new(room_id("Starwars"), RoomInfo::new("Starwars".into(), None, [user, friend.user].into()));
+        ROOMS.write().insert(room.id, room);
+
+        let message = Message::new(user, "Hello there".into(), MessageKind::Text);
+        let (envelope, shares) = seal(&Change::post(room.id, &message)).unwrap();
+
+        (envelope, shares, friend)
+    }
+
+    #[test]
+    fn friends_open_what_was_sealed_for_them() {
+        testing::runtime().run(|| {
+            let (envelope, shares, friend) = sealed();
+
+            // The sender does not keep the key it sealed with.
+            assert_eq!(open(&envelope), Err(t!("crypto-no-key")));
+
+            *DEVICE.write() = friend;
+            assert_eq!(shares.len(), 1);
+            receive(&shares[0]).unwrap();
+
+            let change = open(&envelope).unwrap();
+            let RoomOp::Message(MessageOp::Post(message)) = change.op else {
+                panic!("not a post: {change:?}");
+            };
+
+            assert_eq!(message.content, "Hello there");
+            assert_eq!(message.author, envelope.author);
+            assert_eq!(open(&envelope), Err(t!("crypto-no-key")));
+        });
+    }
+
+    #[test]
+    fn refuses_forgeries() {
+        testing::runtime().run(|| {
+            let sender = DEVICE.peek().clone();
+            let (envelope, shares, friend) = sealed();
+            *DEVICE.write() = friend;
+            receive(&shares[0]).unwrap();
+
+            let mut forged = envelope.clone();
+            forged.signature[0] ^= 1;
+            assert_eq!(open(&forged), Err(t!("crypto-bad-signature")));
+
+            // Posted in the name of someone else, who does not own the device.
+            let mut forged = envelope.clone();
+            forged.author = Uuid::new_v4();
+            assert_eq!(open(&forged), Err(t!("crypto-unknown-device")));
+
+            // Tampered with and signed again by the sender, so that only the cipher notices.
+            let mut tampered = envelope.clone();
+            tampered.ciphertext[0] ^= 1;
+            tampered.signature = sender.signing_key().sign(&tampered.signed()).to_bytes().to_vec();
+            assert_eq!(open(&tampered), Err(t!("crypto-failed")));
+
+            let mut share = shares[0].clone();
+            share.ciphertext[0] ^= 1;
+            assert_eq!(receive(&share), Err(t!("crypto-bad-signature")));
+
+            // What failed to open did not use up the key.
+            assert!(open(&envelope).is_ok());
+        });
+    }
+
+    #[test]
+    fn changes_are_signed_by_their_author() {
+        testing::runtime().run(|| {
+            let user = *crate::USER_ID.peek();
+            let (_, shares, friend) = sealed();
+            let (message, someone) = (Stamp::now(), Uuid::new_v4());
+
+            // Each of them claims to be by someone else inside.
+            let ops: [RoomOp; 6] = [
+                MessageOp::Edit { message, author: someone, content: "Forged".into() }.into(),
+                MessageOp::React { message, user: someone, emoji: "👍".into(), on: true }.into(),
+                MessageOp::HidePreviews { message, author: someone }.into(),
+                InfoOp::Topic(Some("Forged".into())).into(),
+                InfoOp::Member { user: someone, joined: true }.into(),
+                InfoOp::Name { user: someone, name: "Forged".into() }.into(),
+            ];
+            let envelopes: Vec<Envelope> = ops.iter()
+                .map(|op| seal(&Change::new(room_id("Starwars"), user, op.clone())).unwrap().0)
+                .collect();
+
+            *DEVICE.write() = friend;
+            receive(&shares[0]).unwrap();
+
+            let opened: Vec<Change> = envelopes.iter().map(|envelope| open(envelope).unwrap()).collect();
+
+            // Whoever the operation names, the changes the user signed are the user's.
+            assert!(opened.iter().all(|change| change.author == user));
+            assert_eq!(opened[0].op, MessageOp::Edit { message, author: user, content: "Forged".into() }.into());
+            assert_eq!(opened[1].op, MessageOp::React { message, user, emoji: "👍".into(), on: true }.into());
+            assert_eq!(opened[2].op, MessageOp::HidePreviews { message, author: user }.into());
+            assert_eq!(opened[3].op, ops[3]);
+            assert_eq!(opened[4].op, ops[4]);
+            assert_eq!(opened[5].op, InfoOp::Name { user, name: "Forged".into() }.into());
+        });
+    }
+}
@@ -0,0 +1,94 @@
+//! The history module loads the messages of a room page by page. A room starts out empty, [`load_older`] fetches the
+//! page of messages before the oldest one loaded from the [`HistorySource`] in [`HISTORY`] and merges it into the
+//! room's messages.
+
+use std::{cell::RefCell, collections::{BTreeMap, HashMap}, future::Future, pin::Pin, rc::Rc};
+
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::i18n::t;
+use crate::crdt::{MessageOp, Stamp};
+use crate::{Message, ROOMS};
+
+/// How many messages are fetched at a time.
+pub const PAGE_SIZE: usize = 25;
+
+pub struct Page {
+    /// The messages of the page, in any order.
+    pub messages: Vec<Message>,
+    /// Whether there are messages older than the ones in this page.
+    pub has_more: bool,
+}
+
+pub type PageFuture = Pin<Box<dyn Future<Output = Result<Page, String>>>>;
+
+/// Where the history of rooms is fetched from.
+pub trait HistorySource {
+    /// Fetch up to `limit` of the newest messages in `room` sent before the message `before`, or the newest messages
+    /// of the room if `before` is `None`.
+    fn fetch(&self, room: Uuid, before: Option<Stamp>, limit: usize) -> PageFuture;
+}
+
+/// Serves history kept in memory.
+#[derive(Default)]
+pub struct MemoryHistory {
+    rooms: RefCell<HashMap<Uuid, BTreeMap<Stamp, Message>>>,
+}
+
+impl MemoryHistory {
+    pub fn insert(&self, room: Uuid, message: Message) {
+        self.rooms.borrow_mut().entry(room).or_default().insert(message.id, message);
+    }
+}
+
+impl HistorySource for MemoryHistory {
+    fn fetch(&self, room: Uuid, before: Option<Stamp>, limit: usize) -> PageFuture {
+        let rooms = self.rooms.borrow();
+
+        let page = match rooms.get(&room) {
+            Some(messages) => {
+                let older = match before {
+                    Some(before) => messages.range(..before),
+                    None => messages.range(..),
+                };
+
+                let messages: Vec<_> = older.rev().take(limit + 1).map(|(_, message)| message.clone()).collect();
+                let has_more = messages.len() > limit;
+
+                Page {
+                    messages: messages.into_iter().take(limit).collect(),
+                    has_more,
+                }
+            }
+            None => Page {
+                messages: Vec::new(),
+                has_more: false,
+            },
+        };
+
+        Box::pin(async move { Ok(page) })
+    }
+}
+
+pub static HISTORY: GlobalSignal<Rc<dyn HistorySource>> = Global::new(|| Rc::new(MemoryHistory::default()));
+
+/// Fetch the page of messages older than the oldest loaded message of `room` and merge it into the room. Messages
+/// that are already there, like ones that arrived while the page was loading, are left as they are.
+pub async fn load_older(room: Uuid) -> Result<(), String> {
+    let Some(mut room) = ROOMS.peek().get(&room).copied() else {
+        return Err(t!("room-gone"));
+    };
+
+    let before = room.messages.peek().keys().next().copied();
+    let fetch = HISTORY.peek().fetch(room.id, before, PAGE_SIZE);
+    let page = fetch.await?;
+
+    for message in page.messages {
+        room.apply(message.id, MessageOp::Post(Box::new(message)));
+    }
+
+    room.history_complete.set(!page.has_more);
+
+    Ok(())
+}
@@ -0,0 +1,145 @@
+//! The i18n module translates the strings of the user interface with [Fluent](https://projectfluent.org). The
+//! translations of every [`Language`] are bundled from the `locales` directory and looked up with the [`t!`] macro,
+//! which falls back to English for messages that a language does not translate yet.
+//!
+//! The language is picked in the settings and defaults to the language of the system. Components that translate
+//! strings re-render when it changes.
+
+use std::collections::HashMap;
+
+use dioxus::prelude::*;
+use fluent_bundle::{FluentArgs, FluentBundle, FluentResource};
+use serde::{Deserialize, Serialize};
+use unic_langid::LanguageIdentifier;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Language {
+    English,
+    Finnish,
+    Arabic,
+}
+
+impl Language {
+    pub const ALL: [Language; 3] = [Language::English, Language::Finnish, Language::Arabic];
+
+    /// The BCP 47 tag of the language, used for the `lang` attribute and to pick plural rules.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Language::English => "en-US",
+            Language::Finnish => "fi",
+            Language::Arabic => "ar",
+        }
+    }
+
+    /// The name of the language in the language itself, for the language switch.
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Finnish => "Suomi",
+            Language::Arabic => "العربية",
+        }
+    }
+
+    /// Whether the language is written from right to left.
+    pub fn is_rtl(self) -> bool {
+        matches!(self, Language::Arabic)
+    }
+
+    /// The text direction of the language, for the `dir` attribute.
+    pub fn dir(self) -> &'static str {
+        if self.is_rtl() { "rtl" } else { "ltr" }
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Language::English => include_str!("../locales/en-US.ftl"),
+            Language::Finnish => include_str!("../locales/fi.ftl"),
+            Language::Arabic => include_str!("../locales/ar.ftl"),
+        }
+    }
+
+    /// The language of a BCP 47 tag like `fi-FI` or a POSIX locale like `fi_FI.UTF-8`.
+    pub fn from_tag(tag: &str) -> Option<Language> {
+        let primary = tag.split(['-', '_', '.', '@']).next()?.to_lowercase();
+
+        Language::ALL.into_iter().find(|language| language.tag().starts_with(primary.as_str()))
+    }
+
+    /// The language of the system, or English if the app is not translated to it.
+    pub fn system() -> Language {
+        system_tag().as_deref().and_then(Language::from_tag).unwrap_or(Language::English)
+    }
+}
+
+/// The language of the user interface, mirrored from the [`Settings`](crate::settings::Settings).
+pub static LANGUAGE: GlobalSignal<Language> = Global::new(Language::system);
+
+thread_local! {
+    static BUNDLES: HashMap<Language, FluentBundle<FluentResource>> = Language::ALL.into_iter()
+        .map(|language| (language, bundle(language)))
+        .collect();
+}
+
+fn bundle(language: Language) -> FluentBundle<FluentResource> {
+    let id: LanguageIdentifier = language.tag().parse().unwrap_or_default();
+    let mut bundle = FluentBundle::new(vec![id]);
+
+    // Isolation marks around arguments would show up in titles and placeholders, the `dir` of the page is enough.
+    bundle.set_use_isolating(false);
+
+    let resource = FluentResource::try_new(language.source().to_string()).unwrap_or_else(|(resource, errors)| {
+        dioxus::logger::tracing::error!("Errors in the {} translations: {errors:?}", language.tag());
+        resource
+    });
+
+    if let Err(errors) = bundle.add_resource(resource) {
+        dioxus::logger::tracing::error!("Duplicate messages in the {} translations: {errors:?}", language.tag());
+    }
+
+    bundle
+}
+
+/// The message `id` in the current language, or in English if the language does not have it. Returns `id` itself
+/// when no language has the message, so that text which is not a message id passes through unchanged.
+///
+/// Prefer the [`t!`] macro, which builds the arguments.
+pub fn translate(id: &str, args: Option<&FluentArgs>) -> String {
+    let language = *LANGUAGE.read();
+
+    BUNDLES.with(|bundles| {
+        [language, Language::English].into_iter()
+            .filter_map(|language| bundles.get(&language))
+            .find_map(|bundle| {
+                let pattern = bundle.get_message(id)?.value()?;
+                let mut errors = Vec::new();
+
+                Some(bundle.format_pattern(pattern, args, &mut errors).into_owned())
+            })
+            .unwrap_or_else(|| id.to_string())
+    })
+}
+
+/// Translate a message, with optional named arguments: `t!("search-results", count = results.len())`.
+macro_rules! t {
+    ($id:expr) => {
+        $crate::i18n::translate($id, None)
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::translate($id, Some(&args))
+    }};
+}
+
+pub(crate) use t;
+
+/// The language tag of the browser or, elsewhere, of the `LC_ALL`, `LC_MESSAGES` or `LANG` environment variables.
+fn system_tag() -> Option<String> {
+    #[cfg(target_arch = "wasm32")]
+    return web_sys::window().and_then(|window| window.navigator().language());
+
+    #[cfg(not(target_arch = "wasm32"))]
+    return ["LC_ALL", "LC_MESSAGES", "LANG"].into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX");
+}
@@ -1,15 +1,64 @@
-use std::{collections::{BTreeMap, HashMap}, time::Instant};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 // The dioxus prelude contains a ton of common items used in dioxus apps. It's a good idea to import wherever you
 // need dioxus
+use attachments::Attachment;
+use chrono::{DateTime, TimeDelta, Utc};
+use crdt::{InfoOp, InfoStamps, MessageOp, Messages, Reactions, RoomOp, Stamp};
 use dioxus::prelude::*;
+use fluent_bundle::FluentArgs;
+use serde::{Deserialize, Serialize};
+use transport::Delivery;
 use uuid::Uuid;
-use views::{Chat, Home, Navbar};
+use views::{Chat, Home, Navbar, Profile, Search, Settings};
 
 /// Define a components module that contains all shared components for our app.
 mod components;
 /// Define a views module that contains the UI for all Layouts and Routes for our app.
 mod views;
+/// Define a storage module that persists application state between runs.
+mod storage;
+/// Define a commands module that parses and runs the slash commands typed into the composer.
+mod commands;
+/// Define an attachments module that stores the files shared in messages.
+mod attachments;
+/// Define a previews module that unfurls links posted in messages.
+mod previews;
+/// Define a search module that indexes messages for full-text search.
+mod search;
+/// Define a history module that loads the messages of rooms page by page.
+mod history;
+/// Define a render stats module that counts component renders for benchmarking.
+mod render_stats;
+/// Define a time format module that shows message times in the user's locale.
+mod time_format;
+/// Define an i18n module that translates the user interface.
+mod i18n;
+/// Define a settings module that keeps the user's preferences.
+mod settings;
+/// Define a theme module that applies the built-in and custom color themes.
+mod theme;
+/// Define a shortcuts module that maps keyboard shortcuts to actions.
+mod shortcuts;
+/// Define a notifications module that notifies the user about messages in other rooms.
+mod notifications;
+/// Define a push module that receives Web Push messages through a service worker.
+mod push;
+/// Define a presence module that tracks who is online, away or busy.
+mod presence;
+/// Define a typing module that tracks who is typing in which room.
+mod typing;
+/// Define a transport module that sends messages and tracks their delivery and read receipts.
+mod transport;
+/// Define an outbox module that keeps messages until they could be sent.
+mod outbox;
+/// Define a crdt module that merges the changes made to rooms on several devices.
+mod crdt;
+/// Define a crypto module that encrypts messages end to end.
+mod crypto;
+/// Define a testing module that runs tests against the app's global state.
+#[cfg(test)]
+mod testing;
 
 /// The Route enum is used to define the structure of internal routes in our app. All route enums need to derive
 /// the [`Routable`] trait, which provides the necessary methods for the router to work.
@@ -25,6 +74,15 @@ enum Route {
 
         #[route("/blog/:id")]
         Chat { id: Uuid },
+
+        #[route("/user/:id")]
+        Profile { id: Uuid },
+
+        #[route("/search?:query")]
+        Search { query: String },
+
+        #[route("/settings")]
+        Settings {},
 }
 
 // We can import assets in dioxus with the `asset!` macro. This macro takes a path to an asset relative to the crate root.
@@ -37,14 +95,98 @@ const TAILWIND_CSS: Asset = asset!("/assets/tailwind.css");
 fn main() {
     // The `launch` function is the main entry point for a dioxus app. It takes a component and renders it with the platform feature
     // you have enabled
+    #[cfg(not(target_arch = "wasm32"))]
     dioxus::launch(App);
+
+    // On the web, the key of the encrypted storage is unwrapped first, see `crypto::keystore`.
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(async {
+        crypto::keystore::unlock().await;
+        dioxus::launch(App);
+    });
 }
 
-#[derive(Clone)]
+/// How a message is presented: a regular chat bubble, an action written with `/me`, or a notice about something that
+/// happened in the room.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum MessageKind {
+    #[default]
+    Text,
+    Emote,
+    Notice,
+}
+
+/// A message of a room. What the other replicas derive from operations of their own, the edits and reactions, and what
+/// only this one knows, its delivery, is not sent along.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Message {
-    pub id: Instant,
+    /// The stamp of the post, which orders the message among the others of its room.
+    pub id: Stamp,
     pub author: Uuid,
+    /// The wall-clock time the message was sent at.
+    pub sent: DateTime<Utc>,
+    /// The text of the message, or for a notice the id of the Fluent message it is translated from when it is shown.
     pub content: String,
+    pub kind: MessageKind,
+    /// The arguments of a notice, so that every member reads it in their own language.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub args: BTreeMap<String, String>,
+    pub attachments: Vec<Attachment>,
+    /// Whether links in the message are unfurled into preview cards.
+    pub link_previews: bool,
+    /// The stamp of the edit that set the content of the message, if it was edited.
+    #[serde(skip)]
+    pub edited: Option<Stamp>,
+    #[serde(skip)]
+    pub reactions: Reactions,
+    /// How far the message got, for messages of the user.
+    #[serde(skip)]
+    pub delivery: Delivery,
+    /// Picked by the client that wrote the message, so that sending it more than once does not duplicate it.
+    pub client_id: Uuid,
+}
+
+impl Message {
+    /// A message from `author` sent now.
+    pub fn new(author: Uuid, content: String, kind: MessageKind) -> Self {
+        Self {
+            id: Stamp::now(),
+            author,
+            sent: Utc::now(),
+            content,
+            kind,
+            args: BTreeMap::new(),
+            attachments: Vec::new(),
+            link_previews: true,
+            edited: None,
+            reactions: Reactions::default(),
+            delivery: Delivery::default(),
+            client_id: Uuid::new_v4(),
+        }
+    }
+
+    /// A notice from `author` sent now, the Fluent message `id` with `args`.
+    pub fn notice(author: Uuid, id: &str, args: &[(&str, &str)]) -> Self {
+        Self {
+            args: args.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            ..Self::new(author, id.to_string(), MessageKind::Notice)
+        }
+    }
+
+    /// The text of the message as it is shown, notices translated to the current language.
+    pub fn text(&self) -> String {
+        if self.kind != MessageKind::Notice {
+            return self.content.clone();
+        }
+
+        let mut args = FluentArgs::new();
+
+        for (name, value) in &self.args {
+            args.set(name.as_str(), value.as_str());
+        }
+
+        i18n::translate(&self.content, Some(&args))
+    }
 }
 
 #[derive(Clone)]
@@ -55,20 +197,186 @@ pub struct Author {
     pub bio: Option<String>,
 }
 
-pub type Messages = BTreeMap::<Instant, Message>;
 pub type Authors = HashMap::<Uuid, Author>;
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoomInfo {
+    pub name: String,
+    pub topic: Option<String>,
+    pub members: BTreeSet<Uuid>,
+    /// The names members set with `/nick`, by member.
+    pub names: BTreeMap<Uuid, String>,
+    stamps: InfoStamps,
+}
+
+impl RoomInfo {
+    /// The info of a room as it is created, before anything changed it.
+    pub fn new(name: String, topic: Option<String>, members: BTreeSet<Uuid>) -> Self {
+        Self { name, topic, members, names: BTreeMap::new(), stamps: InfoStamps::default() }
+    }
+}
+
+/// The state of a room is split into signals that change independently, so that a view only re-renders for the part
+/// of the room it shows: posting a message re-renders the room's messages but not the room list.
+#[derive(Clone, Copy, PartialEq)]
 pub struct Room {
     pub id: Uuid,
-    pub name: String,
-    pub messages: Messages,
+    pub info: Signal<RoomInfo>,
+    /// The messages loaded so far, older ones are fetched with [`history::load_older`].
+    pub messages: Signal<Messages>,
+    /// Whether the oldest message of the room has been loaded.
+    pub history_complete: Signal<bool>,
+    /// How many messages were posted while the room was not open.
+    pub unread: Signal<usize>,
+    /// The newest message each member has read, as far as is known.
+    pub receipts: Signal<HashMap<Uuid, Stamp>>,
+}
+
+impl Room {
+    /// The signals of the room are owned by the root scope, so the room lives on after the component that created it,
+    /// like the composer running `/join`, is gone.
+    pub fn new(id: Uuid, info: RoomInfo) -> Self {
+        Self {
+            id,
+            info: Signal::new_in_scope(info, ScopeId::ROOT),
+            messages: Signal::new_in_scope(Messages::default(), ScopeId::ROOT),
+            history_complete: Signal::new_in_scope(false, ScopeId::ROOT),
+            unread: Signal::new_in_scope(0, ScopeId::ROOT),
+            receipts: Signal::new_in_scope(HashMap::new(), ScopeId::ROOT),
+        }
+    }
+
+    /// Apply a change to the messages of the room, made by this replica or another one, and keep the search index
+    /// up to date. Returns whether the change was new.
+    pub fn apply(mut self, stamp: Stamp, op: MessageOp) -> bool {
+        crdt::observe(stamp);
+
+        let target = op.target();
+        let before = self.messages.peek().get(&target).cloned();
+
+        if !self.messages.write().apply(stamp, op) {
+            return false;
+        }
+
+        if let Some(after) = self.messages.peek().get(&target) {
+            if before.as_ref().is_none_or(|before| before.content != after.content) {
+                if let Some(before) = &before {
+                    search::unindex(self.id, before);
+                }
+
+                search::index(self.id, after);
+            }
+        }
+
+        true
+    }
+
+    /// Apply a change to the info of the room, made by this replica or another one. Returns whether it changed the
+    /// info. A new name of a member is shown wherever they are.
+    pub fn apply_info(mut self, stamp: Stamp, op: InfoOp) -> bool {
+        crdt::observe(stamp);
+
+        let renamed = match &op {
+            InfoOp::Name { user, name } => Some((*user, name.clone())),
+            _ => None,
+        };
+
+        if !self.info.write().apply(stamp, op) {
+            return false;
+        }
+
+        if let Some((user, name)) = renamed {
+            if let Some(author) = AUTHORS.write().get_mut(&user) {
+                author.username = name;
+            }
+        }
+
+        true
+    }
+
+    /// Apply a change to the messages or the info of the room, without notifying anyone. Returns whether it was new.
+    pub fn merge(self, stamp: Stamp, op: RoomOp) -> bool {
+        match op {
+            RoomOp::Message(op) => self.apply(stamp, op),
+            RoomOp::Info(op) => self.apply_info(stamp, op),
+        }
+    }
+
+    /// Change the info of the room, as `author`, and send the change to the other members.
+    pub fn change_info(self, author: Uuid, op: InfoOp) {
+        transport::change(self, author, op.into());
+    }
+
+    /// Replace the content of one of the room's messages and mark it as edited, as `author`.
+    pub fn edit(self, author: Uuid, id: Stamp, content: String) {
+        transport::change(self, author, MessageOp::Edit { message: id, author, content }.into());
+    }
+
+    /// Add or take back the reaction of `user` to one of the room's messages.
+    pub fn react(self, id: Stamp, user: Uuid, emoji: String, on: bool) {
+        transport::change(self, user, MessageOp::React { message: id, user, emoji, on }.into());
+    }
+
+    /// Stop unfurling the links of one of the room's messages, as `author`.
+    pub fn hide_previews(self, author: Uuid, id: Stamp) {
+        transport::change(self, author, MessageOp::HidePreviews { message: id, author }.into());
+    }
+
+    /// Append a message to the room, unless it is already there.
+    pub fn post(mut self, message: Message) {
+        notifications::incoming(self.id, &message);
+
+        if !self.apply(message.id, MessageOp::Post(Box::new(message))) {
+            return;
+        }
+
+        if *ACTIVE_ROOM.peek() != Some(self.id) {
+            *self.unread.write() += 1;
+        }
+    }
 }
 
 pub type Rooms = BTreeMap::<Uuid, Room>;
 
-pub static AUTHORS: GlobalSignal<Authors> = Global::new(|| Authors::new());
-pub static ROOMS: GlobalSignal<Rooms> = Global::new(|| Rooms::new());
+/// An unsent message in a room's composer.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Draft {
+    pub text: String,
+}
+
+impl Draft {
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+}
+
+pub type Drafts = HashMap::<Uuid, Draft>;
+
+pub static AUTHORS: GlobalSignal<Authors> = Global::new(Authors::new);
+/// The rooms by id. The map itself only changes when rooms are added or removed, see [`Room`].
+pub static ROOMS: GlobalSignal<Rooms> = Global::new(Rooms::new);
+/// The room open in the chat view, if any.
+pub static ACTIVE_ROOM: GlobalSignal<Option<Uuid>> = Global::new(|| None);
+/// Drafts saved before they were encrypted are loaded as they are, and encrypted the next time they are saved.
+pub static DRAFTS: GlobalSignal<Drafts> = Global::new(|| {
+    storage::load_secret("drafts").or_else(|| storage::load("drafts")).unwrap_or_default()
+});
+
+/// The id of the local user. Made when the app first runs and kept, so that what was stored for the user, like the
+/// messages waiting in the outbox, is still theirs after a restart.
+pub static USER_ID: GlobalSignal<Uuid> = Global::new(|| {
+    storage::load("user-id").unwrap_or_else(|| {
+        let id = Uuid::new_v4();
+        storage::save("user-id", &id);
+        id
+    })
+});
+
+/// Room ids are derived from the room name so that state persisted per room, like drafts, finds its room again
+/// after a restart.
+fn room_id(name: &str) -> Uuid {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, name.as_bytes())
+}
 
 /// App is the main component of our app. Components are the building blocks of dioxus apps. Each component is a function
 /// that takes some props and returns an Element. In this case, App takes no props because it is the root of our app.
@@ -77,8 +385,8 @@ pub static ROOMS: GlobalSignal<Rooms> = Global::new(|| Rooms::new());
 #[component]
 fn App() -> Element {
     let anakin = Author {
-        id: Uuid::new_v4(),
-        username: "Anakin".to_string(),
+        id: *USER_ID.peek(),
+        username: storage::load("username").unwrap_or_else(|| "Anakin".to_string()),
         avatar: Some("https://img.daisyui.com/images/profile/demo/anakeen@192.webp".to_string()),
         bio: Some("Badguy".to_string()),
     };
@@ -118,42 +426,91 @@ fn App() -> Element {
         bio: Some("Superhero".to_string()),
     };
 
-    let mut messages = Messages::new();
+    let mut messages = Vec::new();
+
+    // The backlog was written over the last few days, a conversation every eight hours.
+    let start = Utc::now() - TimeDelta::days(3);
 
-    for _ in 0..10 {
-        let ts = Instant::now();
+    for i in 0..10 {
+        let sent = start + TimeDelta::hours(8 * i);
 
-        messages.insert(ts, Message {
-            id: ts,
+        messages.push(Message {
+            id: Stamp::at(sent),
             author: obi.id,
+            sent,
             content: "You were the Chosen One!".into(),
+            kind: MessageKind::Text,
+            args: BTreeMap::new(),
+            attachments: Vec::new(),
+            link_previews: true,
+            edited: None,
+            reactions: Reactions::default(),
+            delivery: Delivery::Delivered,
+            client_id: Uuid::new_v4(),
         });
         
-        let ts = Instant::now();
-
-        messages.insert(ts, Message {
-            id: ts,
+        messages.push(Message {
+            id: Stamp::at(sent + TimeDelta::minutes(1)),
             author: anakin.id,
+            sent: sent + TimeDelta::minutes(1),
             content: "I hate you!".into(),
+            kind: MessageKind::Text,
+            args: BTreeMap::new(),
+            attachments: Vec::new(),
+            link_previews: true,
+            edited: None,
+            reactions: Reactions::default(),
+            delivery: Delivery::Delivered,
+            client_id: Uuid::new_v4(),
         });
 
-        let ts = Instant::now();
-
-        messages.insert(ts, Message {
-            id: ts,
+        messages.push(Message {
+            id: Stamp::at(sent + TimeDelta::minutes(2)),
             author: yoda.id,
+            sent: sent + TimeDelta::minutes(2),
             content: "Do or do not. There is no try.".into(),
+            kind: MessageKind::Text,
+            args: BTreeMap::new(),
+            attachments: Vec::new(),
+            link_previews: true,
+            edited: None,
+            reactions: Reactions::default(),
+            delivery: Delivery::Delivered,
+            client_id: Uuid::new_v4(),
         });
 
-        let ts = Instant::now();
-
-        messages.insert(ts, Message {
-            id: ts,
+        messages.push(Message {
+            id: Stamp::at(sent + TimeDelta::minutes(3)),
             author: luke.id,
+            sent: sent + TimeDelta::minutes(3),
             content: "I'll never turn to the dark side.".into(),
+            kind: MessageKind::Text,
+            args: BTreeMap::new(),
+            attachments: Vec::new(),
+            link_previews: true,
+            edited: None,
+            reactions: Reactions::default(),
+            delivery: Delivery::Delivered,
+            client_id: Uuid::new_v4(),
         });
     }
     
+    let members = BTreeSet::from([anakin.id, obi.id, yoda.id, luke.id, scarlett.id, mark.id]);
+
+    // Until there is a server, the other users have made up statuses.
+    presence::update(obi.id, presence::Status::Online, None);
+    presence::update(yoda.id, presence::Status::Away, Some("🧘 Meditating".into()));
+    presence::update(luke.id, presence::Status::DoNotDisturb, Some("Training on Dagobah".into()));
+    presence::update(scarlett.id, presence::Status::Online, None);
+
+    // Until there is a server, the devices of the other users are made up too. Their secret keys are thrown away,
+    // only their own devices could decrypt what is shared with them.
+    crypto::add_device(crypto::DEVICE.peek().public());
+
+    for author in [&obi, &yoda, &luke, &scarlett, &mark] {
+        crypto::add_device(crypto::DeviceKeys::generate(author.id).public());
+    }
+
     {
         let mut authors = AUTHORS.write();
 
@@ -164,47 +521,37 @@ fn App() -> Element {
         authors.insert(mark.id, mark);
     }
 
-    let starwars = Room {
-        id: Uuid::new_v4(),
-        name: "Starwars".to_string(),
-        messages,
-    };
+    let starwars = room_id("Starwars");
 
-    let spiderman = Room {
-        id: Uuid::new_v4(),
-        name: "Spiderman".to_string(),
-        messages: Messages::new(),
-    };
+    // The Starwars backlog is served page by page, like it would be by a server. It is indexed up front so that
+    // search finds messages that have not been loaded yet.
+    let mut index = search::SearchIndex::default();
+    let archive = history::MemoryHistory::default();
 
-    let batman = Room {
-        id: Uuid::new_v4(),
-        name: "Batman".to_string(),
-        messages: Messages::new(),
-    };
-
-    let hulk = Room {
-        id: Uuid::new_v4(),
-        name: "Hulk".to_string(),
-        messages: Messages::new(),
-    };
+    for message in messages {
+        index.insert(starwars, &message);
+        archive.insert(starwars, message);
+    }
 
-    let avengers = Room {
-        id: Uuid::new_v4(),
-        name: "Avengers".to_string(),
-        messages: Messages::new(),
-    };
+    *history::HISTORY.write() = std::rc::Rc::new(archive);
+    *search::SEARCH.write() = index;
 
     {
         let mut rooms = ROOMS.write();
 
-        rooms.insert(starwars.id, starwars);
-        rooms.insert(spiderman.id, spiderman);
-        rooms.insert(batman.id, batman);
-        rooms.insert(hulk.id, hulk);
-        rooms.insert(avengers.id, avengers);
+        for name in ["Starwars", "Spiderman", "Batman", "Hulk", "Avengers"] {
+            let room = Room::new(room_id(name), RoomInfo::new(name.to_string(), None, members.clone()));
+
+            rooms.insert(room.id, room);
+        }
     }
 
-    use_context_provider(|| anakin);
+    use_context_provider(|| Signal::new(anakin));
+    time_format::use_clock();
+    settings::use_settings_provider();
+    presence::use_presence();
+    transport::use_connection();
+    outbox::use_outbox();
 
     // The `rsx!` macro lets us define HTML inside of rust. It expands to an Element with all of our HTML inside.
     rsx! {
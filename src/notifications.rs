@@ -0,0 +1,401 @@
+//! The notifications module tells the user about messages posted to rooms other than the one that is open. Posted
+//! messages are queued with [`incoming`] and shown by [`use_notifications`], which filters them through the user's
+//! [`Notifications`] settings: the level of the room and the quiet hours. There are none while the user's status is
+//! do not disturb.
+//!
+//! On desktop the notifications are native ones, and clicking one opens its room. On the web they are shown with the
+//! Notification API while the tab is hidden, once the user has allowed them, see [`push`](crate::push). Mobile does
+//! not show notifications yet.
+//!
+//! The number of unread messages is shown in the window title and, on the web, on the favicon with
+//! [`use_unread_badge`].
+
+use chrono::{Local, NaiveTime};
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::i18n::t;
+use crate::presence::{Status, PRESENCE};
+use crate::settings::{use_settings, Notifications};
+use crate::{Author, Message, MessageKind, ACTIVE_ROOM, AUTHORS, ROOMS};
+
+/// How much of a message is shown in its notification, in characters.
+const SNIPPET_LENGTH: usize = 120;
+
+/// Which messages of a room notify.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Level {
+    /// Whatever the notification settings say for all rooms.
+    #[default]
+    Default,
+    All,
+    Mentions,
+    Muted,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Default, Level::All, Level::Mentions, Level::Muted];
+}
+
+/// A daily time range without notifications. Quiet hours that end before they start run over midnight.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuietHours {
+    pub enabled: bool,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if !self.enabled {
+            false
+        } else if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+impl Default for QuietHours {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: NaiveTime::from_hms_opt(22, 0, 0).unwrap_or_default(),
+            end: NaiveTime::from_hms_opt(7, 0, 0).unwrap_or_default(),
+        }
+    }
+}
+
+/// Messages posted to rooms that were not open, waiting for [`use_notifications`].
+static INCOMING: GlobalSignal<Vec<(Uuid, Message)>> = Global::new(Vec::new);
+
+/// Queue a message that was just posted to `room`, unless the room is open.
+pub fn incoming(room: Uuid, message: &Message) {
+    if *ACTIVE_ROOM.peek() != Some(room) {
+        INCOMING.write().push((room, message.clone()));
+    }
+}
+
+/// Shows the unread count in the title and on the favicon. The original title and icon are kept to go back to.
+const BADGE_JS: &str = r##"
+    const count = await dioxus.recv();
+
+    window.dxTitle ??= document.title;
+    document.title = count > 0 ? `(${count}) ${window.dxTitle}` : window.dxTitle;
+
+    const icon = document.querySelector("link[rel~='icon']");
+
+    if (!icon) {
+        return;
+    }
+
+    icon.dataset.original ??= icon.href;
+
+    if (count === 0) {
+        icon.href = icon.dataset.original;
+        return;
+    }
+
+    const image = new Image();
+    image.src = icon.dataset.original;
+    await image.decode().catch(() => {});
+
+    const canvas = document.createElement("canvas");
+    canvas.width = canvas.height = 64;
+
+    const context = canvas.getContext("2d");
+    context.drawImage(image, 0, 0, 64, 64);
+    context.fillStyle = "#dc2626";
+    context.beginPath();
+    context.arc(44, 20, 20, 0, 2 * Math.PI);
+    context.fill();
+    context.fillStyle = "#ffffff";
+    context.font = "bold 28px sans-serif";
+    context.textAlign = "center";
+    context.textBaseline = "middle";
+    context.fillText(count > 9 ? "9+" : String(count), 44, 22);
+
+    icon.href = canvas.toDataURL("image/png");
+"##;
+
+/// Keep the unread badge up to date with the unread messages of all rooms. Called once, by the layout.
+pub fn use_unread_badge() {
+    let unread = use_memo(|| ROOMS.read().values().map(|room| (room.unread)()).sum::<usize>());
+
+    use_effect(move || {
+        let _ = document::eval(BADGE_JS).send(unread());
+    });
+}
+
+/// Show notifications for the queued messages. Called once, by the layout, since opening a room from a
+/// notification needs the router.
+pub fn use_notifications() {
+    let user = use_context::<Signal<Author>>();
+    let settings = use_settings();
+
+    use_effect(move || {
+        if INCOMING.read().is_empty() {
+            return;
+        }
+
+        let incoming = std::mem::take(&mut *INCOMING.write());
+        let user = user.peek();
+        let settings = &settings.peek().notifications;
+        let now = Local::now().time();
+
+        if PRESENCE.peek().get(&user.id).is_some_and(|presence| presence.status == Status::DoNotDisturb) {
+            return;
+        }
+
+        for (room, message) in incoming {
+            if message.author != user.id && wanted(settings, room, &message, &user.username, now) {
+                show(room, &message, settings.sound);
+            }
+        }
+    });
+}
+
+fn wanted(settings: &Notifications, room: Uuid, message: &Message, username: &str, now: NaiveTime) -> bool {
+    if !settings.enabled || message.kind == MessageKind::Notice || settings.quiet_hours.contains(now) {
+        return false;
+    }
+
+    match settings.level(room) {
+        Level::Default | Level::All => true,
+        Level::Mentions => mentions(&message.content, username),
+        Level::Muted => false,
+    }
+}
+
+/// Whether `content` mentions the user: `@` followed by their name or first name, in any case.
+pub fn mentions(content: &str, username: &str) -> bool {
+    let content = content.to_lowercase();
+    let username = username.to_lowercase();
+    let first_name = username.split_whitespace().next().unwrap_or(&username);
+
+    content.contains(&format!("@{username}")) || content.contains(&format!("@{first_name}"))
+}
+
+fn show(room: Uuid, message: &Message, sound: bool) {
+    let room_name = ROOMS.peek().get(&room).map(|room| room.info.peek().name.clone()).unwrap_or_default();
+    let author = AUTHORS.peek()
+        .get(&message.author)
+        .map(|author| author.username.clone())
+        .unwrap_or_else(|| t!("unknown-user"));
+
+    let title = t!("notification-title", author = author, room = room_name);
+    let mut body: String = message.content.chars().take(SNIPPET_LENGTH).collect();
+
+    if body.len() < message.content.len() {
+        body.push('…');
+    }
+
+    platform::show(room, title, body, sound);
+}
+
+#[cfg(all(feature = "desktop", not(target_arch = "wasm32")))]
+mod platform {
+    use notify_rust::Notification;
+    use uuid::Uuid;
+
+    pub fn show(room: Uuid, title: String, body: String, sound: bool) {
+        let mut notification = Notification::new();
+
+        notification.appname("dx-chat").summary(&title).body(&body);
+
+        if sound {
+            notification.sound_name("message-new-instant");
+        }
+
+        show_and_open(room, notification);
+    }
+
+    /// Only the freedesktop notification servers report clicks, elsewhere the notification is only shown.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn show_and_open(room: Uuid, mut notification: Notification) {
+        use dioxus::prelude::*;
+
+        use crate::i18n::t;
+        use crate::Route;
+
+        notification.action("default", &t!("notification-open"));
+
+        spawn(async move {
+            // Waiting for the click blocks until the notification is closed.
+            let clicked = tokio::task::spawn_blocking(move || {
+                let mut clicked = false;
+
+                if let Ok(handle) = notification.show() {
+                    handle.wait_for_action(|action| clicked = action == "default");
+                }
+
+                clicked
+            });
+
+            if clicked.await.unwrap_or(false) {
+                navigator().push(Route::Chat { id: room });
+            }
+        });
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    fn show_and_open(_room: Uuid, notification: Notification) {
+        let _ = notification.show();
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use dioxus::prelude::*;
+    use uuid::Uuid;
+
+    use crate::Route;
+
+    /// Shows a notification while the tab is hidden and sends the room back when it is clicked.
+    const SHOW_JS: &str = r#"
+        const [room, title, body, sound] = await dioxus.recv();
+
+        if (!document.hidden || !("Notification" in window) || Notification.permission !== "granted") {
+            return;
+        }
+
+        const notification = new Notification(title, { body, tag: room, silent: !sound });
+
+        notification.onclick = () => {
+            window.focus();
+            notification.close();
+            dioxus.send(room);
+        };
+
+        await new Promise((resolve) => notification.onclose = resolve);
+    "#;
+
+    pub fn show(room: Uuid, title: String, body: String, sound: bool) {
+        spawn(async move {
+            let mut notification = document::eval(SHOW_JS);
+            let _ = notification.send((room, title, body, sound));
+
+            if let Ok(id) = notification.recv::<Uuid>().await {
+                navigator().push(Route::Chat { id });
+            }
+        });
+    }
+}
+
+#[cfg(not(any(feature = "desktop", target_arch = "wasm32")))]
+mod platform {
+    use uuid::Uuid;
+
+    pub fn show(_room: Uuid, _title: String, _body: String, _sound: bool) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn quiet(start: NaiveTime, end: NaiveTime) -> QuietHours {
+        QuietHours { enabled: true, start, end }
+    }
+
+    #[test]
+    fn quiet_hours_contain_their_times() {
+        let overnight = quiet(time(22, 0), time(7, 0));
+        let office = quiet(time(9, 0), time(17, 0));
+        let off = QuietHours { enabled: false, ..overnight.clone() };
+        let empty = quiet(time(8, 0), time(8, 0));
+
+        let cases = [
+            (&overnight, time(21, 59), false),
+            (&overnight, time(22, 0), true),
+            (&overnight, time(23, 30), true),
+            (&overnight, time(0, 0), true),
+            (&overnight, time(6, 59), true),
+            (&overnight, time(7, 0), false),
+            (&overnight, time(12, 0), false),
+            (&office, time(8, 59), false),
+            (&office, time(9, 0), true),
+            (&office, time(16, 59), true),
+            (&office, time(17, 0), false),
+            (&office, time(23, 0), false),
+            (&off, time(23, 0), false),
+            (&empty, time(8, 0), false),
+        ];
+
+        for (hours, time, contained) in cases {
+            assert_eq!(hours.contains(time), contained, "{}-{} at {time}", hours.start, hours.end);
+        }
+    }
+
+    #[test]
+    fn finds_mentions() {
+        let cases = [
+            ("@Anakin Skywalker, come here", true),
+            ("hey @anakin", true),
+            ("@ANAKIN!", true),
+            ("@Skywalker", false),
+            ("Anakin, come here", false),
+            ("email anakin@tatooine.net", false),
+            ("", false),
+        ];
+
+        for (content, mentioned) in cases {
+            assert_eq!(mentions(content, "Anakin Skywalker"), mentioned, "{content:?}");
+        }
+    }
+
+    #[test]
+    fn rooms_notify_at_their_level() {
+        let [all, mentioning, muted, default] = [1, 2, 3, 4].map(Uuid::from_u128);
+        let noon = time(12, 0);
+        let mut settings = Notifications::default();
+
+        settings.set_level(all, Level::All);
+        settings.set_level(mentioning, Level::Mentions);
+        settings.set_level(muted, Level::Muted);
+        settings.set_level(default, Level::Default);
+
+        let (plain, mention, notice) = testing::runtime().run(|| {
+            let author = Uuid::new_v4();
+
+            (
+                Message::new(author, "Hello there".into(), MessageKind::Text),
+                Message::new(author, "@Anakin hello there".into(), MessageKind::Text),
+                Message::notice(author, "notice-left", &[("name", "Obi-Wan")]),
+            )
+        });
+
+        let mentions_only = Notifications { mentions_only: true, ..settings.clone() };
+        let disabled = Notifications { enabled: false, ..settings.clone() };
+        let sleeping = Notifications { quiet_hours: quiet(time(22, 0), time(7, 0)), ..settings.clone() };
+
+        let cases = [
+            (&settings, all, &plain, noon, true),
+            (&settings, all, &notice, noon, false),
+            (&settings, mentioning, &plain, noon, false),
+            (&settings, mentioning, &mention, noon, true),
+            (&settings, muted, &mention, noon, false),
+            (&settings, default, &plain, noon, true),
+            // Only rooms without a level of their own follow the setting for all rooms.
+            (&mentions_only, default, &plain, noon, false),
+            (&mentions_only, default, &mention, noon, true),
+            (&mentions_only, all, &plain, noon, true),
+            (&disabled, all, &mention, noon, false),
+            (&sleeping, all, &mention, time(23, 0), false),
+            (&sleeping, all, &mention, time(7, 0), true),
+        ];
+
+        for (index, (settings, room, message, now, notifies)) in cases.into_iter().enumerate() {
+            assert_eq!(wanted(settings, room, message, "Anakin", now), notifies, "case {index}");
+        }
+
+        assert_eq!(settings.levels.len(), 3, "the default level is not kept");
+    }
+}
@@ -0,0 +1,336 @@
+//! The outbox module keeps the user's messages until the [`Transport`](crate::transport::Transport) has accepted them.
+//! [`transport::send`] queues every message with [`push`], and [`use_outbox`] sends them one at a time in the order
+//! they were written. While the transport is disconnected the messages wait in their rooms as pending, and the
+//! outbox is flushed as soon as it reconnects.
+//!
+//! A message that fails while connected is sent again after a delay that doubles with every attempt, up to
+//! [`MAX_BACKOFF`], and is marked as failed after [`MAX_ATTEMPTS`]. Sending a message again is safe: the server knows
+//! it by its client id and does not duplicate it.
+//!
+//! Messages are encrypted before they are queued, see [`crypto`](crate::crypto). The outbox is kept in encrypted
+//! storage under `outbox`, so messages written while offline survive a restart. The envelopes cannot be opened by
+//! the device that sealed them, so the outbox keeps each message as it was written too, and puts it back into its
+//! room when the app starts.
+
+use std::{collections::VecDeque, time::Duration};
+
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::attachments;
+use crate::crdt::{Change, MessageOp, RoomOp};
+use crate::crypto::{self, Envelope, KeyShare};
+use crate::transport::{self, Delivery, CONNECTED, TRANSPORT};
+use crate::{storage, time_format, ROOMS};
+
+/// How long to wait before sending a message again after its first failed attempt.
+pub const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The longest wait between two attempts.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How many times a message is tried while connected before it is marked as failed.
+pub const MAX_ATTEMPTS: u32 = 6;
+
+/// A message or other change waiting to be sent, encrypted, with the key shares its recipients need.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Outgoing {
+    /// The change as it was made, to put it back after a restart.
+    pub change: Change,
+    pub envelope: Envelope,
+    pub shares: Vec<KeyShare>,
+}
+
+/// The messages waiting to be sent, oldest first.
+pub static OUTBOX: GlobalSignal<VecDeque<Outgoing>> = Global::new(|| storage::load_secret("outbox").unwrap_or_default());
+
+/// Encrypt `change` and queue it to be sent, unless it already is.
+pub fn push(change: &Change) -> Result<(), String> {
+    if OUTBOX.peek().iter().any(|outgoing| outgoing.envelope.client_id == change.client_id) {
+        return Ok(());
+    }
+
+    let (envelope, shares) = crypto::seal(&packed(change)?)?;
+
+    OUTBOX.write().push_back(Outgoing { change: change.clone(), envelope, shares });
+    storage::save_secret("outbox", &*OUTBOX.peek());
+
+    Ok(())
+}
+
+/// `change` with the contents of the attachments of the message it posts. Only the envelope carries them, the change
+/// kept in the outbox refers to the blob store like the message in the room.
+fn packed(change: &Change) -> Result<Change, String> {
+    let mut change = change.clone();
+
+    if let RoomOp::Message(MessageOp::Post(message)) = &mut change.op {
+        for attachment in &mut message.attachments {
+            attachments::pack(attachment)?;
+        }
+    }
+
+    Ok(change)
+}
+
+fn remove(client_id: Uuid) {
+    OUTBOX.write().retain(|outgoing| outgoing.envelope.client_id != client_id);
+    storage::save_secret("outbox", &*OUTBOX.peek());
+}
+
+/// The wait before the next attempt after `attempts` failed ones.
+fn backoff(attempts: u32) -> Duration {
+    INITIAL_BACKOFF.saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1))).min(MAX_BACKOFF)
+}
+
+/// Put the messages and changes left in the outbox by the last run back into their rooms. They were not sent, so they
+/// did not come back with the history.
+fn restore() {
+    let rooms = ROOMS.peek();
+
+    for outgoing in OUTBOX.peek().iter() {
+        let mut change = outgoing.change.clone();
+
+        let Some(room) = rooms.get(&change.room).copied() else {
+            dioxus::logger::tracing::warn!("Room {} of a queued change is gone, the change is dropped", change.room);
+            continue;
+        };
+
+        if let RoomOp::Message(MessageOp::Post(message)) = &mut change.op {
+            message.delivery = Delivery::Pending;
+        }
+
+        // Merged rather than posted: the user's own messages are neither unread nor notified.
+        room.merge(change.stamp, change.op);
+    }
+}
+
+/// Send the queued messages in order until the outbox is empty or the transport disconnects.
+async fn flush() {
+    let mut attempts = 0;
+
+    while *CONNECTED.peek() {
+        let Some(outgoing) = OUTBOX.peek().front().cloned() else {
+            break;
+        };
+
+        let envelope = &outgoing.envelope;
+        let room = ROOMS.peek().get(&envelope.room).copied();
+        let Some(room) = room else {
+            // The room is gone, there is nobody left to send to.
+            remove(envelope.client_id);
+            continue;
+        };
+
+        let sending = TRANSPORT.peek().send(envelope, &outgoing.shares);
+
+        match sending.await {
+            Ok(()) => {
+                attempts = 0;
+                remove(envelope.client_id);
+                transport::set_delivery(room, envelope.id, Delivery::Sent);
+            }
+            // Waits for the transport to reconnect, without counting as an attempt.
+            Err(_) if !*CONNECTED.peek() => break,
+            Err(error) => {
+                attempts += 1;
+
+                if attempts >= MAX_ATTEMPTS {
+                    attempts = 0;
+                    remove(envelope.client_id);
+                    transport::set_delivery(room, envelope.id, Delivery::Failed(error));
+                } else {
+                    time_format::sleep(backoff(attempts)).await;
+                }
+            }
+        }
+    }
+}
+
+/// Restore the outbox and flush it whenever the transport is connected and there is something to send. Called once,
+/// by the root component, after the rooms have been created.
+pub fn use_outbox() {
+    let mut flushing = use_signal(|| false);
+
+    use_hook(restore);
+
+    use_effect(move || {
+        if !CONNECTED() || OUTBOX.read().is_empty() || *flushing.peek() {
+            return;
+        }
+
+        flushing.set(true);
+
+        spawn(async move {
+            flush().await;
+            flushing.set(false);
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::crdt::InfoOp;
+    use crate::transport::{SendFuture, Transport};
+    use crate::{room_id, testing, Message, MessageKind, Room, RoomInfo, USER_ID};
+
+    /// Records the client ids of the messages it is asked to send, and fails them all if `failing` is set.
+    #[derive(Default)]
+    struct Recording {
+        sent: Rc<RefCell<Vec<Uuid>>>,
+        failing: bool,
+    }
+
+    impl Transport for Recording {
+        fn send(&self, envelope: &Envelope, _shares: &[KeyShare]) -> SendFuture {
+            self.sent.borrow_mut().push(envelope.client_id);
+            let failing = self.failing;
+
+            Box::pin(async move {
+                if failing {
+                    Err("Refused".into())
+                } else {
+                    Ok(())
+                }
+            })
+        }
+
+        fn read(&self, _room: Uuid, _id: crate::crdt::Stamp) {}
+
+        fn typing(&self, _room: Uuid, _typing: bool) {}
+
+        fn listen(&self, _on_change: Rc<dyn Fn(Envelope, Vec<KeyShare>)>, _on_typing: Rc<dyn Fn(Uuid, Uuid, bool)>) {}
+    }
+
+    /// An empty room, as it is when the app starts.
+    fn starwars() -> Room {
+        let room = Room::new(room_id("Starwars"), RoomInfo::new("Starwars".into(), None, Default::default()));
+        ROOMS.write().insert(room.id, room);
+
+        room
+    }
+
+    /// A room with `count` messages of the user sent to it, and the transport they are sent with.
+    fn sent(count: usize, failing: bool) -> (Room, Rc<RefCell<Vec<Uuid>>>) {
+        let room = starwars();
+
+        for i in 0..count {
+            transport::send(room, Message::new(*USER_ID.peek(), format!("Message {i}"), MessageKind::Text));
+        }
+
+        let recording = Recording { failing, ..Default::default() };
+        let sent = recording.sent.clone();
+        *TRANSPORT.write() = Rc::new(recording);
+
+        (room, sent)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_limit() {
+        assert_eq!(backoff(1), INITIAL_BACKOFF);
+        assert_eq!(backoff(2), INITIAL_BACKOFF * 2);
+        assert_eq!(backoff(3), INITIAL_BACKOFF * 4);
+        assert_eq!(backoff(6), INITIAL_BACKOFF * 32);
+        assert_eq!(backoff(7), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn queues_a_message_once() {
+        testing::runtime().run(|| {
+            let message = Message::new(Uuid::new_v4(), "Hello there".into(), MessageKind::Text);
+
+            push(&Change::post(room_id("Starwars"), &message)).unwrap();
+            push(&Change::post(room_id("Starwars"), &message)).unwrap();
+
+            assert_eq!(OUTBOX.peek().len(), 1);
+            assert_eq!(storage::load_secret::<VecDeque<Outgoing>>("outbox").unwrap().len(), 1);
+        });
+    }
+
+    #[test]
+    fn sends_in_order() {
+        let app = testing::runtime();
+        let (room, sent) = app.run(|| sent(3, false));
+
+        app.block_on(flush());
+
+        app.run(|| {
+            let messages = room.messages.peek();
+
+            assert_eq!(*sent.borrow(), messages.values().map(|message| message.client_id).collect::<Vec<_>>());
+            assert!(messages.values().all(|message| message.delivery == Delivery::Sent));
+            assert!(OUTBOX.peek().is_empty());
+        });
+    }
+
+    #[test]
+    fn gives_up_after_the_last_attempt() {
+        let app = testing::runtime();
+        let (room, sent) = app.run(|| sent(2, true));
+
+        let waited = app.block_on(async {
+            let started = tokio::time::Instant::now();
+            flush().await;
+            started.elapsed()
+        });
+
+        // Every message is tried MAX_ATTEMPTS times, waiting longer after each failed attempt but the last.
+        let waits: Duration = (1..MAX_ATTEMPTS).map(backoff).sum();
+        assert_eq!(waited, waits * 2);
+
+        app.run(|| {
+            let messages = room.messages.peek();
+            let ids: Vec<Uuid> = messages.values().map(|message| message.client_id).collect();
+
+            assert_eq!(sent.borrow().len(), 2 * MAX_ATTEMPTS as usize);
+            assert!(sent.borrow()[..MAX_ATTEMPTS as usize].iter().all(|id| *id == ids[0]));
+            assert!(messages.values().all(|message| message.delivery == Delivery::Failed("Refused".into())));
+            assert!(OUTBOX.peek().is_empty());
+        });
+    }
+
+    #[test]
+    fn restores_after_a_restart() {
+        let (message, change) = testing::runtime().run(|| {
+            *CONNECTED.write() = false;
+            let (room, _) = sent(1, false);
+            room.change_info(*USER_ID.peek(), InfoOp::Topic(Some("Sand".into())));
+
+            let message = room.messages.peek().values().next().cloned().unwrap();
+            (message, OUTBOX.peek()[1].change.clone())
+        });
+
+        // The app starts again, on the same storage.
+        testing::runtime().run(|| {
+            let room = starwars();
+            restore();
+
+            let restored = room.messages.peek().get(&message.id).cloned();
+            assert_eq!(restored, Some(message));
+            assert_eq!(room.info.peek().topic.as_deref(), Some("Sand"));
+            assert_eq!(OUTBOX.peek().len(), 2);
+            assert_eq!(OUTBOX.peek()[1].change, change);
+        });
+    }
+
+    #[test]
+    fn waits_for_the_connection() {
+        let app = testing::runtime();
+        let (room, sent) = app.run(|| {
+            *CONNECTED.write() = false;
+            sent(1, false)
+        });
+
+        app.block_on(flush());
+
+        app.run(|| {
+            assert!(sent.borrow().is_empty());
+            assert_eq!(OUTBOX.peek().len(), 1);
+            assert!(room.messages.peek().values().all(|message| message.delivery == Delivery::Pending));
+        });
+    }
+}
@@ -0,0 +1,314 @@
+//! The presence module keeps track of who is around. The [`Presence`] of every user is kept in [`PRESENCE`], so that
+//! every view showing a user reacts when it changes.
+//!
+//! The user's own presence follows the status picked in the [`Settings`](crate::settings::Settings), except that
+//! being online turns into being away after [`IDLE_AFTER`] without using the keyboard or the pointer. A custom status
+//! can be set to clear itself after a while, see [`ClearAfter`].
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::settings::{use_settings, Settings};
+use crate::{time_format, Author};
+
+/// How long the user can be inactive before they are shown as away.
+pub const IDLE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// The longest custom status text, in characters.
+pub const MAX_STATUS_LENGTH: usize = 80;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Status {
+    Online,
+    Away,
+    /// Online, but without notifications.
+    DoNotDisturb,
+    /// Not connected, or appearing so.
+    #[default]
+    Offline,
+}
+
+impl Status {
+    pub const ALL: [Status; 4] = [Status::Online, Status::Away, Status::DoNotDisturb, Status::Offline];
+
+    /// The id of the message naming the status.
+    pub fn label(self) -> &'static str {
+        match self {
+            Status::Online => "status-online",
+            Status::Away => "status-away",
+            Status::DoNotDisturb => "status-do-not-disturb",
+            Status::Offline => "status-offline",
+        }
+    }
+
+    /// The background color of the status dot.
+    pub fn color(self) -> &'static str {
+        match self {
+            Status::Online => "bg-success",
+            Status::Away => "bg-warning",
+            Status::DoNotDisturb => "bg-error",
+            Status::Offline => "bg-base-300",
+        }
+    }
+}
+
+/// When a custom status clears itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClearAfter {
+    Never,
+    Hour,
+    FourHours,
+    Day,
+}
+
+impl ClearAfter {
+    pub const ALL: [ClearAfter; 4] = [ClearAfter::Never, ClearAfter::Hour, ClearAfter::FourHours, ClearAfter::Day];
+
+    /// The id of the message naming the choice.
+    pub fn label(self) -> &'static str {
+        match self {
+            ClearAfter::Never => "status-clear-never",
+            ClearAfter::Hour => "status-clear-hour",
+            ClearAfter::FourHours => "status-clear-four-hours",
+            ClearAfter::Day => "status-clear-day",
+        }
+    }
+
+    /// When a custom status set at `now` clears itself, if ever.
+    pub fn until(self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let hours = match self {
+            ClearAfter::Never => return None,
+            ClearAfter::Hour => 1,
+            ClearAfter::FourHours => 4,
+            ClearAfter::Day => 24,
+        };
+
+        Some(now + TimeDelta::hours(hours))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Presence {
+    pub status: Status,
+    /// A custom status like "🌴 On vacation".
+    pub text: Option<String>,
+    /// When the status last changed.
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// The presence of every user that has one. Users without one are offline.
+pub static PRESENCE: GlobalSignal<HashMap<Uuid, Presence>> = Global::new(HashMap::new);
+
+/// The presence of `user`.
+pub fn of(user: Uuid) -> Presence {
+    PRESENCE.read().get(&user).cloned().unwrap_or_default()
+}
+
+/// Update the presence of `user`, keeping when it changed if the status stays the same.
+pub fn update(user: Uuid, status: Status, text: Option<String>) {
+    let current = PRESENCE.peek().get(&user).cloned().unwrap_or_default();
+
+    if current.status == status && current.text == text {
+        return;
+    }
+
+    let since = if current.status == status { current.since } else { Some(Utc::now()) };
+
+    PRESENCE.write().insert(user, Presence { status, text, since });
+}
+
+/// The status others see when the user picked `status`. Online turns into away while the user is `idle`.
+pub fn shown_status(status: Status, idle: bool) -> Status {
+    match status {
+        Status::Online if idle => Status::Away,
+        status => status,
+    }
+}
+
+/// The custom status in `settings` at `now`, unless there is none or it ran out.
+pub fn custom_status(settings: &Settings, now: DateTime<Utc>) -> Option<String> {
+    if settings.status_until.is_some_and(|until| until <= now) {
+        return None;
+    }
+
+    Some(settings.status_text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+/// Reports whether the user is idle: `true` after the given number of milliseconds without input, `false` as soon
+/// as there is input again.
+const IDLE_JS: &str = r#"
+    const idleAfter = await dioxus.recv();
+    let idle = false;
+    let timer;
+
+    const set = (value) => {
+        if (value !== idle) {
+            idle = value;
+            dioxus.send(idle);
+        }
+    };
+
+    const active = () => {
+        set(false);
+        clearTimeout(timer);
+        timer = setTimeout(() => set(true), idleAfter);
+    };
+
+    for (const type of ["pointermove", "pointerdown", "keydown", "wheel", "touchstart"]) {
+        document.addEventListener(type, active, { passive: true });
+    }
+
+    active();
+
+    // Stays pending so that the channel to the app stays open.
+    await new Promise(() => {});
+"#;
+
+/// Publish the user's own presence and keep it up to date. Called once, by the root component.
+pub fn use_presence() {
+    let user = use_context::<Signal<Author>>();
+    let mut settings = use_settings();
+    let mut idle = use_signal(|| false);
+
+    use_future(move || async move {
+        let mut detector = document::eval(IDLE_JS);
+        let _ = detector.send(IDLE_AFTER.as_millis() as u64);
+
+        while let Ok(value) = detector.recv::<bool>().await {
+            idle.set(value);
+        }
+    });
+
+    use_effect(move || {
+        let settings = settings.read();
+
+        update(user.read().id, shown_status(settings.status, idle()), custom_status(&settings, Utc::now()));
+    });
+
+    // Also clears a custom status that ran out while the app was closed.
+    use_effect(move || {
+        let Some(until) = settings.read().status_until else {
+            return;
+        };
+
+        spawn(async move {
+            time_format::sleep((until - Utc::now()).to_std().unwrap_or_default()).await;
+
+            // Unless the custom status was changed meanwhile.
+            if settings.peek().status_until == Some(until) {
+                let mut settings = settings.write();
+                settings.status_text.clear();
+                settings.status_until = None;
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, Route, USER_ID};
+
+    const FRIEND: Uuid = Uuid::from_u128(2);
+
+    #[test]
+    fn idle_users_are_shown_as_away() {
+        let cases = [
+            (Status::Online, false, Status::Online),
+            (Status::Online, true, Status::Away),
+            (Status::Away, false, Status::Away),
+            (Status::DoNotDisturb, true, Status::DoNotDisturb),
+            (Status::Offline, true, Status::Offline),
+        ];
+
+        for (status, idle, shown) in cases {
+            assert_eq!(shown_status(status, idle), shown, "{status:?}, idle: {idle}");
+        }
+    }
+
+    #[test]
+    fn changes_are_dated_by_their_status() {
+        let app = testing::runtime();
+        let long_ago = DateTime::UNIX_EPOCH;
+
+        app.run(|| {
+            assert_eq!(of(FRIEND), Presence::default());
+
+            PRESENCE.write().insert(FRIEND, Presence { status: Status::Online, text: None, since: Some(long_ago) });
+
+            // A new text keeps the time the status changed.
+            update(FRIEND, Status::Online, Some("🌴 On vacation".to_string()));
+            assert_eq!(of(FRIEND).text.as_deref(), Some("🌴 On vacation"));
+            assert_eq!(of(FRIEND).since, Some(long_ago));
+
+            update(FRIEND, Status::Away, Some("🌴 On vacation".to_string()));
+            assert_eq!(of(FRIEND).status, Status::Away);
+            assert!(of(FRIEND).since.is_some_and(|since| since > long_ago));
+        });
+    }
+
+    #[test]
+    fn custom_statuses_run_out() {
+        let now = Utc::now();
+        let hour = TimeDelta::hours(1);
+        let status = |text: &str, until| Settings {
+            status_text: text.to_string(),
+            status_until: until,
+            ..Settings::default()
+        };
+
+        assert_eq!(custom_status(&status(" 🌴 On vacation ", None), now).as_deref(), Some("🌴 On vacation"));
+        assert_eq!(custom_status(&status("🌴 On vacation", Some(now + hour)), now).as_deref(), Some("🌴 On vacation"));
+        assert_eq!(custom_status(&status("🌴 On vacation", Some(now)), now), None);
+        assert_eq!(custom_status(&status("  ", None), now), None);
+
+        assert_eq!(ClearAfter::Never.until(now), None);
+        assert_eq!(ClearAfter::Hour.until(now), Some(now + hour));
+        assert_eq!(ClearAfter::Day.until(now), Some(now + hour * 24));
+    }
+
+    #[test]
+    fn custom_statuses_are_cleared_when_they_run_out() {
+        let mut app = testing::app(Route::Home {});
+        let hour = TimeDelta::hours(1);
+        let (user, mut settings) =
+            app.run(|| (*USER_ID.peek(), ScopeId::APP.consume_context::<Signal<Settings>>().unwrap()));
+
+        let minute = Duration::from_secs(60);
+
+        app.run(|| {
+            let mut settings = settings.write();
+            settings.status_text = "🌴 On vacation".to_string();
+            settings.status_until = ClearAfter::Hour.until(Utc::now());
+        });
+        app.advance(minute);
+        app.run(|| assert_eq!(of(user).text.as_deref(), Some("🌴 On vacation")));
+
+        app.advance(hour.to_std().unwrap() - 2 * minute);
+        app.run(|| assert_eq!(of(user).text.as_deref(), Some("🌴 On vacation")));
+
+        app.advance(2 * minute);
+        app.run(|| {
+            assert!(settings.peek().status_text.is_empty());
+            assert_eq!(settings.peek().status_until, None);
+            assert_eq!(of(user).text, None);
+        });
+
+        // A status that ran out while the app was closed is cleared right away.
+        app.run(|| {
+            let mut settings = settings.write();
+            settings.status_text = "🌴 On vacation".to_string();
+            settings.status_until = Some(Utc::now() - hour);
+        });
+        app.advance(minute);
+        app.run(|| {
+            assert!(settings.peek().status_text.is_empty());
+            assert_eq!(of(user).text, None);
+        });
+    }
+}
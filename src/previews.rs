@@ -0,0 +1,409 @@
+//! The previews module unfurls links posted in messages. The page behind a link is fetched once through the
+//! [`PreviewFetcher`] in [`FETCHER`], its OpenGraph metadata is parsed into a [`LinkPreview`] and the result is cached
+//! per URL in [`PREVIEWS`].
+//!
+//! Fetching a page tells its site the user's address, so links in messages from others are only fetched once the user
+//! asks for their preview, or turned on previews for every link in the settings. Pages are read up to
+//! [`MAX_PAGE_SIZE`] and for at most [`FETCH_TIMEOUT`], and only HTML is read at all. A page that failed is tried
+//! again once it is asked for after [`RETRY_AFTER`].
+//!
+//! Browsers only let a page read other sites that allow it, which few do, so there are no previews on the web, see
+//! [`SUPPORTED`].
+
+use std::{collections::HashMap, future::Future, pin::Pin, rc::Rc, time::Duration};
+
+use dioxus::prelude::*;
+use futures_util::StreamExt;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+
+use crate::time_format;
+
+/// How much of a page is read. The metadata previews are made from is in the head, at the top of the page.
+pub const MAX_PAGE_SIZE: usize = 512 * 1024;
+
+/// How long fetching a page may take, from connecting until its last byte.
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a page that could not be fetched is not tried again.
+pub const RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Whether links are unfurled on this platform.
+pub const SUPPORTED: bool = !cfg!(target_arch = "wasm32");
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub site_name: Option<String>,
+}
+
+impl LinkPreview {
+    /// A preview is only worth showing if the page told us something about itself.
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none() && self.image.is_none()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PreviewState {
+    Loading,
+    Ready(LinkPreview),
+    Failed,
+}
+
+pub type FetchFuture = Pin<Box<dyn Future<Output = Result<String, String>>>>;
+
+/// Fetches the HTML of a page. Swap the fetcher in [`FETCHER`] to serve pages from somewhere else, like a local stub.
+pub trait PreviewFetcher {
+    fn fetch(&self, url: &str) -> FetchFuture;
+}
+
+/// Fetches pages over HTTP(S). Anything but HTML is refused, and pages are cut off at `max_size` bytes.
+pub struct HttpFetcher {
+    client: reqwest::Client,
+    timeout: Duration,
+    max_size: usize,
+}
+
+impl Default for HttpFetcher {
+    fn default() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            timeout: FETCH_TIMEOUT,
+            max_size: MAX_PAGE_SIZE,
+        }
+    }
+}
+
+impl PreviewFetcher for HttpFetcher {
+    fn fetch(&self, url: &str) -> FetchFuture {
+        let request = self.client.get(url).header(ACCEPT, "text/html").timeout(self.timeout);
+        let max_size = self.max_size;
+
+        Box::pin(async move {
+            let response = request.send().await
+                .and_then(|response| response.error_for_status())
+                .map_err(|err| err.to_string())?;
+
+            let content_type = response.headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+
+            if !is_html(content_type) {
+                return Err(format!("not a page: {content_type}"));
+            }
+
+            let mut page = Vec::new();
+            let mut chunks = response.bytes_stream();
+
+            while let Some(chunk) = chunks.next().await {
+                let chunk = chunk.map_err(|err| err.to_string())?;
+
+                page.extend_from_slice(&chunk[..chunk.len().min(max_size - page.len())]);
+
+                if page.len() == max_size {
+                    break;
+                }
+            }
+
+            Ok(String::from_utf8_lossy(&page).into_owned())
+        })
+    }
+}
+
+fn is_html(content_type: &str) -> bool {
+    content_type.split(';')
+        .next()
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/html"))
+}
+
+pub static FETCHER: GlobalSignal<Rc<dyn PreviewFetcher>> = Global::new(|| Rc::new(HttpFetcher::default()));
+pub static PREVIEWS: GlobalSignal<HashMap<String, PreviewState>> = Global::new(HashMap::new);
+
+/// Fetch the preview of `url` in the background unless it is already cached or being fetched. The result lands in
+/// [`PREVIEWS`], a failure only for [`RETRY_AFTER`].
+pub fn request(url: &str) {
+    if PREVIEWS.peek().contains_key(url) {
+        return;
+    }
+
+    PREVIEWS.write().insert(url.to_string(), PreviewState::Loading);
+
+    let url = url.to_string();
+    let fetch = FETCHER.peek().fetch(&url);
+
+    // Owned by the root scope, so that the result lands even if the message scrolled out of view meanwhile.
+    spawn_forever(async move {
+        let state = match fetch.await {
+            Ok(html) => PreviewState::Ready(parse(&url, &html)),
+            Err(_) => PreviewState::Failed,
+        };
+
+        let failed = state == PreviewState::Failed;
+        PREVIEWS.write().insert(url.clone(), state);
+
+        if failed {
+            time_format::sleep(RETRY_AFTER).await;
+            PREVIEWS.write().remove(&url);
+        }
+    });
+}
+
+/// The URLs in a message, in the order they appear.
+pub fn find_urls(content: &str) -> Vec<&str> {
+    content.split_whitespace()
+        .filter_map(|word| {
+            let start = word.find("https://").or_else(|| word.find("http://"))?;
+            let url = word[start..].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '>', '"', '\'']);
+
+            url::Url::parse(url).ok().map(|_| url)
+        })
+        .collect()
+}
+
+/// Build a preview of `url` from the OpenGraph `<meta>` tags of its page, falling back to the `<title>` and the
+/// description meta tag.
+pub fn parse(url: &str, html: &str) -> LinkPreview {
+    let mut meta = HashMap::new();
+    let mut rest = html;
+
+    while let Some(start) = find_ignore_case(rest, "<meta") {
+        rest = &rest[start + 5..];
+
+        let end = rest.find('>').unwrap_or(rest.len());
+        let attributes = parse_attributes(&rest[..end]);
+
+        let key = attributes.get("property").or_else(|| attributes.get("name"));
+
+        if let (Some(key), Some(content)) = (key, attributes.get("content")) {
+            meta.entry(key.to_ascii_lowercase()).or_insert_with(|| content.clone());
+        }
+
+        rest = &rest[end..];
+    }
+
+    let title = meta.remove("og:title").or_else(|| {
+        let start = find_ignore_case(html, "<title")?;
+        let start = start + html[start..].find('>')? + 1;
+        let end = start + find_ignore_case(&html[start..], "</title")?;
+
+        Some(decode_entities(html[start..end].trim()))
+    });
+
+    let image = meta.remove("og:image")
+        .or_else(|| meta.remove("twitter:image"))
+        .and_then(|image| url::Url::parse(url).ok()?.join(&image).ok())
+        .map(|image| image.to_string());
+
+    LinkPreview {
+        url: url.to_string(),
+        title: title.filter(|title| !title.is_empty()),
+        description: meta.remove("og:description").or_else(|| meta.remove("description")),
+        image,
+        site_name: meta.remove("og:site_name"),
+    }
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Parse `key="value"` pairs of a tag, lower casing the keys and decoding entities in the values.
+fn parse_attributes(tag: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = tag.trim_start();
+
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].split_whitespace().last().unwrap_or_default().to_ascii_lowercase();
+        let value = rest[eq + 1..].trim_start();
+
+        let (value, remaining) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let end = value[1..].find(quote).map(|end| end + 1).unwrap_or(value.len());
+
+                (&value[1..end], value.get(end + 1..).unwrap_or_default())
+            }
+            _ => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+
+                (value[..end].trim_end_matches('/'), &value[end..])
+            }
+        };
+
+        attributes.insert(key, decode_entities(value));
+        rest = remaining;
+    }
+
+    attributes
+}
+
+fn decode_entities(value: &str) -> String {
+    value.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+
+    /// Answer the first request to the returned URL with `response`, after `delay`.
+    fn serve(response: Vec<u8>, delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 4096]);
+
+            std::thread::sleep(delay);
+            let _ = stream.write_all(&response);
+        });
+
+        format!("http://{address}/")
+    }
+
+    fn response(status: &str, content_type: &str, body: &[u8]) -> Vec<u8> {
+        let mut response = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len(),
+        ).into_bytes();
+
+        response.extend_from_slice(body);
+        response
+    }
+
+    fn fetcher(timeout: Duration, max_size: usize) -> HttpFetcher {
+        HttpFetcher {
+            client: reqwest::Client::builder().no_proxy().build().unwrap(),
+            timeout,
+            max_size,
+        }
+    }
+
+    fn fetch(fetcher: &HttpFetcher, url: &str) -> Result<String, String> {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(fetcher.fetch(url))
+    }
+
+    const PAGE: &str = r#"<html><head>
+        <title>Fallback</title>
+        <meta property="og:title" content="Stub &amp; Co">
+        <meta property="og:image" content="/cover.png">
+        <meta name="description" content='A page served by a stub'>
+    </head><body></body></html>"#;
+
+    #[test]
+    fn fetches_and_parses_pages() {
+        let url = serve(response("200 OK", "text/html; charset=utf-8", PAGE.as_bytes()), Duration::ZERO);
+
+        let html = fetch(&fetcher(FETCH_TIMEOUT, MAX_PAGE_SIZE), &url).unwrap();
+        let preview = parse(&url, &html);
+
+        assert_eq!(preview.title.as_deref(), Some("Stub & Co"));
+        assert_eq!(preview.description.as_deref(), Some("A page served by a stub"));
+        assert_eq!(preview.image, Some(format!("{url}cover.png")));
+    }
+
+    #[test]
+    fn refuses_what_is_not_html() {
+        let url = serve(response("200 OK", "image/png", b"\x89PNG"), Duration::ZERO);
+
+        assert!(fetch(&fetcher(FETCH_TIMEOUT, MAX_PAGE_SIZE), &url).is_err());
+    }
+
+    #[test]
+    fn fails_on_error_status() {
+        let url = serve(response("404 Not Found", "text/html", PAGE.as_bytes()), Duration::ZERO);
+
+        assert!(fetch(&fetcher(FETCH_TIMEOUT, MAX_PAGE_SIZE), &url).is_err());
+    }
+
+    #[test]
+    fn stops_reading_at_the_size_limit() {
+        let mut body = PAGE.as_bytes().to_vec();
+        body.resize(1024 * 1024, b' ');
+        let url = serve(response("200 OK", "text/html", &body), Duration::ZERO);
+
+        let html = fetch(&fetcher(FETCH_TIMEOUT, 4096), &url).unwrap();
+
+        assert_eq!(html.len(), 4096);
+        assert_eq!(parse(&url, &html).title.as_deref(), Some("Stub & Co"));
+    }
+
+    #[test]
+    fn gives_up_on_slow_pages() {
+        let url = serve(response("200 OK", "text/html", PAGE.as_bytes()), Duration::from_secs(5));
+
+        assert!(fetch(&fetcher(Duration::from_millis(200), MAX_PAGE_SIZE), &url).is_err());
+    }
+
+    /// Fails every fetch, and counts them.
+    #[derive(Default)]
+    struct Failing(Rc<std::cell::Cell<usize>>);
+
+    impl PreviewFetcher for Failing {
+        fn fetch(&self, _url: &str) -> FetchFuture {
+            self.0.set(self.0.get() + 1);
+
+            Box::pin(async { Err("unreachable".to_string()) })
+        }
+    }
+
+    #[test]
+    fn failures_are_tried_again_later() {
+        let mut app = crate::testing::runtime();
+        let url = "https://example.com/";
+        let fetches = app.run(|| {
+            let failing = Failing::default();
+            let fetches = failing.0.clone();
+            *FETCHER.write() = Rc::new(failing);
+
+            request(url);
+            fetches
+        });
+
+        app.advance(Duration::from_secs(1));
+        app.run(|| {
+            assert_eq!(PREVIEWS.peek().get(url), Some(&PreviewState::Failed));
+            request(url);
+        });
+        assert_eq!(fetches.get(), 1);
+
+        app.advance(RETRY_AFTER);
+        app.run(|| {
+            assert_eq!(PREVIEWS.peek().get(url), None);
+            request(url);
+        });
+        assert_eq!(fetches.get(), 2);
+    }
+
+    #[test]
+    fn falls_back_to_the_title() {
+        let preview = parse("https://example.com/", "<html><head><TITLE> Plain &lt;page&gt; </TITLE></head></html>");
+
+        assert_eq!(preview.title.as_deref(), Some("Plain <page>"));
+        assert!(preview.description.is_none() && preview.image.is_none());
+    }
+
+    #[test]
+    fn finds_urls_in_text() {
+        let urls = find_urls("see (https://example.com/a), and http://example.org. not ftp://x or https://");
+
+        assert_eq!(urls, ["https://example.com/a", "http://example.org"]);
+    }
+}
@@ -0,0 +1,324 @@
+//! The push module connects the web build to Web Push. A service worker, `assets/sw.js`, is registered by
+//! [`use_push`]; it shows pushed messages as notifications while the app is in the background and opens their room
+//! when they are clicked.
+//!
+//! The worker is served from `/assets/`, and asks for the whole site as its scope, which the server has to allow by
+//! sending `Service-Worker-Allowed: /` with it. Without the header the worker is registered for `/assets/` only. It
+//! then does not control the page, which it does not need to: notifications, their clicks and [`test_push`] work the
+//! same.
+//!
+//! The app subscribes to Web Push when it is built with the public VAPID key of a push server in `DX_CHAT_PUSH_KEY`,
+//! and keeps the subscription in storage under `push-subscription` for the server. Without a server,
+//! [`test_push`] posts a message to the service worker the same way a push would arrive.
+//!
+//! Other platforms do not use Web Push, on them [`permission`] is [`Permission::Unsupported`].
+
+#[cfg(target_arch = "wasm32")]
+use dioxus::prelude::*;
+use serde::Deserialize;
+#[cfg(any(target_arch = "wasm32", test))]
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Whether the browser shows notifications for the app, as reported by the Notification API.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// Not asked yet.
+    Default,
+    Granted,
+    Denied,
+    Unsupported,
+}
+
+impl Permission {
+    /// The permission the Notification API reports, or [`Permission::Unsupported`] for anything the app does not
+    /// know, like the `prompt` of older browsers.
+    #[cfg(any(target_arch = "wasm32", test))]
+    fn from_js(value: serde_json::Value) -> Permission {
+        serde_json::from_value(value).unwrap_or(Permission::Unsupported)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+const SERVICE_WORKER: Asset = asset!("/assets/sw.js");
+
+#[cfg(target_arch = "wasm32")]
+const PUSH_KEY: Option<&str> = option_env!("DX_CHAT_PUSH_KEY");
+
+/// Registers the service worker and subscribes to Web Push once notifications are allowed. Sends the rooms of
+/// clicked notifications and new subscriptions to the app.
+#[cfg(target_arch = "wasm32")]
+const REGISTER_JS: &str = r#"
+    const [worker, key] = await dioxus.recv();
+
+    if (!("serviceWorker" in navigator)) {
+        return;
+    }
+
+    // The scope of the whole site needs the Service-Worker-Allowed header, without it the default scope is taken.
+    window.dxPushRegistration = navigator.serviceWorker.register(worker, { scope: "/" })
+        .catch(() => navigator.serviceWorker.register(worker));
+
+    const registration = await window.dxPushRegistration;
+
+    navigator.serviceWorker.addEventListener("message", (event) => {
+        if (event.data?.type === "open-room" && event.data.room) {
+            dioxus.send({ Open: event.data.room });
+        }
+    });
+
+    window.dxSubscribePush = async () => {
+        if (!key || !("PushManager" in window) || Notification.permission !== "granted") {
+            return;
+        }
+
+        const subscription = await registration.pushManager.getSubscription()
+            ?? await registration.pushManager.subscribe({ userVisibleOnly: true, applicationServerKey: key });
+
+        dioxus.send({ Subscribed: subscription.toJSON() });
+    };
+
+    await window.dxSubscribePush().catch(() => {});
+
+    // Stays pending so that the channel to the app stays open.
+    await new Promise(() => {});
+"#;
+
+#[cfg(target_arch = "wasm32")]
+const PERMISSION_JS: &str = r#"
+    return "Notification" in window ? Notification.permission : "unsupported";
+"#;
+
+#[cfg(target_arch = "wasm32")]
+const REQUEST_PERMISSION_JS: &str = r#"
+    if (!("Notification" in window)) {
+        return "unsupported";
+    }
+
+    const permission = await Notification.requestPermission();
+
+    if (permission === "granted") {
+        await window.dxSubscribePush?.().catch(() => {});
+    }
+
+    return permission;
+"#;
+
+#[cfg(target_arch = "wasm32")]
+const TEST_PUSH_JS: &str = r#"
+    const message = await dioxus.recv();
+    // Not `navigator.serviceWorker.ready`, which never resolves when the page is outside the scope of the worker.
+    const registration = await window.dxPushRegistration?.catch(() => null);
+    const worker = registration?.active ?? registration?.waiting ?? registration?.installing;
+
+    if (worker && worker.state !== "activated") {
+        await new Promise((resolve) => worker.addEventListener("statechange", () => {
+            if (worker.state === "activated") {
+                resolve();
+            }
+        }));
+    }
+
+    worker?.postMessage({ type: "push", message });
+"#;
+
+/// A pushed message, as a push server sends it to the service worker and [`test_push`] hands it over.
+#[cfg(any(target_arch = "wasm32", test))]
+#[derive(Debug, Serialize)]
+struct PushMessage {
+    room: Option<Uuid>,
+    title: String,
+    body: String,
+}
+
+#[cfg(any(target_arch = "wasm32", test))]
+#[derive(Debug, PartialEq, Deserialize)]
+enum FromWorker {
+    /// A notification of the room was clicked.
+    Open(Uuid),
+    Subscribed(serde_json::Value),
+}
+
+/// Register the service worker and open the rooms of clicked notifications. Called once, by the layout.
+pub fn use_push() {
+    #[cfg(target_arch = "wasm32")]
+    use_future(|| async {
+        let mut worker = document::eval(REGISTER_JS);
+        let _ = worker.send((SERVICE_WORKER.to_string(), PUSH_KEY));
+
+        while let Ok(message) = worker.recv::<FromWorker>().await {
+            match message {
+                FromWorker::Open(id) => {
+                    navigator().push(crate::Route::Chat { id });
+                }
+                FromWorker::Subscribed(subscription) => crate::storage::save("push-subscription", &subscription),
+            }
+        }
+    });
+}
+
+pub async fn permission() -> Permission {
+    #[cfg(target_arch = "wasm32")]
+    return document::eval(PERMISSION_JS).join().await.map_or(Permission::Unsupported, Permission::from_js);
+
+    #[cfg(not(target_arch = "wasm32"))]
+    return Permission::Unsupported;
+}
+
+/// Ask the user to allow notifications, and subscribe to Web Push if they do.
+pub async fn request_permission() -> Permission {
+    #[cfg(target_arch = "wasm32")]
+    return document::eval(REQUEST_PERMISSION_JS).join().await.map_or(Permission::Unsupported, Permission::from_js);
+
+    #[cfg(not(target_arch = "wasm32"))]
+    return Permission::Unsupported;
+}
+
+/// Stand in for a push server: hand a message for `room` to the service worker as if it was pushed. It is shown
+/// even while the app is visible, so that notifications can be tried out.
+pub fn test_push(room: Option<Uuid>, title: String, body: String) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let push = document::eval(TEST_PUSH_JS);
+        let _ = push.send(PushMessage { room, title, body });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = (room, title, body);
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::Route;
+
+    /// Runs `assets/sw.js` in Node with the service worker API stubbed out, hands it `events` with the windows of
+    /// `clients` open, and reports what it called.
+    const WORKER_HARNESS: &str = r#"
+        const [worker, clients, events] = process.argv.slice(1);
+        const calls = [];
+        const listeners = {};
+
+        const windows = JSON.parse(clients).map((visibilityState, index) => ({
+            visibilityState,
+            focus: async () => calls.push(["focus", index]),
+            postMessage: (message) => calls.push(["postMessage", index, message]),
+        }));
+
+        const self = {
+            addEventListener: (type, listener) => listeners[type] = listener,
+            skipWaiting: () => {},
+            clients: {
+                claim: async () => {},
+                matchAll: async () => windows,
+                openWindow: async (url) => calls.push(["openWindow", url]),
+            },
+            registration: {
+                showNotification: async (title, options) => calls.push(["showNotification", title, options]),
+            },
+        };
+
+        new Function("self", require("fs").readFileSync(worker, "utf8"))(self);
+
+        (async () => {
+            for (const [type, event] of JSON.parse(events)) {
+                const pending = [];
+
+                if (type === "push") {
+                    const data = event.data;
+                    event.data = data === null ? null : { json: () => data };
+                }
+
+                if (type === "notificationclick") {
+                    event.notification.close = () => calls.push(["close"]);
+                }
+
+                listeners[type]({ ...event, waitUntil: (promise) => pending.push(promise) });
+                await Promise.all(pending);
+            }
+
+            console.log(JSON.stringify(calls));
+        })();
+    "#;
+
+    fn run_worker(clients: &[&str], events: Value) -> Value {
+        let worker = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/sw.js");
+        let output = std::process::Command::new("node")
+            .args(["-e", WORKER_HARNESS, worker, &json!(clients).to_string(), &events.to_string()])
+            .output()
+            .expect("the service worker tests need Node");
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        serde_json::from_slice(&output.stdout).unwrap()
+    }
+
+    #[test]
+    fn reads_the_notification_permission() {
+        let cases = [
+            (json!("default"), Permission::Default),
+            (json!("granted"), Permission::Granted),
+            (json!("denied"), Permission::Denied),
+            (json!("unsupported"), Permission::Unsupported),
+            (json!("prompt"), Permission::Unsupported),
+            (Value::Null, Permission::Unsupported),
+        ];
+
+        for (value, permission) in cases {
+            assert_eq!(Permission::from_js(value.clone()), permission, "{value}");
+        }
+    }
+
+    #[test]
+    fn pushes_are_shown_while_the_app_is_hidden() {
+        let room = Uuid::new_v4();
+        let pushed = json!({ "room": room, "title": "Yoda", "body": "Do or do not" });
+        let shown = json!([["showNotification", "Yoda", { "body": "Do or do not", "tag": room, "data": pushed }]]);
+
+        assert_eq!(run_worker(&["hidden"], json!([["push", { "data": pushed }]])), shown);
+        assert_eq!(run_worker(&["visible", "hidden"], json!([["push", { "data": pushed }]])), json!([]));
+
+        // A push without data still says something.
+        let empty = json!([["showNotification", "dx-chat", { "body": "", "data": {} }]]);
+        assert_eq!(run_worker(&[], json!([["push", { "data": null }]])), empty);
+    }
+
+    #[test]
+    fn test_pushes_are_shown_like_pushes() {
+        let room = Uuid::new_v4();
+        let message = serde_json::to_value(PushMessage { room: Some(room), title: "Yoda".into(), body: "Do".into() })
+            .unwrap();
+
+        // Even while the app is visible, so that they can be tried out.
+        let calls = run_worker(&["visible"], json!([["message", { "data": { "type": "push", "message": message } }]]));
+
+        assert_eq!(calls, json!([["showNotification", "Yoda", { "body": "Do", "tag": room, "data": message }]]));
+    }
+
+    #[test]
+    fn clicks_open_the_room() {
+        let room = Uuid::new_v4();
+        let click = json!([["notificationclick", { "notification": { "data": { "room": room } } }]]);
+
+        // An open window is focused and told to open the room, which the app reads as such.
+        let calls = run_worker(&["hidden"], click.clone());
+        assert_eq!(calls, json!([["close"], ["focus", 0], ["postMessage", 0, { "type": "open-room", "room": room }]]));
+
+        let open: FromWorker = serde_json::from_value(json!({ "Open": calls[2][2]["room"] })).unwrap();
+        assert_eq!(open, FromWorker::Open(room));
+
+        // Otherwise the room is opened in a new one.
+        let route = Route::Chat { id: room }.to_string();
+        assert_eq!(run_worker(&[], click), json!([["close"], ["openWindow", route]]));
+    }
+
+    #[test]
+    fn reads_subscriptions() {
+        let subscribed = json!({ "Subscribed": { "endpoint": "https://push.example/1" } });
+
+        assert!(matches!(serde_json::from_value(subscribed).unwrap(), FromWorker::Subscribed(_)));
+    }
+}
@@ -0,0 +1,101 @@
+//! The render stats module counts how often components render. It is used to check that a change in one place only
+//! re-renders the components that show it, for example that posting in one room does not re-render the others.
+//!
+//! Counting is compiled in with the `render-stats` feature, and in tests. With the feature, every call to [`record`]
+//! logs the running count of the component; tests read the counts with `counts`. Otherwise [`record`] does nothing.
+
+#[cfg(any(test, feature = "render-stats"))]
+use std::{cell::RefCell, collections::BTreeMap};
+
+#[cfg(any(test, feature = "render-stats"))]
+thread_local! {
+    static COUNTS: RefCell<BTreeMap<&'static str, usize>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Count a render of `component`. Called at the top of the component function.
+#[cfg(any(test, feature = "render-stats"))]
+pub fn record(component: &'static str) {
+    let count = COUNTS.with_borrow_mut(|counts| {
+        let count = counts.entry(component).or_default();
+        *count += 1;
+        *count
+    });
+
+    #[cfg(feature = "render-stats")]
+    dioxus::logger::tracing::info!("{component} rendered {count} times");
+    #[cfg(not(feature = "render-stats"))]
+    let _ = count;
+}
+
+#[cfg(not(any(test, feature = "render-stats")))]
+pub fn record(_component: &'static str) {}
+
+/// The render count of every component that has rendered so far on this thread.
+#[cfg(test)]
+pub fn counts() -> BTreeMap<&'static str, usize> {
+    COUNTS.with_borrow(Clone::clone)
+}
+
+/// The benchmark behind the render counts in the README: post 10 messages with the Starwars room open, in that room
+/// and in another one, and count how often each component renders.
+#[cfg(test)]
+mod tests {
+    use dioxus::prelude::*;
+
+    use super::*;
+    use crate::testing;
+    use crate::{room_id, Message, MessageKind, Route, ROOMS};
+
+    const COMPONENTS: [&str; 6] = ["Navbar", "RoomLink", "Chat", "MessageList", "ChatInput", "MessageBubble"];
+
+    /// How often each component renders while 10 messages are posted to `room`, with the Starwars room open.
+    fn renders_posting_to(room: &str) -> Vec<(&'static str, usize)> {
+        let starwars = room_id("Starwars");
+        let room = room_id(room);
+        let mut app = testing::app(Route::Chat { id: starwars });
+
+        let before = counts();
+        assert!(before.get("MessageList").is_some_and(|count| *count > 0), "the room is not open");
+
+        for i in 0..10 {
+            app.run(|| {
+                let room = ROOMS.peek()[&room];
+                room.post(Message::new(uuid::Uuid::new_v4(), format!("Message {i}"), MessageKind::Text));
+            });
+            app.render();
+        }
+
+        let after = counts();
+
+        COMPONENTS.into_iter()
+            .map(|component| {
+                let count = |counts: &BTreeMap<_, _>| counts.get(component).copied().unwrap_or_default();
+                (component, count(&after) - count(&before))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn posting_to_the_open_room() {
+        assert_eq!(renders_posting_to("Starwars"), [
+            ("Navbar", 0),
+            ("RoomLink", 0),
+            ("Chat", 0),
+            ("MessageList", 10),
+            ("ChatInput", 0),
+            ("MessageBubble", 10),
+        ]);
+    }
+
+    #[test]
+    fn posting_to_another_room() {
+        assert_eq!(renders_posting_to("Spiderman"), [
+            ("Navbar", 0),
+            ("RoomLink", 10),
+            ("Chat", 0),
+            ("MessageList", 0),
+            ("ChatInput", 0),
+            ("MessageBubble", 0),
+        ]);
+    }
+}
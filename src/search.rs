@@ -0,0 +1,318 @@
+//! The search module keeps an inverted index over the content of every message in every room. Messages are added to
+//! [`SEARCH`] as they are posted, queries are parsed with [`Query::parse`] and answered by [`SearchIndex::search`].
+//!
+//! A query is a list of words that all have to match the beginning of a word in the message, plus filters:
+//! `from:<user>`, `in:<room>`, `before:<yyyy-mm-dd>`, `after:<yyyy-mm-dd>` and `has:link`.
+//!
+//! The index keeps a [`Document`] for every message, enough to show it as a result, so that messages in pages of
+//! history that have not been loaded are found and shown too.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::crdt::Stamp;
+use crate::{previews, Message, MessageKind, Rooms};
+
+/// Identifies a message: the room it was posted in and its id within the room.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MessageRef {
+    pub room: Uuid,
+    pub id: Stamp,
+}
+
+/// What the index keeps of a message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Document {
+    pub author: Uuid,
+    pub sent: DateTime<Utc>,
+    pub content: String,
+    has_link: bool,
+}
+
+#[derive(Default)]
+pub struct SearchIndex {
+    postings: BTreeMap<String, BTreeSet<MessageRef>>,
+    documents: HashMap<MessageRef, Document>,
+}
+
+impl SearchIndex {
+    pub fn insert(&mut self, room: Uuid, message: &Message) {
+        // Notices are translated as they are shown, their content is a message id rather than text to search.
+        if message.kind == MessageKind::Notice {
+            return;
+        }
+
+        let key = MessageRef { room, id: message.id };
+
+        for token in tokenize(&message.content) {
+            self.postings.entry(token).or_default().insert(key);
+        }
+
+        self.documents.insert(key, Document {
+            author: message.author,
+            sent: message.sent,
+            content: message.content.clone(),
+            has_link: !previews::find_urls(&message.content).is_empty(),
+        });
+    }
+
+    pub fn remove(&mut self, room: Uuid, message: &Message) {
+        let key = MessageRef { room, id: message.id };
+
+        for token in tokenize(&message.content) {
+            if let Some(keys) = self.postings.get_mut(&token) {
+                keys.remove(&key);
+
+                if keys.is_empty() {
+                    self.postings.remove(&token);
+                }
+            }
+        }
+
+        self.documents.remove(&key);
+    }
+
+    pub fn document(&self, key: &MessageRef) -> Option<&Document> {
+        self.documents.get(key)
+    }
+
+    /// The messages matching `query`, newest first.
+    pub fn search(&self, query: &Query) -> Vec<MessageRef> {
+        let mut matches: Option<BTreeSet<MessageRef>> = None;
+
+        for term in &query.terms {
+            let found = self.prefix_matches(term);
+
+            matches = Some(match matches {
+                Some(matches) => matches.intersection(&found).copied().collect(),
+                None => found,
+            });
+        }
+
+        // A query with only filters matches every message the filters let through.
+        let candidates = match matches {
+            Some(matches) => matches,
+            None if query.has_filters() => self.documents.keys().copied().collect(),
+            None => BTreeSet::new(),
+        };
+
+        let mut results: Vec<(MessageRef, DateTime<Utc>)> = candidates.into_iter()
+            .filter_map(|key| {
+                let document = self.documents.get(&key)?;
+                query.accepts(&key, document).then_some((key, document.sent))
+            })
+            .collect();
+
+        results.sort_by(|(a, a_sent), (b, b_sent)| b_sent.cmp(a_sent).then(b.id.cmp(&a.id)));
+        results.into_iter().map(|(key, _)| key).collect()
+    }
+
+    fn prefix_matches(&self, prefix: &str) -> BTreeSet<MessageRef> {
+        self.postings.range(prefix.to_string()..)
+            .take_while(|(token, _)| token.starts_with(prefix))
+            .flat_map(|(_, keys)| keys.iter().copied())
+            .collect()
+    }
+}
+
+pub static SEARCH: GlobalSignal<SearchIndex> = Global::new(SearchIndex::default);
+
+/// The message a search result was opened for, so that the chat view can scroll to it. The chat view clears it once
+/// the message has scrolled into view, or when the room is left before that.
+pub static FOCUS: GlobalSignal<Option<MessageRef>> = Global::new(|| None);
+
+/// Add a freshly posted message to the index.
+pub fn index(room: Uuid, message: &Message) {
+    SEARCH.write().insert(room, message);
+}
+
+/// Take a message out of the index, before its content changes.
+pub fn unindex(room: Uuid, message: &Message) {
+    SEARCH.write().remove(room, message);
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    pub terms: Vec<String>,
+    pub from: Option<Vec<Uuid>>,
+    pub rooms: Option<Vec<Uuid>>,
+    pub before: Option<DateTime<Utc>>,
+    pub after: Option<DateTime<Utc>>,
+    pub has_link: bool,
+}
+
+impl Query {
+    /// Parse `input`, resolving `from:` against the names in `authors` and `in:` against the names in `rooms`.
+    /// Filters that match nothing make the whole query match nothing.
+    pub fn parse(input: &str, authors: &[(Uuid, String)], rooms: &Rooms) -> Self {
+        let mut query = Self::default();
+
+        for word in input.split_whitespace() {
+            match word.split_once(':') {
+                Some(("from", name)) if !name.is_empty() => {
+                    let name = name.to_lowercase();
+                    let ids = authors.iter()
+                        .filter(|(_, username)| matches_name(username, &name))
+                        .map(|(id, _)| *id);
+
+                    query.from.get_or_insert_with(Vec::new).extend(ids);
+                }
+                Some(("in", name)) if !name.is_empty() => {
+                    let name = name.trim_start_matches('#').to_lowercase();
+                    let ids = rooms.values()
+                        .filter(|room| matches_name(&room.info.peek().name, &name))
+                        .map(|room| room.id);
+
+                    query.rooms.get_or_insert_with(Vec::new).extend(ids);
+                }
+                Some(("before", date)) => query.before = start_of_day(date, 0),
+                Some(("after", date)) => query.after = start_of_day(date, 1),
+                Some(("has", "link")) => query.has_link = true,
+                _ => query.terms.extend(tokenize(word)),
+            }
+        }
+
+        query
+    }
+
+    fn has_filters(&self) -> bool {
+        self.from.is_some() || self.rooms.is_some() || self.before.is_some() || self.after.is_some() || self.has_link
+    }
+
+    fn accepts(&self, key: &MessageRef, document: &Document) -> bool {
+        self.from.as_ref().is_none_or(|from| from.contains(&document.author))
+            && self.rooms.as_ref().is_none_or(|rooms| rooms.contains(&key.room))
+            && self.before.is_none_or(|before| document.sent < before)
+            && self.after.is_none_or(|after| document.sent >= after)
+            && (!self.has_link || document.has_link)
+    }
+}
+
+/// Split text into lower case words for the index.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+/// A name matches when it, or one of its words, starts with `prefix`.
+fn matches_name(name: &str, prefix: &str) -> bool {
+    let name = name.to_lowercase();
+
+    name.starts_with(prefix) || name.split_whitespace().any(|word| word.starts_with(prefix))
+}
+
+/// Local midnight at the start of `date` plus `days`.
+fn start_of_day(date: &str, days: u64) -> Option<DateTime<Utc>> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?
+        .checked_add_days(chrono::Days::new(days))?;
+
+    Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+    use crate::attachments::Attachment;
+    use crate::crdt::Reactions;
+    use crate::transport::Delivery;
+
+    fn message(millis: i64, author: Uuid, content: &str) -> Message {
+        Message {
+            id: Stamp { millis, counter: 0, replica: Uuid::nil() },
+            author,
+            sent: DateTime::from_timestamp_millis(millis).unwrap(),
+            content: content.into(),
+            kind: MessageKind::Text,
+            args: BTreeMap::new(),
+            attachments: Vec::<Attachment>::new(),
+            link_previews: true,
+            edited: None,
+            reactions: Reactions::default(),
+            delivery: Delivery::Delivered,
+            client_id: Uuid::new_v4(),
+        }
+    }
+
+    fn query(terms: &[&str]) -> Query {
+        Query { terms: terms.iter().map(|term| term.to_string()).collect(), ..Query::default() }
+    }
+
+    #[test]
+    fn finds_words_by_prefix_newest_first() {
+        let room = Uuid::new_v4();
+        let author = Uuid::new_v4();
+        let mut index = SearchIndex::default();
+
+        index.insert(room, &message(1_000, author, "The Chosen One"));
+        index.insert(room, &message(2_000, author, "chosen, not chose"));
+        index.insert(room, &message(3_000, author, "nothing here"));
+
+        let ids: Vec<i64> = index.search(&query(&["chos"])).iter().map(|key| key.id.millis).collect();
+        assert_eq!(ids, [2_000, 1_000]);
+
+        let ids: Vec<i64> = index.search(&query(&["chosen", "one"])).iter().map(|key| key.id.millis).collect();
+        assert_eq!(ids, [1_000]);
+
+        assert!(index.search(&query(&[])).is_empty());
+    }
+
+    #[test]
+    fn keeps_what_results_show() {
+        let room = Uuid::new_v4();
+        let author = Uuid::new_v4();
+        let mut index = SearchIndex::default();
+        let posted = message(1_000, author, "see https://example.com");
+
+        index.insert(room, &posted);
+
+        let key = index.search(&query(&["see"]))[0];
+        let document = index.document(&key).unwrap();
+        assert_eq!((document.author, document.sent, document.content.as_str()), (author, posted.sent, "see https://example.com"));
+    }
+
+    #[test]
+    fn filters_without_terms() {
+        let room = Uuid::new_v4();
+        let other_room = Uuid::new_v4();
+        let (obi, yoda) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut index = SearchIndex::default();
+
+        index.insert(room, &message(1_000, obi, "hello there https://example.com"));
+        index.insert(room, &message(2_000, yoda, "do or do not"));
+        index.insert(other_room, &message(3_000, obi, "hello again"));
+
+        let from_obi = Query { from: Some(vec![obi]), ..Query::default() };
+        assert_eq!(index.search(&from_obi).len(), 2);
+
+        let in_room = Query { from: Some(vec![obi]), rooms: Some(vec![room]), ..Query::default() };
+        assert_eq!(index.search(&in_room).len(), 1);
+
+        let links = Query { has_link: true, ..Query::default() };
+        assert_eq!(index.search(&links)[0].id.millis, 1_000);
+
+        let before = Query { before: Some(DateTime::from_timestamp_millis(1_000).unwrap() + TimeDelta::seconds(1)), ..Query::default() };
+        assert_eq!(index.search(&before).len(), 1);
+    }
+
+    #[test]
+    fn removed_messages_are_not_found() {
+        let room = Uuid::new_v4();
+        let author = Uuid::new_v4();
+        let mut index = SearchIndex::default();
+        let posted = message(1_000, author, "You were the Chosen One");
+
+        index.insert(room, &posted);
+        index.remove(room, &posted);
+
+        assert!(index.search(&query(&["chosen"])).is_empty());
+        assert!(index.postings.is_empty() && index.documents.is_empty());
+    }
+}
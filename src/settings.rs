@@ -0,0 +1,234 @@
+//! The settings module holds the user's preferences. [`Settings`] is loaded from storage when the app starts,
+//! provided to every component through context by [`use_settings_provider`] and saved whenever it changes.
+//!
+//! Preferences that code outside of components needs, the language and the clock, are mirrored into
+//! [`LANGUAGE`](crate::i18n::LANGUAGE) and [`CLOCK`](crate::time_format::CLOCK).
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::i18n::{Language, LANGUAGE};
+use crate::notifications::{Level, QuietHours};
+use crate::presence::Status;
+use crate::shortcuts::Shortcuts;
+use crate::storage;
+use crate::theme::{self, CustomTheme, Theme};
+use crate::time_format::{Clock, CLOCK};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum FontSize {
+    Small,
+    #[default]
+    Medium,
+    Large,
+}
+
+impl FontSize {
+    pub const ALL: [FontSize; 3] = [FontSize::Small, FontSize::Medium, FontSize::Large];
+
+    /// The root font size in pixels, everything else is sized relative to it.
+    pub fn pixels(self) -> u32 {
+        match self {
+            FontSize::Small => 14,
+            FontSize::Medium => 16,
+            FontSize::Large => 18,
+        }
+    }
+}
+
+/// The key that sends the message in the composer. The other one inserts a new line.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SendKey {
+    Enter,
+    /// Ctrl+Enter, or Cmd+Enter on macOS.
+    CtrlEnter,
+    /// Only the send button, and Ctrl+Enter on a hardware keyboard, send. Enter always starts a new line.
+    Button,
+}
+
+impl SendKey {
+    pub const ALL: [SendKey; 3] = [SendKey::Enter, SendKey::CtrlEnter, SendKey::Button];
+}
+
+/// Enter sends, except on touch screens: their keyboards have no Shift+Enter, so Enter is the only way to start a new
+/// line there.
+impl Default for SendKey {
+    fn default() -> Self {
+        if touch_screen() {
+            SendKey::Button
+        } else {
+            SendKey::Enter
+        }
+    }
+}
+
+/// Whether the device is mainly used by touch. On the web that is what the `pointer` media feature reports, which is
+/// coarse for phones and tablets but not for laptops that also have a touch screen.
+#[cfg(target_arch = "wasm32")]
+fn touch_screen() -> bool {
+    web_sys::window()
+        .and_then(|window| window.match_media("(pointer: coarse)").ok().flatten())
+        .is_some_and(|query| query.matches())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn touch_screen() -> bool {
+    cfg!(feature = "mobile")
+}
+
+/// How message times are shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum TimestampStyle {
+    /// "5 min ago", "yesterday at 14:02".
+    #[default]
+    Relative,
+    /// The time of day, and the date for messages from other days.
+    Exact,
+}
+
+impl TimestampStyle {
+    pub const ALL: [TimestampStyle; 2] = [TimestampStyle::Relative, TimestampStyle::Exact];
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Notifications {
+    pub enabled: bool,
+    pub sound: bool,
+    /// Only notify about messages that mention the user, in rooms without a level of their own.
+    pub mentions_only: bool,
+    /// The rooms with a level other than [`Level::Default`].
+    pub levels: HashMap<Uuid, Level>,
+    pub quiet_hours: QuietHours,
+}
+
+impl Notifications {
+    /// The level of `room`, with [`Level::Default`] resolved.
+    pub fn level(&self, room: Uuid) -> Level {
+        match self.levels.get(&room).copied().unwrap_or_default() {
+            Level::Default if self.mentions_only => Level::Mentions,
+            Level::Default => Level::All,
+            level => level,
+        }
+    }
+
+    pub fn set_level(&mut self, room: Uuid, level: Level) {
+        if level == Level::Default {
+            self.levels.remove(&room);
+        } else {
+            self.levels.insert(room, level);
+        }
+    }
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sound: true,
+            mentions_only: false,
+            levels: HashMap::new(),
+            quiet_hours: QuietHours::default(),
+        }
+    }
+}
+
+/// Missing fields take their default, so settings saved by an older version still load.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub theme: Theme,
+    pub custom_themes: Vec<CustomTheme>,
+    pub font_size: FontSize,
+    /// Fewer margins and no avatars in the message list.
+    pub compact: bool,
+    pub send_key: SendKey,
+    /// Ask whether to keep very long pastes before they can be sent.
+    pub confirm_long_pastes: bool,
+    /// Tell others which messages the user has read, and see which messages they have read.
+    pub read_receipts: bool,
+    /// Fetch the previews of links in messages from others without asking, which tells their sites the user's address.
+    pub auto_previews: bool,
+    pub notifications: Notifications,
+    pub timestamps: TimestampStyle,
+    pub clock: Clock,
+    pub language: Language,
+    pub shortcuts: Shortcuts,
+    /// The status the user picked. Online turns into away while the user is idle.
+    pub status: Status,
+    /// A custom status like "🌴 On vacation", shown next to the status.
+    pub status_text: String,
+    /// When the custom status clears itself, if ever.
+    pub status_until: Option<DateTime<Utc>>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            theme: Theme::default(),
+            custom_themes: Vec::new(),
+            font_size: FontSize::default(),
+            compact: false,
+            send_key: SendKey::default(),
+            confirm_long_pastes: true,
+            read_receipts: true,
+            auto_previews: false,
+            notifications: Notifications::default(),
+            timestamps: TimestampStyle::default(),
+            clock: Clock::default(),
+            language: Language::system(),
+            shortcuts: Shortcuts::default(),
+            status: Status::Online,
+            status_text: String::new(),
+            status_until: None,
+        }
+    }
+}
+
+/// Sets the root font size of the document.
+const FONT_SIZE_JS: &str = r#"
+    document.documentElement.style.fontSize = `${await dioxus.recv()}px`;
+"#;
+
+/// Load the settings and provide them to every component. Called once, by the root component.
+pub fn use_settings_provider() -> Signal<Settings> {
+    let settings = use_context_provider(|| {
+        let settings: Settings = storage::load("settings").unwrap_or_default();
+
+        // Mirrored before the first render so that nothing renders in the wrong language first.
+        *LANGUAGE.write() = settings.language;
+        *CLOCK.write() = settings.clock;
+
+        Signal::new(settings)
+    });
+
+    use_effect(move || {
+        let settings = settings.read();
+
+        if *LANGUAGE.peek() != settings.language {
+            *LANGUAGE.write() = settings.language;
+        }
+
+        if *CLOCK.peek() != settings.clock {
+            *CLOCK.write() = settings.clock;
+        }
+
+        theme::apply(settings.theme, &settings.custom_themes);
+
+        let font_size = document::eval(FONT_SIZE_JS);
+        let _ = font_size.send(settings.font_size.pixels());
+
+        storage::save("settings", &*settings);
+    });
+
+    settings
+}
+
+/// The settings of the user, for reading them in a component or changing them.
+pub fn use_settings() -> Signal<Settings> {
+    use_context()
+}
@@ -0,0 +1,359 @@
+//! The shortcuts module maps keyboard shortcuts to [`Action`]s. Every action has a default [`Binding`] that the user
+//! can change in the settings; the bindings in use are kept in [`Shortcuts`].
+//!
+//! Global actions are caught by a listener on the document, installed with [`use_shortcuts`], so they work wherever
+//! the focus is. Actions that only make sense in one place, like editing the last message from the composer, are
+//! matched by the component itself with [`Binding::matches`].
+
+use std::collections::HashMap;
+use std::fmt;
+
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::settings::use_settings;
+use crate::{ACTIVE_ROOM, ROOMS};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    /// Open the quick switcher to jump to a room by name.
+    QuickSwitcher,
+    PreviousRoom,
+    NextRoom,
+    CloseDialog,
+    ShowHelp,
+    /// Load the user's last message into an empty composer to edit it.
+    EditLastMessage,
+}
+
+impl Action {
+    pub const ALL: [Action; 6] = [
+        Action::QuickSwitcher,
+        Action::PreviousRoom,
+        Action::NextRoom,
+        Action::CloseDialog,
+        Action::ShowHelp,
+        Action::EditLastMessage,
+    ];
+
+    /// The id of the message describing the action.
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::QuickSwitcher => "shortcut-quick-switcher",
+            Action::PreviousRoom => "shortcut-previous-room",
+            Action::NextRoom => "shortcut-next-room",
+            Action::CloseDialog => "shortcut-close-dialog",
+            Action::ShowHelp => "shortcut-show-help",
+            Action::EditLastMessage => "shortcut-edit-last-message",
+        }
+    }
+
+    pub fn default_binding(self) -> Binding {
+        match self {
+            Action::QuickSwitcher => Binding::ctrl("k"),
+            Action::PreviousRoom => Binding { alt: true, ..Binding::key("ArrowUp") },
+            Action::NextRoom => Binding { alt: true, ..Binding::key("ArrowDown") },
+            Action::CloseDialog => Binding::key("Escape"),
+            Action::ShowHelp => Binding::ctrl("/"),
+            Action::EditLastMessage => Binding::key("ArrowUp"),
+        }
+    }
+
+    /// Whether the action is caught by the document listener, instead of by the component it belongs to.
+    fn is_global(self) -> bool {
+        !matches!(self, Action::EditLastMessage)
+    }
+}
+
+/// A key and the modifiers held with it. `ctrl` stands for Ctrl or, on macOS, Cmd.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding {
+    /// The `key` of the keyboard event, in lower case for characters: `k`, `/`, `ArrowUp`, `Escape`.
+    pub key: String,
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub alt: bool,
+    #[serde(default)]
+    pub shift: bool,
+}
+
+impl Binding {
+    fn key(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            ctrl: false,
+            alt: false,
+            shift: false,
+        }
+    }
+
+    fn ctrl(key: &str) -> Self {
+        Self { ctrl: true, ..Self::key(key) }
+    }
+
+    /// The binding of a key press, or `None` while only a modifier is pressed.
+    pub fn from_event(evt: &KeyboardData) -> Option<Self> {
+        let key = match evt.key() {
+            Key::Control | Key::Alt | Key::AltGraph | Key::Shift | Key::Meta | Key::CapsLock => return None,
+            Key::Character(text) => text.to_lowercase(),
+            key => key.to_string(),
+        };
+        let modifiers = evt.modifiers();
+
+        Some(Self {
+            key,
+            ctrl: modifiers.ctrl() || modifiers.meta(),
+            alt: modifiers.alt(),
+            shift: modifiers.shift(),
+        })
+    }
+
+    pub fn matches(&self, evt: &KeyboardData) -> bool {
+        Self::from_event(evt).as_ref() == Some(self)
+    }
+}
+
+impl fmt::Display for Binding {
+    /// Like the keys are labeled: `Ctrl+K`, `Alt+↑`, `Esc`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [(self.ctrl, "Ctrl+"), (self.alt, "Alt+"), (self.shift, "Shift+")] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+
+        match self.key.as_str() {
+            "ArrowUp" => f.write_str("↑"),
+            "ArrowDown" => f.write_str("↓"),
+            "ArrowLeft" => f.write_str("←"),
+            "ArrowRight" => f.write_str("→"),
+            "Escape" => f.write_str("Esc"),
+            " " => f.write_str("Space"),
+            key => f.write_str(&key.to_uppercase()),
+        }
+    }
+}
+
+/// The bindings the user changed. Actions that are not in the map use their default binding.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Shortcuts(HashMap<Action, Binding>);
+
+impl Shortcuts {
+    pub fn binding(&self, action: Action) -> Binding {
+        self.0.get(&action).cloned().unwrap_or_else(|| action.default_binding())
+    }
+
+    pub fn is_changed(&self, action: Action) -> bool {
+        self.0.contains_key(&action)
+    }
+
+    /// Bind `action` to `binding`. An action that had the binding before takes over the old binding of `action`, so
+    /// no two actions share a binding.
+    pub fn set(&mut self, action: Action, binding: Binding) {
+        let previous = self.binding(action);
+
+        if let Some(other) = Action::ALL.into_iter().find(|other| *other != action && self.binding(*other) == binding) {
+            self.put(other, previous);
+        }
+
+        self.put(action, binding);
+    }
+
+    pub fn reset(&mut self, action: Action) {
+        self.set(action, action.default_binding());
+    }
+
+    fn put(&mut self, action: Action, binding: Binding) {
+        if binding == action.default_binding() {
+            self.0.remove(&action);
+        } else {
+            self.0.insert(action, binding);
+        }
+    }
+}
+
+/// Calls back with the actions of the key presses anywhere in the document. Shortcuts are ignored while a binding is
+/// being recorded or an IME composes text, and in text fields only those with Ctrl or Alt or a key that does not type
+/// text are caught. Only the bindings of actions that would do something are sent, so the default of every other key
+/// press, such as Escape in a text field when no dialog is open, is kept.
+const LISTEN_JS: &str = r#"
+    if (window.dxShortcuts) {
+        document.removeEventListener("keydown", window.dxShortcuts);
+    }
+
+    window.dxShortcuts = (event) => {
+        if (event.defaultPrevented || event.isComposing || event.keyCode === 229 || event.target.closest?.("[data-recording-shortcut]")) {
+            return;
+        }
+
+        const key = event.key.length === 1 ? event.key.toLowerCase() : event.key;
+        const ctrl = event.ctrlKey || event.metaKey;
+        const typing = event.target.matches?.("input, textarea, select, [contenteditable]");
+
+        const found = (window.dxShortcutBindings ?? []).find(([, binding]) =>
+            binding.key === key
+                && binding.ctrl === ctrl
+                && binding.alt === event.altKey
+                && binding.shift === event.shiftKey
+                && !(typing && key.length === 1 && !ctrl && !event.altKey)
+        );
+
+        if (found) {
+            event.preventDefault();
+            dioxus.send(found[0]);
+        }
+    };
+
+    document.addEventListener("keydown", window.dxShortcuts);
+
+    // Stays pending so that the channel to the app stays open.
+    await new Promise(() => {});
+"#;
+
+const BINDINGS_JS: &str = r#"
+    window.dxShortcutBindings = await dioxus.recv();
+"#;
+
+/// Run `on_action` for the global shortcuts the user presses. The keys of the actions `available` turns down are left
+/// to the page; the bindings are sent again when a signal it reads changes. Called once, by the layout.
+pub fn use_shortcuts(available: impl Fn(Action) -> bool + 'static, on_action: impl FnMut(Action) + 'static) {
+    let settings = use_settings();
+    let on_action = use_callback(on_action);
+
+    use_effect(move || {
+        let shortcuts = &settings.read().shortcuts;
+        let bindings: Vec<(Action, Binding)> = Action::ALL.into_iter()
+            .filter(|action| action.is_global() && available(*action))
+            .map(|action| (action, shortcuts.binding(action)))
+            .collect();
+
+        let _ = document::eval(BINDINGS_JS).send(bindings);
+    });
+
+    use_future(move || {
+        let mut listener = document::eval(LISTEN_JS);
+
+        async move {
+            while let Ok(action) = listener.recv::<Action>().await {
+                on_action.call(action);
+            }
+        }
+    });
+}
+
+/// The room `step` places below the open one in the room list of `user`, wrapping around at the ends.
+pub fn adjacent_room(user: Uuid, step: isize) -> Option<Uuid> {
+    let rooms: Vec<Uuid> = ROOMS.peek().values()
+        .filter(|room| room.info.peek().members.contains(&user))
+        .map(|room| room.id)
+        .collect();
+
+    if rooms.is_empty() {
+        return None;
+    }
+
+    let current = ACTIVE_ROOM.peek().and_then(|id| rooms.iter().position(|room| *room == id));
+    let index = match current {
+        Some(index) => (index as isize + step).rem_euclid(rooms.len() as isize) as usize,
+        None if step > 0 => 0,
+        None => rooms.len() - 1,
+    };
+
+    rooms.get(index).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{room_id, testing, Room, RoomInfo};
+
+    #[test]
+    fn setting_a_taken_binding_swaps_it() {
+        let mut shortcuts = Shortcuts::default();
+
+        shortcuts.set(Action::QuickSwitcher, Binding::ctrl("p"));
+        assert_eq!(shortcuts.binding(Action::QuickSwitcher), Binding::ctrl("p"));
+        assert!(shortcuts.is_changed(Action::QuickSwitcher));
+        assert!(!shortcuts.is_changed(Action::ShowHelp));
+
+        // Help had Ctrl+/, which the quick switcher takes; help gets Ctrl+P, the old binding of the switcher.
+        shortcuts.set(Action::QuickSwitcher, Binding::ctrl("/"));
+        assert_eq!(shortcuts.binding(Action::QuickSwitcher), Binding::ctrl("/"));
+        assert_eq!(shortcuts.binding(Action::ShowHelp), Binding::ctrl("p"));
+
+        // No two actions share a binding.
+        for action in Action::ALL {
+            let sharing = Action::ALL.into_iter().filter(|other| shortcuts.binding(*other) == shortcuts.binding(action));
+            assert_eq!(sharing.count(), 1, "{action:?}");
+        }
+    }
+
+    #[test]
+    fn defaults_are_not_kept_as_changes() {
+        let mut shortcuts = Shortcuts::default();
+
+        shortcuts.set(Action::NextRoom, Binding::ctrl("j"));
+        shortcuts.set(Action::NextRoom, Action::NextRoom.default_binding());
+        assert!(!shortcuts.is_changed(Action::NextRoom));
+        assert_eq!(shortcuts, Shortcuts::default());
+
+        // Swapping two actions back and forth ends where it started.
+        shortcuts.set(Action::PreviousRoom, Action::NextRoom.default_binding());
+        assert!(shortcuts.is_changed(Action::PreviousRoom) && shortcuts.is_changed(Action::NextRoom));
+
+        shortcuts.reset(Action::PreviousRoom);
+        assert_eq!(shortcuts, Shortcuts::default());
+    }
+
+    #[test]
+    fn resetting_takes_the_default_back() {
+        let mut shortcuts = Shortcuts::default();
+
+        // The composer edits with Escape, the dialog closes with the old binding of editing.
+        shortcuts.set(Action::EditLastMessage, Binding::key("Escape"));
+        assert_eq!(shortcuts.binding(Action::CloseDialog), Binding::key("ArrowUp"));
+
+        shortcuts.reset(Action::CloseDialog);
+        assert_eq!(shortcuts.binding(Action::CloseDialog), Binding::key("Escape"));
+        assert_eq!(shortcuts.binding(Action::EditLastMessage), Binding::key("ArrowUp"));
+        assert_eq!(shortcuts, Shortcuts::default());
+
+        // Resetting what was never changed does nothing.
+        shortcuts.reset(Action::ShowHelp);
+        assert_eq!(shortcuts, Shortcuts::default());
+    }
+
+    #[test]
+    fn adjacent_rooms_wrap_around() {
+        testing::runtime().run(|| {
+            let user = Uuid::new_v4();
+            let names = ["Avengers", "Batman", "Hulk"];
+
+            for name in names.into_iter().chain(["Spiderman"]) {
+                // The user is not a member of Spiderman, which is skipped.
+                let members = if name == "Spiderman" { Default::default() } else { [user].into() };
+                let room = Room::new(room_id(name), RoomInfo::new(name.into(), None, members));
+                ROOMS.write().insert(room.id, room);
+            }
+
+            // In the order of the room list, which is the order of the ids.
+            let mut rooms = names.map(room_id);
+            rooms.sort();
+
+            // Without an open room, the first step goes to either end.
+            assert_eq!(adjacent_room(user, 1), Some(rooms[0]));
+            assert_eq!(adjacent_room(user, -1), Some(rooms[2]));
+
+            let cases = [(0, 1, 1), (2, 1, 0), (0, -1, 2), (1, -1, 0), (1, 3, 1)];
+
+            for (open, step, adjacent) in cases {
+                *ACTIVE_ROOM.write() = Some(rooms[open]);
+                assert_eq!(adjacent_room(user, step), Some(rooms[adjacent]), "{step} from {open}");
+            }
+
+            assert_eq!(adjacent_room(Uuid::new_v4(), 1), None);
+        });
+    }
+}
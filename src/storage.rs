@@ -0,0 +1,122 @@
+//! The storage module persists small pieces of application state between runs. On the web the values are kept in
+//! `localStorage`, on desktop and mobile they are written as JSON files into the platform's local data directory.
+//!
+//! Values that hold keys or message content are stored with [`save_secret`], encrypted with a key of the
+//! installation, see [`crypto::seal_local`]. That key is not stored with them: it is kept in the keychain of the
+//! system on desktop and wrapped with a key the browser does not give out on the web.
+
+use base64::Engine;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::crypto;
+
+/// Load the value stored under `key`, or `None` if nothing was stored or it could not be decoded.
+pub fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let raw = backend::read(key)?;
+
+    serde_json::from_str(&raw).ok()
+}
+
+/// Store `value` under `key`, replacing any previous value. Failures are ignored since persistence is best effort.
+pub fn save<T: Serialize>(key: &str, value: &T) {
+    if let Ok(raw) = serde_json::to_string(value) {
+        backend::write(key, &raw);
+    }
+}
+
+/// Remove the value stored under `key`, if any. Only the keystores remove values, see [`crypto`].
+#[cfg(any(target_arch = "wasm32", all(feature = "desktop", not(test))))]
+pub fn remove(key: &str) {
+    backend::remove(key);
+}
+
+/// Load a value stored with [`save_secret`] under `key`, or `None` if nothing was stored or it could not be
+/// decrypted.
+pub fn load_secret<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let sealed: String = load(key)?;
+    let sealed = base64::engine::general_purpose::STANDARD.decode(sealed).ok()?;
+
+    serde_json::from_slice(&crypto::open_local(key, &sealed)?).ok()
+}
+
+/// Encrypt `value` and store it under `key`, replacing any previous value.
+pub fn save_secret<T: Serialize>(key: &str, value: &T) {
+    let Ok(raw) = serde_json::to_vec(value) else {
+        return;
+    };
+
+    if let Some(sealed) = crypto::seal_local(key, &raw) {
+        save(key, &base64::engine::general_purpose::STANDARD.encode(sealed));
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    const PREFIX: &str = "dx-chat.";
+
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    pub fn read(key: &str) -> Option<String> {
+        local_storage()?.get_item(&format!("{PREFIX}{key}")).ok()?
+    }
+
+    pub fn write(key: &str, value: &str) {
+        if let Some(storage) = local_storage() {
+            let _ = storage.set_item(&format!("{PREFIX}{key}"), value);
+        }
+    }
+
+    pub fn remove(key: &str) {
+        if let Some(storage) = local_storage() {
+            let _ = storage.remove_item(&format!("{PREFIX}{key}"));
+        }
+    }
+}
+
+/// The directory application data is kept in on desktop and mobile.
+#[cfg(all(not(target_arch = "wasm32"), not(test)))]
+pub fn data_dir() -> std::path::PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("dx-chat")
+}
+
+/// Every test runs on a thread of its own, and every thread gets a directory of its own.
+#[cfg(test)]
+pub fn data_dir() -> std::path::PathBuf {
+    thread_local! {
+        static DIR: std::path::PathBuf = std::env::temp_dir().join(format!("dx-chat-test-{}", uuid::Uuid::new_v4()));
+    }
+
+    DIR.with(Clone::clone)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use std::path::PathBuf;
+
+    fn path(key: &str) -> PathBuf {
+        super::data_dir().join(format!("{key}.json"))
+    }
+
+    pub fn read(key: &str) -> Option<String> {
+        std::fs::read_to_string(path(key)).ok()
+    }
+
+    pub fn write(key: &str, value: &str) {
+        let path = path(key);
+
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+
+        let _ = std::fs::write(path, value);
+    }
+
+    #[cfg(all(feature = "desktop", not(test)))]
+    pub fn remove(key: &str) {
+        let _ = std::fs::remove_file(path(key));
+    }
+}
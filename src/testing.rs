@@ -0,0 +1,79 @@
+//! The testing module helps tests that need a Dioxus runtime. Global signals only exist inside one, so a test that
+//! touches them runs in a [`Headless`] app of its own: an empty one from [`runtime`], or the whole app from [`app`].
+//! Each test also stores its data in a directory of its own, see [`storage::data_dir`](crate::storage::data_dir), so
+//! tests never see each other's state.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use dioxus::dioxus_core::NoOpMutations;
+use dioxus::history::{History, MemoryHistory};
+use dioxus::prelude::*;
+
+/// An app without a renderer. Timers work as they would in the app, on a Tokio runtime of its own. Its clock is
+/// paused and jumps ahead whenever everything waits, so a test never waits for real.
+pub struct Headless {
+    dom: VirtualDom,
+    tokio: tokio::runtime::Runtime,
+}
+
+impl Headless {
+    fn new(dom: VirtualDom, history: Option<Rc<dyn History>>) -> Self {
+        let tokio = tokio::runtime::Builder::new_current_thread().enable_all().start_paused(true).build().unwrap();
+        let mut app = Self { dom, tokio };
+
+        if let Some(history) = history {
+            app.dom.provide_root_context(history);
+        }
+
+        let _tokio = app.tokio.enter();
+        app.dom.rebuild_in_place();
+        drop(_tokio);
+
+        app
+    }
+
+    /// Run `f` with the global signals of the app.
+    pub fn run<O>(&self, f: impl FnOnce() -> O) -> O {
+        let _tokio = self.tokio.enter();
+        self.dom.in_runtime(f)
+    }
+
+    /// Run `future` to completion with the global signals of the app.
+    pub fn block_on<O>(&self, future: impl std::future::Future<Output = O>) -> O {
+        self.dom.in_runtime(|| self.tokio.block_on(future))
+    }
+
+    /// Let `duration` pass on the paused clock, running the tasks of the app, like its timers, as they wake up.
+    pub fn advance(&mut self, duration: Duration) {
+        self.tokio.block_on(async {
+            let deadline = tokio::time::Instant::now() + duration;
+
+            while tokio::time::timeout_at(deadline, self.dom.wait_for_work()).await.is_ok() {
+                self.dom.render_immediate(&mut NoOpMutations);
+            }
+        });
+    }
+
+    /// Re-render what the changes made since the last render invalidated.
+    pub fn render(&mut self) {
+        let _tokio = self.tokio.enter();
+        self.dom.process_events();
+        self.dom.render_immediate(&mut NoOpMutations);
+    }
+
+    /// The HTML the app renders, as it is after the last render.
+    pub fn html(&self) -> String {
+        dioxus_ssr::render(&self.dom)
+    }
+}
+
+/// An empty app, to run code that uses global signals in.
+pub fn runtime() -> Headless {
+    Headless::new(VirtualDom::new(|| rsx! {}), None)
+}
+
+/// The whole app with `route` open.
+pub fn app(route: crate::Route) -> Headless {
+    Headless::new(VirtualDom::new(crate::App), Some(Rc::new(MemoryHistory::with_initial_path(route))))
+}
@@ -0,0 +1,201 @@
+//! The theme module decides the colors of the app. The built-in themes are daisyUI's light and dark theme, or
+//! whichever of them the system prefers. Custom themes start from one of them and replace the accent color and the
+//! bubble colors of the user's own and other people's messages.
+//!
+//! The theme is applied to the document root with [`apply`], so switching themes takes effect without a reload.
+
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    /// Light or dark, following the system.
+    #[default]
+    System,
+    Light,
+    Dark,
+    /// One of the user's [`CustomTheme`]s.
+    Custom(Uuid),
+}
+
+impl Theme {
+    /// The themes that are always available.
+    pub const BUILT_IN: [Theme; 3] = [Theme::System, Theme::Light, Theme::Dark];
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CustomTheme {
+    pub id: Uuid,
+    pub name: String,
+    /// Whether the theme starts from the dark theme instead of the light one.
+    pub dark: bool,
+    /// Colors as `#rrggbb`.
+    pub accent: String,
+    pub own_bubble: String,
+    pub other_bubble: String,
+}
+
+impl CustomTheme {
+    pub fn new(name: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name,
+            dark: false,
+            accent: "#0d9488".to_string(),
+            own_bubble: "#0284c7".to_string(),
+            other_bubble: "#e2e8f0".to_string(),
+        }
+    }
+
+    /// The CSS variables the theme sets. The bubble variables are read by `main.css`. Colors that are not `#rrggbb`
+    /// are left out, so that the base theme's color shows instead.
+    fn variables(&self) -> Vec<(&'static str, String)> {
+        [
+            ("--color-accent", "--color-accent-content", &self.accent),
+            ("--bubble-own", "--bubble-own-content", &self.own_bubble),
+            ("--bubble-other", "--bubble-other-content", &self.other_bubble),
+        ]
+        .into_iter()
+        .filter_map(|(name, content, color)| {
+            let content_color = content_color(color)?;
+            Some([(name, color.clone()), (content, content_color.to_string())])
+        })
+        .flatten()
+        .collect()
+    }
+}
+
+const VARIABLES: [&str; 6] = [
+    "--color-accent",
+    "--color-accent-content",
+    "--bubble-own",
+    "--bubble-own-content",
+    "--bubble-other",
+    "--bubble-other-content",
+];
+
+/// Sets the theme on the document root. Following the system is resolved here, and again whenever the system
+/// preference changes, so that `data-theme` always names the theme in use and the `dark:` variants match it.
+const APPLY_JS: &str = r#"
+    const [theme, names, variables] = await dioxus.recv();
+    const root = document.documentElement;
+    const system = window.matchMedia("(prefers-color-scheme: dark)");
+
+    window.dxFollowSystem ??= () => {
+        if (root.dataset.followSystem) {
+            root.dataset.theme = system.matches ? "dark" : "light";
+        }
+    };
+
+    system.removeEventListener("change", window.dxFollowSystem);
+    system.addEventListener("change", window.dxFollowSystem);
+
+    if (theme) {
+        delete root.dataset.followSystem;
+        root.dataset.theme = theme;
+    } else {
+        root.dataset.followSystem = "true";
+        window.dxFollowSystem();
+    }
+
+    for (const name of names) {
+        root.style.removeProperty(name);
+    }
+
+    for (const [name, value] of variables) {
+        root.style.setProperty(name, value);
+    }
+"#;
+
+/// Apply `theme` to the document. A custom theme that no longer exists falls back to following the system.
+pub fn apply(theme: Theme, custom: &[CustomTheme]) {
+    let (base, variables) = match theme {
+        Theme::System => (None, Vec::new()),
+        Theme::Light => (Some("light"), Vec::new()),
+        Theme::Dark => (Some("dark"), Vec::new()),
+        Theme::Custom(id) => match custom.iter().find(|theme| theme.id == id) {
+            Some(theme) => (Some(if theme.dark { "dark" } else { "light" }), theme.variables()),
+            None => (None, Vec::new()),
+        },
+    };
+
+    let apply = document::eval(APPLY_JS);
+    let _ = apply.send((base, VARIABLES, variables));
+}
+
+/// The red, green and blue channels of a `#rrggbb` color.
+fn rgb(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6 && hex.bytes().all(|b| b.is_ascii_hexdigit()))?;
+    let channel = |start: usize| u8::from_str_radix(&hex[start..start + 2], 16).ok();
+
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Black or white, whichever is easier to read on `background`. `None` if `background` is not a `#rrggbb` color.
+fn content_color(background: &str) -> Option<&'static str> {
+    let [red, green, blue] = rgb(background)?.map(|channel| channel as f64 / 255.0);
+    let luminance = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+
+    Some(if luminance > 0.5 { "#000000" } else { "#ffffff" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_is_readable_on_its_background() {
+        let cases = [
+            ("#ffffff", "#000000"),
+            ("#000000", "#ffffff"),
+            ("#FFFF00", "#000000"),
+            ("#0000ff", "#ffffff"),
+            // Either side of half the luminance.
+            ("#808080", "#000000"),
+            ("#7f7f7f", "#ffffff"),
+        ];
+
+        for (background, content) in cases {
+            assert_eq!(content_color(background), Some(content), "{background}");
+        }
+    }
+
+    #[test]
+    fn custom_themes_set_their_variables() {
+        let theme = CustomTheme::new("Teal".to_string());
+
+        assert_eq!(
+            theme.variables(),
+            [
+                ("--color-accent", "#0d9488".to_string()),
+                ("--color-accent-content", "#ffffff".to_string()),
+                ("--bubble-own", "#0284c7".to_string()),
+                ("--bubble-own-content", "#ffffff".to_string()),
+                ("--bubble-other", "#e2e8f0".to_string()),
+                ("--bubble-other-content", "#000000".to_string()),
+            ]
+        );
+
+        // Every variable the theme sets is removed again when switching themes.
+        assert!(theme.variables().iter().all(|(name, _)| VARIABLES.contains(name)));
+    }
+
+    #[test]
+    fn invalid_colors_are_left_out() {
+        for color in ["", "#", "teal", "0d9488", "#0d948", "#0d94888", "#0d948g", "#+d9488", "#ÿÿÿ"] {
+            assert_eq!(content_color(color), None, "{color}");
+        }
+
+        let theme = CustomTheme {
+            accent: "teal".to_string(),
+            own_bubble: "#12345".to_string(),
+            ..CustomTheme::new("Broken".to_string())
+        };
+
+        assert_eq!(
+            theme.variables(),
+            [("--bubble-other", "#e2e8f0".to_string()), ("--bubble-other-content", "#000000".to_string())]
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! The time format module turns message times into text for the user's locale: relative times like "5 min ago" or
+//! "yesterday at 14:02" for recent messages and absolute dates for old ones.
+//!
+//! Relative times depend on the current time, which is kept in [`NOW`] and advanced by [`use_clock`], so that they
+//! refresh while the app is open. Whether times are shown on a 12 or 24 hour clock follows [`CLOCK`], which defaults
+//! to what is usual for the locale.
+//!
+//! Dates use the regional format of the system when it is for the language of the user interface, and the usual
+//! format of the language otherwise.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Locale, NaiveDate, NaiveTime, Utc};
+use dioxus::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::{t, Language, LANGUAGE};
+
+/// How often [`NOW`] is advanced.
+const TICK: Duration = Duration::from_secs(20);
+
+/// Messages older than this many days are shown with their date instead of the day of the week.
+const WEEKDAY_DAYS: i64 = 6;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Clock {
+    /// The clock usual for the locale.
+    #[default]
+    Auto,
+    H12,
+    H24,
+}
+
+impl Clock {
+    pub const ALL: [Clock; 3] = [Clock::Auto, Clock::H12, Clock::H24];
+}
+
+/// The current time, advanced every [`TICK`] while [`use_clock`] is mounted.
+pub static NOW: GlobalSignal<DateTime<Utc>> = Global::new(Utc::now);
+static SYSTEM_LOCALE: GlobalSignal<Option<Locale>> = Global::new(system_locale);
+/// The clock preference, mirrored from the [`Settings`](crate::settings::Settings).
+pub static CLOCK: GlobalSignal<Clock> = Global::new(Clock::default);
+
+/// Keep [`NOW`] up to date. Called once, by the root component.
+pub fn use_clock() {
+    use_future(|| async {
+        loop {
+            sleep(TICK).await;
+            *NOW.write() = Utc::now();
+        }
+    });
+}
+
+/// When `time` was, as seen at `now`: "just now", "5 min ago", "today at 14:02", "yesterday at 14:02",
+/// "Monday at 14:02" and for older times the date.
+pub fn relative(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let locale = locale();
+    let elapsed = now - time;
+    let local = time.with_timezone(&Local);
+    let days = (now.with_timezone(&Local).date_naive() - local.date_naive()).num_days();
+
+    if elapsed.num_minutes() < 1 {
+        t!("time-just-now")
+    } else if elapsed.num_minutes() < 60 {
+        t!("time-minutes-ago", minutes = elapsed.num_minutes())
+    } else if days == 0 {
+        t!("time-today-at", time = time_of_day(time))
+    } else if days == 1 {
+        t!("time-yesterday-at", time = time_of_day(time))
+    } else if days <= WEEKDAY_DAYS {
+        t!("time-weekday-at", weekday = local.format_localized("%A", locale).to_string(), time = time_of_day(time))
+    } else {
+        local.format_localized("%x", locale).to_string()
+    }
+}
+
+/// The time of day of `time` if it was on the same day as `now`, otherwise its date and time.
+pub fn absolute(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let locale = locale();
+    let local = time.with_timezone(&Local);
+
+    if local.date_naive() == now.with_timezone(&Local).date_naive() {
+        time_of_day(time)
+    } else {
+        format!("{} {}", local.format_localized("%x", locale), time_of_day(time))
+    }
+}
+
+/// The full date and time, for showing the exact time of a message on hover.
+pub fn exact(time: DateTime<Utc>) -> String {
+    let locale = locale();
+    let local = time.with_timezone(&Local);
+
+    format!("{} {}", full_date(local.date_naive(), locale), time_of_day(time))
+}
+
+/// The label of the day separator above a message sent at `time`, unless the message before it, sent at `previous`,
+/// was sent on the same day.
+pub fn separator(previous: Option<DateTime<Utc>>, time: DateTime<Utc>, today: NaiveDate) -> Option<String> {
+    let sent = local_day(time);
+
+    previous.is_none_or(|previous| local_day(previous) != sent).then(|| day(sent, today))
+}
+
+/// The day `time` was on, in the time zone of the user.
+pub fn local_day(time: DateTime<Utc>) -> NaiveDate {
+    time.with_timezone(&Local).date_naive()
+}
+
+/// The label of a day separator: "Today", "Yesterday" or the date.
+pub fn day(day: NaiveDate, today: NaiveDate) -> String {
+    let locale = locale();
+
+    if day == today {
+        t!("day-today")
+    } else if today.pred_opt() == Some(day) {
+        t!("day-yesterday")
+    } else {
+        full_date(day, locale)
+    }
+}
+
+/// The day of the week and the date, in the order and format of the locale.
+fn full_date(day: NaiveDate, locale: Locale) -> String {
+    let weekday = day.format_localized("%A", locale).to_string();
+    let date = day.format_localized("%x", locale).to_string();
+
+    // Some locales already include the day of the week in their date format.
+    if date.contains(&weekday) {
+        date
+    } else {
+        format!("{weekday} {date}")
+    }
+}
+
+/// The hours and minutes of `time` on the user's clock.
+pub fn time_of_day(time: DateTime<Utc>) -> String {
+    let locale = locale();
+    let local = time.with_timezone(&Local);
+
+    if uses_12_hours(*CLOCK.read(), locale) {
+        // Locales with a 24 hour clock have no names for AM and PM.
+        let period = Some(local.format_localized("%p", locale).to_string())
+            .filter(|period| !period.is_empty())
+            .unwrap_or_else(|| local.format("%p").to_string());
+
+        format!("{} {period}", local.format_localized("%-I:%M", locale))
+    } else {
+        local.format_localized("%H:%M", locale).to_string()
+    }
+}
+
+fn uses_12_hours(clock: Clock, locale: Locale) -> bool {
+    match clock {
+        Clock::H12 => true,
+        Clock::H24 => false,
+        // Locales with a 12 hour clock don't show 13 in their time format.
+        Clock::Auto => {
+            let afternoon = NaiveTime::from_hms_opt(13, 0, 0).unwrap_or_default();
+
+            !NaiveDate::default().and_time(afternoon)
+                .and_utc()
+                .format_localized("%X", locale)
+                .to_string()
+                .contains("13")
+        }
+    }
+}
+
+/// The locale dates and times are formatted for.
+fn locale() -> Locale {
+    let language = *LANGUAGE.read();
+
+    SYSTEM_LOCALE.read()
+        .filter(|locale| Language::from_tag(&format!("{locale:?}")) == Some(language))
+        .unwrap_or(match language {
+            Language::English => Locale::en_US,
+            Language::Finnish => Locale::fi_FI,
+            Language::Arabic => Locale::ar_SA,
+        })
+}
+
+/// The locale of the browser or, elsewhere, of the `LC_ALL`, `LC_TIME` or `LANG` environment variables.
+fn system_locale() -> Option<Locale> {
+    #[cfg(target_arch = "wasm32")]
+    let tag = web_sys::window().and_then(|window| window.navigator().language());
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let tag = ["LC_ALL", "LC_TIME", "LANG"].into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+
+    tag.and_then(|tag| parse_locale(&tag))
+}
+
+/// Parse a BCP 47 tag like `en-US` or a POSIX locale like `fi_FI.UTF-8`. A bare language like `fi` is read as the
+/// country of the same name, `fi_FI`.
+fn parse_locale(tag: &str) -> Option<Locale> {
+    let tag = tag.split(['.', '@']).next()?.replace('-', "_");
+
+    Locale::try_from(tag.as_str())
+        .or_else(|_| Locale::try_from(format!("{tag}_{}", tag.to_uppercase()).as_str()))
+        .ok()
+}
+
+/// Wait for `duration` without blocking, on every platform.
+#[cfg(target_arch = "wasm32")]
+pub async fn sleep(duration: Duration) {
+    gloo_timers::future::sleep(duration).await;
+}
+
+/// Wait for `duration` without blocking, on every platform.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone};
+
+    use super::*;
+    use crate::testing;
+
+    /// Wednesday afternoon, in the time zone the tests run in.
+    fn now() -> DateTime<Utc> {
+        at(2024, 6, 12, 15, 0)
+    }
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Local.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap().with_timezone(&Utc)
+    }
+
+    /// Run `f` in `language` on `clock`, whatever the locale of the system the tests run on.
+    fn formatted<O>(language: Language, clock: Clock, f: impl FnOnce() -> O) -> O {
+        testing::runtime().run(|| {
+            *LANGUAGE.write() = language;
+            *CLOCK.write() = clock;
+            *SYSTEM_LOCALE.write() = None;
+
+            f()
+        })
+    }
+
+    #[test]
+    fn relative_times_cross_their_thresholds() {
+        let minute = TimeDelta::minutes(1);
+        let cases = [
+            (now(), "just now"),
+            (now() - TimeDelta::seconds(59), "just now"),
+            (now() - minute, "1 min ago"),
+            (now() - minute * 59, "59 min ago"),
+            (now() - minute * 60, "today at 14:00"),
+            (at(2024, 6, 12, 0, 5), "today at 00:05"),
+            (at(2024, 6, 11, 23, 59), "yesterday at 23:59"),
+            (at(2024, 6, 11, 0, 0), "yesterday at 00:00"),
+            (at(2024, 6, 10, 9, 0), "Monday at 09:00"),
+            (at(2024, 6, 6, 9, 0), "Thursday at 09:00"),
+            (at(2024, 6, 5, 23, 59), "06/05/2024"),
+            (at(2023, 12, 24, 9, 0), "12/24/2023"),
+        ];
+
+        formatted(Language::English, Clock::H24, || {
+            for (time, expected) in cases {
+                assert_eq!(relative(time, now()), expected, "{time}");
+            }
+
+            // Minutes count before days: twenty minutes ago is not yesterday.
+            assert_eq!(relative(at(2024, 6, 11, 23, 50), at(2024, 6, 12, 0, 10)), "20 min ago");
+        });
+    }
+
+    #[test]
+    fn times_follow_the_clock() {
+        let afternoon = at(2024, 6, 12, 14, 5);
+        let morning = at(2024, 6, 12, 0, 5);
+        let cases = [
+            (Language::English, Clock::Auto, "2:05 PM", "12:05 AM"),
+            (Language::English, Clock::H12, "2:05 PM", "12:05 AM"),
+            (Language::English, Clock::H24, "14:05", "00:05"),
+            (Language::Finnish, Clock::Auto, "14:05", "00:05"),
+            (Language::Finnish, Clock::H24, "14:05", "00:05"),
+            (Language::Arabic, Clock::Auto, "14:05", "00:05"),
+        ];
+
+        for (language, clock, afternoon_text, morning_text) in cases {
+            formatted(language, clock, || {
+                assert_eq!(time_of_day(afternoon), afternoon_text, "{language:?} {clock:?}");
+                assert_eq!(time_of_day(morning), morning_text, "{language:?} {clock:?}");
+            });
+        }
+
+        // A 12 hour clock in a locale without names for AM and PM.
+        formatted(Language::Finnish, Clock::H12, || assert_eq!(time_of_day(afternoon), "2:05 PM"));
+    }
+
+    #[test]
+    fn dates_follow_the_locale() {
+        let monday = at(2024, 6, 10, 9, 0);
+        let old = at(2024, 6, 5, 9, 0);
+        let cases = [
+            (Language::English, "Monday at 9:00 AM", "06/05/2024", "Wednesday 06/05/2024"),
+            (Language::Finnish, "maanantai klo 09:00", "05.06.2024", "keskiviikko 05.06.2024"),
+            // The Arabic date has the day of the week already.
+            (Language::Arabic, "الإثنين الساعة 09:00", "الأربعاء  5 يونيو 2024", "الأربعاء  5 يونيو 2024"),
+        ];
+
+        for (language, weekday, date, separator) in cases {
+            formatted(language, Clock::Auto, || {
+                assert_eq!(relative(monday, now()), weekday);
+                assert_eq!(relative(old, now()), date);
+                assert_eq!(day(local_day(old), local_day(now())), separator);
+            });
+        }
+    }
+
+    #[test]
+    fn days_are_separated() {
+        let today = local_day(now());
+
+        formatted(Language::English, Clock::Auto, || {
+            let cases = [
+                (None, now(), Some("Today")),
+                (Some(at(2024, 6, 12, 0, 0)), now(), None),
+                (Some(at(2024, 6, 11, 23, 59)), at(2024, 6, 12, 0, 0), Some("Today")),
+                (None, at(2024, 6, 11, 12, 0), Some("Yesterday")),
+                (Some(at(2024, 6, 11, 8, 0)), at(2024, 6, 11, 12, 0), None),
+                (Some(at(2024, 6, 1, 8, 0)), at(2024, 6, 10, 12, 0), Some("Monday 06/10/2024")),
+            ];
+
+            for (previous, time, label) in cases {
+                assert_eq!(separator(previous, time, today).as_deref(), label, "{previous:?} {time}");
+            }
+        });
+
+        formatted(Language::Finnish, Clock::Auto, || {
+            assert_eq!(separator(None, at(2024, 6, 11, 12, 0), today).as_deref(), Some("Eilen"));
+        });
+    }
+}
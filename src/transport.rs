@@ -0,0 +1,479 @@
+//! The transport module sends the user's messages to the other members of a room and tracks how far they got. [`send`]
+//! posts a message to its room as [`Delivery::Pending`] and queues it, encrypted, in the [`outbox`], which hands it to the
+//! [`Transport`] in [`TRANSPORT`] while it is [`CONNECTED`]. It is [`Delivery::Sent`] once the server has accepted it
+//! and [`Delivery::Delivered`] once the server reports that it reached the other members. A message that could not be
+//! sent is [`Delivery::Failed`] until it is sent again with [`retry`].
+//!
+//! What other devices send arrives through [`Transport::listen`]: their messages and other changes are taken in with
+//! [`receive`], and whether their users are typing with [`receive_typing`], see [`typing`](crate::typing).
+//!
+//! Read receipts travel the other way: [`mark_read`] records the newest message the user has seen and, if they share
+//! read receipts, tells the other members. What the others have seen is recorded with [`read_up_to`]. Both end up in
+//! the room's [`receipts`](crate::Room::receipts).
+
+use std::{cell::RefCell, collections::HashSet, future::Future, pin::Pin, rc::Rc, time::Duration};
+
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::attachments;
+use crate::crdt::{Change, MessageOp, RoomOp, Stamp};
+use crate::crypto::{self, Envelope, KeyShare};
+use crate::i18n::t;
+use crate::outbox;
+use crate::presence::{self, Status};
+use crate::time_format;
+use crate::typing;
+use crate::{Message, Room, ROOMS};
+
+/// How far a message of the user got.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Delivery {
+    /// Waiting for the server to accept it.
+    Pending,
+    /// Accepted by the server.
+    Sent,
+    /// Received by the other members. Messages from others and from the history always are.
+    #[default]
+    Delivered,
+    /// Could not be sent, for the reason given.
+    Failed(String),
+}
+
+pub type SendFuture = Pin<Box<dyn Future<Output = Result<(), String>>>>;
+
+/// How messages and read receipts reach the other members of a room.
+pub trait Transport {
+    /// Send an encrypted message or other change to the members of its room, along with the key shares they need to
+    /// decrypt it. Resolves once the server has accepted it.
+    ///
+    /// The server knows changes by their `client_id`: a change it already has is accepted again without being
+    /// passed on twice, so that sending again after the acceptance got lost is safe.
+    fn send(&self, envelope: &Envelope, shares: &[KeyShare]) -> SendFuture;
+
+    /// Tell the members of `room` that the user has read it up to the message `id`.
+    fn read(&self, room: Uuid, id: Stamp);
+
+    /// Tell the members of `room` that the user started or stopped typing in it. Typing is not encrypted, it says
+    /// no more than the server sees anyway: that someone is about to send something.
+    fn typing(&self, room: Uuid, typing: bool);
+
+    /// Hand what other devices send to the rooms of the user over as it arrives: messages and other changes, with
+    /// the key shares that came along, to `on_change`, and who started or stopped typing in which room to
+    /// `on_typing`.
+    fn listen(&self, on_change: Rc<dyn Fn(Envelope, Vec<KeyShare>)>, on_typing: Rc<dyn Fn(Uuid, Uuid, bool)>);
+}
+
+/// How long the [`LoopbackTransport`] takes to accept, deliver and read a message.
+const ACCEPT_AFTER: Duration = Duration::from_millis(300);
+const DELIVER_AFTER: Duration = Duration::from_secs(1);
+const READ_AFTER: Duration = Duration::from_secs(3);
+
+/// Stands in for a server: accepts every message and change after a moment while connected, then reports messages
+/// delivered, and read by the members that are online.
+#[derive(Default)]
+pub struct LoopbackTransport {
+    /// The client ids of the messages accepted so far.
+    accepted: Rc<RefCell<HashSet<Uuid>>>,
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&self, envelope: &Envelope, _shares: &[KeyShare]) -> SendFuture {
+        let room = envelope.room;
+        let id = envelope.id;
+        let author = envelope.author;
+        let client_id = envelope.client_id;
+        let accepted = self.accepted.clone();
+
+        Box::pin(async move {
+            if !*CONNECTED.peek() {
+                return Err(t!("delivery-disconnected"));
+            }
+
+            if accepted.borrow().contains(&client_id) {
+                return Ok(());
+            }
+
+            time_format::sleep(ACCEPT_AFTER).await;
+            accepted.borrow_mut().insert(client_id);
+
+            // The message got through, but the answer did not.
+            if !*CONNECTED.peek() {
+                return Err(t!("delivery-disconnected"));
+            }
+
+            let message = ROOMS.peek()
+                .get(&room)
+                .is_some_and(|room| room.messages.peek().get(&id).is_some());
+
+            // Other changes are not delivered or read, there is nothing to show it on.
+            if !message {
+                return Ok(());
+            }
+
+            spawn_forever(async move {
+                time_format::sleep(DELIVER_AFTER).await;
+                delivered(room, id);
+
+                time_format::sleep(READ_AFTER).await;
+
+                let members = ROOMS.peek()
+                    .get(&room)
+                    .map(|room| room.info.peek().members.clone())
+                    .unwrap_or_default();
+
+                for member in members {
+                    if member != author && presence::of(member).status == Status::Online {
+                        read_up_to(room, member, id);
+                    }
+                }
+            });
+
+            Ok(())
+        })
+    }
+
+    fn read(&self, _room: Uuid, _id: Stamp) {}
+
+    fn typing(&self, _room: Uuid, _typing: bool) {}
+
+    // Nobody else sends through the loopback.
+    fn listen(&self, _on_change: Rc<dyn Fn(Envelope, Vec<KeyShare>)>, _on_typing: Rc<dyn Fn(Uuid, Uuid, bool)>) {}
+}
+
+pub static TRANSPORT: GlobalSignal<Rc<dyn Transport>> = Global::new(|| Rc::new(LoopbackTransport::default()));
+
+/// Whether the transport can reach the server.
+pub static CONNECTED: GlobalSignal<bool> = Global::new(|| true);
+
+/// Reports whether the device is online, now and whenever it changes.
+const ONLINE_JS: &str = r#"
+    const report = () => dioxus.send(navigator.onLine);
+
+    window.addEventListener("online", report);
+    window.addEventListener("offline", report);
+    report();
+
+    // Stays pending so that the channel to the app stays open.
+    await new Promise(() => {});
+"#;
+
+/// Keep [`CONNECTED`] up to date and take in what other devices send. The loopback transport has no connection of its
+/// own, it is connected while the device is online. Called once, by the root component.
+pub fn use_connection() {
+    use_hook(|| {
+        let on_change = Rc::new(|envelope: Envelope, shares: Vec<KeyShare>| {
+            if let Err(error) = receive(&envelope, &shares) {
+                dioxus::logger::tracing::warn!("A change to room {} could not be opened: {error}", envelope.room);
+            }
+        });
+        let on_typing = Rc::new(|room, user, typing| {
+            if let Err(error) = receive_typing(room, user, typing) {
+                dioxus::logger::tracing::warn!("Typing in room {room} is refused: {error}");
+            }
+        });
+
+        TRANSPORT.peek().listen(on_change, on_typing);
+    });
+
+    use_future(|| async {
+        let mut online = document::eval(ONLINE_JS);
+
+        while let Ok(connected) = online.recv::<bool>().await {
+            if *CONNECTED.peek() != connected {
+                *CONNECTED.write() = connected;
+            }
+        }
+    });
+}
+
+/// Post `message` to `room` as pending and queue it to be sent. It fails right away if it cannot be encrypted.
+pub fn send(room: Room, message: Message) {
+    let delivery = match outbox::push(&Change::post(room.id, &message)) {
+        Ok(()) => Delivery::Pending,
+        Err(error) => Delivery::Failed(error),
+    };
+
+    room.post(Message { delivery, ..message });
+}
+
+/// Queue a message that failed to be sent again.
+pub fn retry(room: Room, id: Stamp) {
+    let Some(message) = room.messages.peek().get(&id).cloned() else {
+        return;
+    };
+
+    let delivery = match outbox::push(&Change::post(room.id, &message)) {
+        Ok(()) => Delivery::Pending,
+        Err(error) => Delivery::Failed(error),
+    };
+
+    set_delivery(room, id, delivery);
+}
+
+/// Take in a message or other change that another device sent, with the key shares that came along. Messages are
+/// posted to their room, other changes are merged into it. Changes the room does not allow their author to make, see
+/// [`RoomInfo::allows`](crate::RoomInfo::allows), are refused.
+pub fn receive(envelope: &Envelope, shares: &[KeyShare]) -> Result<(), String> {
+    let device = crypto::DEVICE.peek().device;
+
+    for share in shares.iter().filter(|share| share.to == device) {
+        // The envelope may still open with a ratchet shared before.
+        if let Err(error) = crypto::receive(share) {
+            dioxus::logger::tracing::warn!("A key share from device {} is refused: {error}", share.from);
+        }
+    }
+
+    let change = crypto::open(envelope)?;
+    let room = ROOMS.peek().get(&change.room).copied().ok_or_else(|| t!("room-gone"))?;
+
+    if !room.info.peek().allows(change.author, &change.op) {
+        return Err(t!("change-not-allowed"));
+    }
+
+    match change.op {
+        RoomOp::Message(MessageOp::Post(mut message)) => {
+            for attachment in &mut message.attachments {
+                if let Err(error) = attachments::unpack(attachment) {
+                    dioxus::logger::tracing::warn!("An attachment to room {} is lost: {error}", change.room);
+                }
+            }
+
+            room.post(*message);
+        }
+        op => {
+            room.merge(change.stamp, op);
+        }
+    }
+
+    Ok(())
+}
+
+/// Take in that `user` started or stopped typing in `room`, if they are a member.
+pub fn receive_typing(room: Uuid, user: Uuid, typing: bool) -> Result<(), String> {
+    let room = ROOMS.peek().get(&room).copied().ok_or_else(|| t!("room-gone"))?;
+
+    if !room.info.peek().members.contains(&user) {
+        return Err(t!("change-not-allowed"));
+    }
+
+    typing::received(room.id, user, typing);
+
+    Ok(())
+}
+
+/// Make a change to `room` as `author`, other than posting a message, and queue it to be sent to the other members.
+/// A change that cannot be encrypted is only made here.
+pub fn change(room: Room, author: Uuid, op: RoomOp) {
+    let change = Change::new(room.id, author, op);
+
+    room.merge(change.stamp, change.op.clone());
+
+    if let Err(error) = outbox::push(&change) {
+        dioxus::logger::tracing::warn!("A change to room {} is not sent: {error}", room.id);
+    }
+}
+
+pub fn set_delivery(room: Room, id: Stamp, delivery: Delivery) {
+    let mut messages = room.messages;
+
+    messages.write().set_delivery(id, delivery);
+}
+
+/// The server reports that the message `id` of `room` reached the other members.
+pub fn delivered(room: Uuid, id: Stamp) {
+    let Some(room) = ROOMS.peek().get(&room).copied() else {
+        return;
+    };
+
+    // Only sent messages move on, a message sent again after failing waits for its new attempt.
+    if room.messages.peek().get(&id).is_some_and(|message| message.delivery == Delivery::Sent) {
+        set_delivery(room, id, Delivery::Delivered);
+    }
+}
+
+/// Record that `user` has read `room` up to the message `id`, and tell the other members if `share` is set.
+pub fn mark_read(room: Room, user: Uuid, id: Stamp, share: bool) {
+    if record(room, user, id) && share {
+        TRANSPORT.peek().read(room.id, id);
+    }
+}
+
+/// The server reports that `user` has read `room` up to the message `id`.
+pub fn read_up_to(room: Uuid, user: Uuid, id: Stamp) {
+    if let Some(room) = ROOMS.peek().get(&room).copied() {
+        record(room, user, id);
+    }
+}
+
+/// Move the read marker of `user` forward to `id`. Returns whether it moved.
+fn record(room: Room, user: Uuid, id: Stamp) -> bool {
+    if room.receipts.peek().get(&user).is_some_and(|read| *read >= id) {
+        return false;
+    }
+
+    let mut receipts = room.receipts;
+    receipts.write().insert(user, id);
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::InfoOp;
+    use crate::crypto::{DeviceKeys, DEVICE};
+    use crate::outbox::OUTBOX;
+    use crate::{room_id, testing, Author, MessageKind, RoomInfo, AUTHORS, USER_ID};
+
+    #[test]
+    fn changes_reach_other_devices() {
+        testing::runtime().run(|| {
+            let user = *USER_ID.peek();
+            let friend = DeviceKeys::generate(Uuid::new_v4());
+            crypto::add_device(DEVICE.peek().public());
+            crypto::add_device(friend.public());
+
+            let info = RoomInfo::new("Starwars".into(), None, [user, friend.user].into());
+            let room = Room::new(room_id("Starwars"), info.clone());
+            ROOMS.write().insert(room.id, room);
+
+            let message = Message::new(user, "Hello there".into(), MessageKind::Text);
+            send(room, message.clone());
+            room.edit(user, message.id, "General Kenobi".into());
+            room.react(message.id, user, "👍".into(), true);
+            room.hide_previews(user, message.id);
+            room.change_info(user, InfoOp::Topic(Some("Sand".into())));
+            room.change_info(user, InfoOp::Name { user, name: "Vader".into() });
+
+            assert_eq!(OUTBOX.peek().len(), 6);
+
+            // The device of the friend, with a replica of the room of its own, takes in what was sent.
+            let other = Room::new(room.id, info);
+            ROOMS.write().insert(room.id, other);
+            *DEVICE.write() = friend;
+            AUTHORS.write().insert(user, Author { id: user, username: "Anakin".into(), avatar: None, bio: None });
+
+            for outgoing in OUTBOX.peek().iter() {
+                receive(&outgoing.envelope, &outgoing.shares).unwrap();
+            }
+
+            let received = other.messages.peek().get(&message.id).cloned().unwrap();
+            let sent = room.messages.peek().get(&message.id).cloned().unwrap();
+
+            assert_eq!(received, Message { delivery: Delivery::Delivered, ..sent });
+            assert_eq!(received.content, "General Kenobi");
+            assert!(received.reactions.contains("👍", user));
+            assert!(!received.link_previews);
+            assert_eq!(*other.info.peek(), *room.info.peek());
+            assert_eq!(other.info.peek().topic.as_deref(), Some("Sand"));
+            assert_eq!(AUTHORS.peek()[&user].username, "Vader");
+
+            // Every key opens one envelope only.
+            let first = OUTBOX.peek()[0].clone();
+            assert_eq!(receive(&first.envelope, &first.shares), Err(t!("crypto-no-key")));
+        });
+    }
+
+    #[test]
+    fn attachments_reach_other_devices() {
+        testing::runtime().run(|| {
+            let user = *USER_ID.peek();
+            let friend = DeviceKeys::generate(Uuid::new_v4());
+            crypto::add_device(DEVICE.peek().public());
+            crypto::add_device(friend.public());
+
+            let info = RoomInfo::new("Starwars".into(), None, [user, friend.user].into());
+            let room = Room::new(room_id("Starwars"), info.clone());
+            ROOMS.write().insert(room.id, room);
+
+            let attachment = attachments::attach("sand.txt", None, b"coarse and rough").unwrap();
+            let message =
+                Message { attachments: vec![attachment.clone()], ..Message::new(user, String::new(), MessageKind::Text) };
+            send(room, message.clone());
+
+            // Kept by the sender as it was attached, the contents only travel in the envelope.
+            assert_eq!(OUTBOX.peek()[0].change, Change::post(room.id, &message));
+
+            let other = Room::new(room.id, info);
+            ROOMS.write().insert(room.id, other);
+            *DEVICE.write() = friend;
+
+            let outgoing = OUTBOX.peek()[0].clone();
+            receive(&outgoing.envelope, &outgoing.shares).unwrap();
+
+            // Taken into a blob of the friend's own.
+            attachments::discard(&attachment);
+            let received = other.messages.peek().get(&message.id).unwrap().attachments[0].clone();
+
+            assert_eq!(received.contents, None);
+            assert_eq!(attachments::data_url(&received).as_deref(), Some("data:text/plain;base64,Y29hcnNlIGFuZCByb3VnaA=="));
+        });
+    }
+
+    #[test]
+    fn refuses_changes_the_sender_may_not_make() {
+        testing::runtime().run(|| {
+            let user = *USER_ID.peek();
+            let keys = DEVICE.peek().clone();
+            let friend = DeviceKeys::generate(Uuid::new_v4());
+            let stranger = DeviceKeys::generate(Uuid::new_v4());
+
+            for device in [&keys, &friend, &stranger] {
+                crypto::add_device(device.public());
+            }
+
+            let room = Room::new(room_id("Starwars"), RoomInfo::new("Starwars".into(), None, [user, friend.user].into()));
+            ROOMS.write().insert(room.id, room);
+
+            let message = Message::new(user, "Hello there".into(), MessageKind::Text);
+
+            // Sealed on the devices of the friend and the stranger.
+            let sealed = |sender: &DeviceKeys, op: RoomOp| {
+                crypto::switch_device(sender.clone());
+                crypto::seal(&Change::new(room.id, sender.user, op)).unwrap()
+            };
+            let edit = MessageOp::Edit { message: message.id, author: friend.user, content: "Forged".into() };
+            let edit = sealed(&friend, edit.into());
+            let hide = sealed(&friend, MessageOp::HidePreviews { message: message.id, author: friend.user }.into());
+            let kick = sealed(&friend, InfoOp::Member { user, joined: false }.into());
+            let topic = sealed(&stranger, InfoOp::Topic(Some("Forged".into())).into());
+            let join = sealed(&stranger, InfoOp::Member { user: stranger.user, joined: true }.into());
+            crypto::switch_device(keys);
+
+            // The edits of the friend arrive before the message. They are held, and ignored once it is there.
+            receive(&edit.0, &edit.1).unwrap();
+            receive(&hide.0, &hide.1).unwrap();
+            room.post(message.clone());
+
+            assert_eq!(room.messages.peek().get(&message.id), Some(&message));
+
+            assert_eq!(receive(&kick.0, &kick.1), Err(t!("change-not-allowed")));
+            assert_eq!(receive(&topic.0, &topic.1), Err(t!("change-not-allowed")));
+            assert!(room.info.peek().members.contains(&user));
+            assert_eq!(room.info.peek().topic, None);
+
+            receive(&join.0, &join.1).unwrap();
+            assert!(room.info.peek().members.contains(&stranger.user));
+        });
+    }
+
+    #[test]
+    fn takes_in_the_typing_of_members() {
+        testing::runtime().run(|| {
+            let (user, friend) = (*USER_ID.peek(), Uuid::new_v4());
+            let room = Room::new(room_id("Starwars"), RoomInfo::new("Starwars".into(), None, [user, friend].into()));
+            ROOMS.write().insert(room.id, room);
+
+            receive_typing(room.id, friend, true).unwrap();
+
+            assert_eq!(receive_typing(room.id, Uuid::new_v4(), true), Err(t!("change-not-allowed")));
+            assert_eq!(receive_typing(Uuid::new_v4(), friend, true), Err(t!("room-gone")));
+            assert_eq!(typing::typists(room.id), [friend]);
+
+            receive_typing(room.id, friend, false).unwrap();
+
+            assert!(typing::typists(room.id).is_empty());
+        });
+    }
+}
@@ -0,0 +1,253 @@
+//! The typing module keeps track of who is typing in which room. The composer reports the user's own typing with
+//! [`typing`] and [`stopped`], which tell the other members through the [`Transport`](crate::transport::Transport).
+//! Typing is only passed on once per [`THROTTLE`] while the user keeps typing. The typing of others arrives through
+//! [`transport::receive_typing`](crate::transport::receive_typing) and is recorded with [`received`].
+//!
+//! Someone stops typing when they send their message or clear the composer, or [`TIMEOUT`] after their last event.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::time_format;
+use crate::transport::TRANSPORT;
+
+/// How often typing events are sent while someone keeps typing.
+pub const THROTTLE: Duration = Duration::from_secs(3);
+
+/// How long after their last event someone still counts as typing. Longer than [`THROTTLE`], so that someone who
+/// keeps typing does not flicker.
+pub const TIMEOUT: Duration = Duration::from_secs(6);
+
+/// For every room, who is typing and the number of their last event.
+pub static TYPING: GlobalSignal<HashMap<Uuid, BTreeMap<Uuid, u64>>> = Global::new(HashMap::new);
+
+/// The rooms the user was passed on as typing in less than [`THROTTLE`] ago, with the number of that event.
+static THROTTLED: GlobalSignal<HashMap<Uuid, u64>> = Global::new(HashMap::new);
+
+/// Counts the typing events, so that a timer can tell whether a newer event came in while it ran.
+static EVENTS: GlobalSignal<u64> = Global::new(|| 0);
+
+fn next_event() -> u64 {
+    let mut events = EVENTS.write();
+    *events += 1;
+    *events
+}
+
+/// Report that `user`, the local user, is typing in `room`, and tell the other members unless they were told less
+/// than [`THROTTLE`] ago.
+pub fn typing(room: Uuid, user: Uuid) {
+    if THROTTLED.peek().contains_key(&room) {
+        return;
+    }
+
+    let event = next_event();
+    THROTTLED.write().insert(room, event);
+
+    spawn_forever(async move {
+        time_format::sleep(THROTTLE).await;
+
+        if THROTTLED.peek().get(&room) == Some(&event) {
+            THROTTLED.write().remove(&room);
+        }
+    });
+
+    received(room, user, true);
+    TRANSPORT.peek().typing(room, true);
+}
+
+/// Report that `user`, the local user, stopped typing in `room`, and tell the other members.
+pub fn stopped(room: Uuid, user: Uuid) {
+    // Typing again right after sending a message starts a new event.
+    if THROTTLED.peek().contains_key(&room) {
+        THROTTLED.write().remove(&room);
+    }
+
+    if remove(room, user) {
+        TRANSPORT.peek().typing(room, false);
+    }
+}
+
+/// Record that `user` started or stopped typing in `room`. Someone who started counts as typing until they stop, or
+/// for [`TIMEOUT`] unless another event comes in.
+pub fn received(room: Uuid, user: Uuid, typing: bool) {
+    if !typing {
+        remove(room, user);
+        return;
+    }
+
+    let event = next_event();
+    TYPING.write().entry(room).or_default().insert(user, event);
+
+    // Owned by the root scope, so the timeout runs out even if the composer is gone by then.
+    spawn_forever(async move {
+        time_format::sleep(TIMEOUT).await;
+
+        // Unless a newer event came in meanwhile.
+        if TYPING.peek().get(&room).and_then(|users| users.get(&user)) == Some(&event) {
+            remove(room, user);
+        }
+    });
+}
+
+/// Forget that `user` is typing in `room`. Returns whether they were.
+fn remove(room: Uuid, user: Uuid) -> bool {
+    if !TYPING.peek().get(&room).is_some_and(|users| users.contains_key(&user)) {
+        return false;
+    }
+
+    let mut typing = TYPING.write();
+
+    if let Some(users) = typing.get_mut(&room) {
+        users.remove(&user);
+
+        if users.is_empty() {
+            typing.remove(&room);
+        }
+    }
+
+    true
+}
+
+/// Who is typing in `room`.
+pub fn typists(room: Uuid) -> Vec<Uuid> {
+    TYPING.read().get(&room).map(|users| users.keys().copied().collect()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::crdt::Stamp;
+    use crate::crypto::{Envelope, KeyShare};
+    use crate::i18n::t;
+    use crate::transport::{SendFuture, Transport};
+    use crate::{room_id, testing, Route, AUTHORS, USER_ID};
+
+    const ROOM: Uuid = Uuid::from_u128(1);
+    const FRIEND: Uuid = Uuid::from_u128(2);
+
+    /// Records whether the user was said to be typing, event by event.
+    #[derive(Default)]
+    struct Recording(Rc<RefCell<Vec<bool>>>);
+
+    impl Transport for Recording {
+        fn send(&self, _envelope: &Envelope, _shares: &[KeyShare]) -> SendFuture {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn read(&self, _room: Uuid, _id: Stamp) {}
+
+        fn typing(&self, _room: Uuid, typing: bool) {
+            self.0.borrow_mut().push(typing);
+        }
+
+        fn listen(&self, _on_change: Rc<dyn Fn(Envelope, Vec<KeyShare>)>, _on_typing: Rc<dyn Fn(Uuid, Uuid, bool)>) {}
+    }
+
+    fn recording() -> Rc<RefCell<Vec<bool>>> {
+        let recording = Recording::default();
+        let events = recording.0.clone();
+        *TRANSPORT.write() = Rc::new(recording);
+
+        events
+    }
+
+    #[test]
+    fn typing_is_passed_on_once_per_throttle() {
+        let mut app = testing::runtime();
+        let (user, events) = app.run(|| (*USER_ID.peek(), recording()));
+
+        app.run(|| {
+            typing(ROOM, user);
+            typing(ROOM, user);
+        });
+        app.advance(THROTTLE / 2);
+        app.run(|| typing(ROOM, user));
+
+        assert_eq!(*events.borrow(), [true]);
+
+        app.advance(THROTTLE);
+        app.run(|| typing(ROOM, user));
+
+        assert_eq!(*events.borrow(), [true, true]);
+
+        // Stopping is passed on once, and typing right after is not held back.
+        app.run(|| {
+            stopped(ROOM, user);
+            stopped(ROOM, user);
+            typing(ROOM, user);
+
+            assert_eq!(typists(ROOM), [user]);
+        });
+
+        assert_eq!(*events.borrow(), [true, true, false, true]);
+    }
+
+    #[test]
+    fn typing_runs_out_without_new_events() {
+        let mut app = testing::runtime();
+        let second = Duration::from_secs(1);
+
+        app.run(|| received(ROOM, FRIEND, true));
+        app.advance(TIMEOUT - second);
+        app.run(|| {
+            assert_eq!(typists(ROOM), [FRIEND]);
+            received(ROOM, FRIEND, true);
+        });
+
+        // The first event ran out meanwhile, the second one did not.
+        app.advance(TIMEOUT - second);
+        app.run(|| assert_eq!(typists(ROOM), [FRIEND]));
+
+        app.advance(2 * second);
+        app.run(|| {
+            assert!(typists(ROOM).is_empty());
+            assert!(TYPING.peek().is_empty());
+
+            received(ROOM, FRIEND, true);
+            received(ROOM, FRIEND, false);
+
+            assert!(typists(ROOM).is_empty());
+        });
+    }
+
+    #[test]
+    fn several_typists_are_summed_up() {
+        let mut app = testing::app(Route::Chat { id: room_id("Starwars") });
+        let named = |name: &str| *AUTHORS.peek().iter().find(|(_, author)| author.username == name).unwrap().0;
+        let (user, obi, yoda, luke) =
+            app.run(|| (*USER_ID.peek(), named("Obi-Wan Kenobi"), named("Yoda"), named("Luke Skywalker")));
+        let room = room_id("Starwars");
+        let (anakin, one, two, several) = app.run(|| {
+            (
+                t!("typing-one", name = "Anakin"),
+                t!("typing-one", name = "Obi-Wan Kenobi"),
+                [
+                    t!("typing-two", first = "Obi-Wan Kenobi", second = "Yoda"),
+                    t!("typing-two", first = "Yoda", second = "Obi-Wan Kenobi"),
+                ],
+                t!("typing-several"),
+            )
+        });
+
+        let mut typing = |typist| {
+            app.run(|| received(room, typist, true));
+            app.render();
+            app.html()
+        };
+
+        // Nobody is told that they are typing themselves.
+        assert!(!typing(user).contains(&anakin));
+        assert!(typing(obi).contains(&one));
+
+        let html = typing(yoda);
+        assert!(two.iter().any(|two| html.contains(two)));
+
+        assert!(typing(luke).contains(&several));
+    }
+}
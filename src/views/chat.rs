@@ -1,98 +1,285 @@
-use std::time::Instant;
+use std::sync::Arc;
 
-use crate::{Author, Message, AUTHORS, ROOMS};
+use crate::attachments::{self, Attachment};
+use crate::components::PresenceDot;
+use crate::crdt::Stamp;
+use crate::commands::{self, CommandContext, Outcome, COMMANDS};
+use crate::history;
+use crate::i18n::t;
+use crate::previews::{self, PreviewState, PREVIEWS};
+use crate::search::FOCUS;
+use crate::settings::{use_settings, SendKey, TimestampStyle};
+use crate::shortcuts::Action;
+use crate::time_format::{self, NOW};
+use crate::transport::{self, Delivery, CONNECTED};
+use crate::typing;
+use crate::{render_stats, storage, Author, Draft, Message, MessageKind, Room, Route, ACTIVE_ROOM, AUTHORS, DRAFTS, ROOMS};
+use chrono::{DateTime, Local, NaiveDate, TimeDelta, Utc};
+use dioxus::html::{FileEngine, HasFileData};
 use dioxus::prelude::*;
-use dioxus_free_icons::{icons::ld_icons::{LdMoveDown, LdSendHorizontal}, Icon};
+use dioxus_free_icons::{icons::ld_icons::{LdCheck, LdCheckCheck, LdCircleAlert, LdClock, LdMoveDown, LdPaperclip, LdSendHorizontal, LdX}, Icon};
 use uuid::Uuid;
 
+/// How many messages are mounted before the viewer has been measured, and how many are kept mounted above and below
+/// the visible ones while scrolling.
+const INITIAL_WINDOW: usize = 30;
+const OVERSCAN: usize = 10;
+
+/// Used for the rows that are not mounted until real rows have been measured.
+const ESTIMATED_ROW_HEIGHT: f64 = 96.0;
+
+/// The longest message that can be sent, in characters. The composer shows a counter from three quarters of it on.
+const MAX_MESSAGE_LENGTH: usize = 4000;
+
+/// Inserting at least this many characters at once, usually by pasting, asks whether to keep them.
+const LONG_PASTE_LENGTH: usize = 1000;
+
 #[component]
 pub fn Chat(id: Uuid) -> Element {
-    let local_user = use_context::<Author>();
+    render_stats::record("Chat");
 
-    let mut input_line: Signal<usize> = use_signal(|| 0);
-    let mut input_lines: Signal<Vec<String>> = use_signal(|| vec![String::new()]);
-    let mut input_cursor: Signal<usize> = use_signal(|| 0);
-    let mut last_message: Signal<Option<std::rc::Rc<MountedData>>> = use_signal(|| None);
-    let mut message_viewer: Signal<Option<std::rc::Rc<MountedData>>> = use_signal(|| None);
-    let mut chat_input: Signal<Option<std::rc::Rc<MountedData>>> = use_signal(|| None);
-    let mut show_scroll = use_signal(|| false);
+    let user = use_context::<Signal<Author>>();
 
-    let rooms = ROOMS.read();
-    let authors = AUTHORS.read();
+    let mut chat_input: Signal<Option<std::rc::Rc<MountedData>>> = use_signal(|| None);
 
-    let Some(room) = rooms.get(&id) else {
+    // Only reads the map of rooms, so messages posted to the room do not re-render the chat view itself.
+    let Some(room) = ROOMS.read().get(&id).copied() else {
         return rsx! {
             div {
-                "EMPTY ROOM"
+                {t!("room-not-found")}
             }
         }
     };
 
-    let messages = room.messages.iter().map(|(_, msg)| {
-        let (side_class, name, avatar_url) = if msg.author == local_user.id {
-            (
-                "chat chat-end",
-                local_user.username.as_str(),
-                local_user.avatar.as_ref()
-                        .and_then(|url| Some(url.as_str()))
-            )
-        } else {
-            authors
-                .get(&msg.author)
-                .map(|author| (
-                    "chat chat-start",
-                    author.username.as_str(),
-                    author.avatar.as_ref()
-                        .and_then(|url| Some(url.as_str()))
-                ))
-                .unwrap_or((
-                    "chat chat-start",
-                    "Unknown", 
-                    None
-                ))
-        };
+    let focus_chat_input = move || async move {
+        if let Some(chat_input) = chat_input.cloned() {
+            let _ = chat_input.set_focus(true).await;
+        }
+    };
 
-        rsx! {
-            div { 
-                class: "{side_class} gap-2",
-                div { 
-                    class: "chat-image avatar",
-                    
-                    div { 
-                        class: "w-10 rounded-full",
-                        if let Some(avatar_url) = avatar_url {
-                            img {
-                                alt: "{name}",
-                                src: "{avatar_url}"
-                            }
-                        }
-                    }
-                }
-                div { 
-                    class: "chat-bubble", 
-                    for line in msg.content.split('\n') {
-                        p {
-                            span {
-                                "{line}"
-                            }
-                        }
-                    }
-                }
-                div { 
-                    class: "chat-header",
-                    "{name}",
+    let send_message = move |(content, attachments): (String, Vec<Attachment>)| {
+        let author = user.peek().id;
+
+        transport::send(room, Message {
+            attachments,
+            ..Message::new(author, content, MessageKind::Text)
+        });
+    };
+
+    let topic = room.info.read().topic.clone();
+
+    rsx! {
+        div {
+            onmounted: move |_| async move {
+                focus_chat_input().await;
+            },
+            class: "flex flex-col w-full gap-4 p-2 h-full", 
+            if let Some(topic) = topic {
+                div {
+                    class: "px-4 text-sm opacity-75",
+                    "{topic}"
                 }
+            }
+            if !CONNECTED() {
                 div {
-                    class: "chat-footer opacity-50",
-                    time { 
-                        class: "text-xs text-secondary opacity-50", 
-                        "{msg.id.elapsed().as_secs()}s ago" 
-                    }
+                    class: "alert alert-warning alert-soft py-2 text-sm",
+                    role: "status",
+                    {t!("offline-banner")}
                 }
             }
+            MessageList {
+                // Keyed by room so that the scroll position and mounted window start over in every room.
+                key: "{id}",
+                room: room,
+                onscrolled: move |_| async move {
+                    focus_chat_input().await;
+                },
+            }
+            TypingIndicator { room: id }
+            ChatInput {
+                // Keyed by room so that switching rooms swaps in that room's draft instead of carrying the composer over.
+                key: "{id}",
+                room: id,
+                onsend: send_message,
+                onmounted: move |evt: MountedEvent| chat_input.set(Some(evt.data())),
+            }
+        }
+    }
+}
+
+/// Who else is typing in the room, aggregated when several people are. Keeps its height while nobody is typing, so
+/// that the composer does not jump.
+#[component]
+fn TypingIndicator(room: Uuid) -> Element {
+    let user = use_context::<Signal<Author>>();
+    let names = use_memo(move || {
+        let user = user.read().id;
+        let authors = AUTHORS.read();
+
+        typing::typists(room).into_iter()
+            .filter(|typist| *typist != user)
+            .map(|typist| authors.get(&typist).map(|author| author.username.clone()).unwrap_or_else(|| t!("unknown-user")))
+            .collect::<Vec<_>>()
+    });
+
+    let text = match names.read().as_slice() {
+        [] => String::new(),
+        [name] => t!("typing-one", name = name.clone()),
+        [first, second] => t!("typing-two", first = first.clone(), second = second.clone()),
+        _ => t!("typing-several"),
+    };
+
+    rsx! {
+        div {
+            class: "h-5 -my-2 px-4 text-xs italic opacity-75",
+            "{text}"
+        }
+    }
+}
+
+/// Keeps the messages in view in place when older ones are inserted above them, by scrolling down as much as the
+/// viewer grew once the new messages have been rendered.
+const PRESERVE_SCROLL_JS: &str = r#"
+    const viewer = document.getElementById(await dioxus.recv());
+    const height = viewer ? viewer.scrollHeight : 0;
+
+    await dioxus.recv();
+
+    requestAnimationFrame(() => requestAnimationFrame(() => {
+        if (viewer) {
+            viewer.scrollTop += viewer.scrollHeight - height;
+        }
+    }));
+"#;
+
+/// The messages of a room. Only the messages in view, plus [`OVERSCAN`] on each side, are mounted; the rest are
+/// replaced by spacers sized from the measured average row height, so rooms with any number of messages scroll
+/// the same. The list stays anchored to the newest message while scrolled to the bottom.
+///
+/// The room's history is loaded a page at a time: the newest page when the list mounts and older pages when it is
+/// scrolled near the top.
+#[component]
+fn MessageList(room: Room, onscrolled: EventHandler<()>) -> Element {
+    render_stats::record("MessageList");
+
+    let mut last_message: Signal<Option<std::rc::Rc<MountedData>>> = use_signal(|| None);
+    let mut message_viewer: Signal<Option<std::rc::Rc<MountedData>>> = use_signal(|| None);
+    let mut show_scroll = use_signal(|| false);
+    let mut scroll_top = use_signal(|| 0.0);
+    let mut viewport = use_signal(|| 0.0);
+    let mut row_height = use_signal(|| ESTIMATED_ROW_HEIGHT);
+    let mut loading = use_signal(|| false);
+    // The message opened from a search result, highlighted for as long as the room stays open.
+    let mut highlight = use_signal(|| None);
+
+    let total = use_memo(move || room.messages.read().len());
+    // Changes at midnight, when the day separators need new labels.
+    let today = use_memo(move || NOW().with_timezone(&Local).date_naive());
+    let complete = use_memo(move || (room.history_complete)());
+    let name = use_memo(move || room.info.read().name.clone());
+    let newest = use_memo(move || room.messages.read().last_key_value().map(|(id, _)| *id));
+    let viewer_id = format!("messages-{}", room.id);
+    let user = use_context::<Signal<Author>>();
+    let settings = use_settings();
+    let read_receipts = use_memo(move || settings.read().read_receipts);
+
+    // Who else has read the newest message of the user, shown under it while read receipts are on.
+    let seen = use_memo(move || {
+        if !read_receipts() {
+            return None;
+        }
+
+        let user = user.read().id;
+        let messages = room.messages.read();
+        let (id, _) = messages.iter().rev().find(|(_, message)| message.author == user && message.kind != MessageKind::Notice)?;
+        let readers: Vec<Uuid> = room.receipts.read().iter()
+            .filter(|(reader, read)| **reader != user && *read >= id)
+            .map(|(reader, _)| *reader)
+            .collect();
+
+        Some((*id, readers))
+    });
+
+    // What screen readers announce. Rows mount and unmount as the list scrolls, so the list itself is not live.
+    let mut announcement = use_signal(String::new);
+    let mut announced = use_signal(|| *newest.peek());
+
+    // Messages posted while the list is mounted are read right away, so they do not count as unread.
+    use_hook(move || {
+        let mut unread = room.unread;
+
+        *ACTIVE_ROOM.write() = Some(room.id);
+        unread.set(0);
+    });
+
+    use_drop(move || {
+        if *ACTIVE_ROOM.peek() == Some(room.id) {
+            *ACTIVE_ROOM.write() = None;
+        }
+
+        // A search result that never came into view is not brought up again on the next visit.
+        if FOCUS.peek().is_some_and(|focus| focus.room == room.id) {
+            *FOCUS.write() = None;
         }
     });
 
+    // The newest message is read as soon as it is in the open room.
+    use_effect(move || {
+        if let Some(id) = newest() {
+            transport::mark_read(room, user.peek().id, id, *read_receipts.peek());
+        }
+    });
+
+    // Announce messages from others as they arrive. Older pages are inserted above the newest message, so they
+    // are not announced, and neither is the first page, which sets the newest message for the first time.
+    use_effect(move || {
+        let latest = newest();
+        let previous = announced.replace(latest);
+
+        if previous.is_none() || previous == latest {
+            return;
+        }
+
+        let messages = room.messages.peek();
+        let Some(message) = latest.and_then(|id| messages.get(&id)) else {
+            return;
+        };
+
+        if message.author == user.peek().id {
+            return;
+        }
+
+        let author = AUTHORS.peek()
+            .get(&message.author)
+            .map(|author| author.username.clone())
+            .unwrap_or_else(|| t!("unknown-user"));
+
+        announcement.set(t!("message-announcement", author = author, content = message.text()));
+    });
+
+    let load_older = move || async move {
+        if *loading.peek() || *complete.peek() {
+            return;
+        }
+
+        loading.set(true);
+
+        // The first page goes to the bottom of the list, only older pages need to keep the scroll position.
+        let preserve = (*total.peek() > 0).then(|| {
+            let preserve = document::eval(PRESERVE_SCROLL_JS);
+            let _ = preserve.send(format!("messages-{}", room.id));
+            preserve
+        });
+
+        let _ = history::load_older(room.id).await;
+
+        if let Some(preserve) = preserve {
+            let _ = preserve.send(());
+        }
+
+        loading.set(false);
+    };
+
     let update_viewer = move || async move {
         if let Some(viewer) = message_viewer.cloned() {
             if let Ok(scroll) = viewer.get_scroll_size().await {
@@ -105,6 +292,25 @@ pub fn Chat(id: Uuid) -> Element {
                         } else {
                             show_scroll.set(true);
                         }
+
+                        // Re-estimate the row height from the rows that are mounted, everything else is spacers.
+                        let range = visible_range(*total.peek(), *scroll_top.peek(), *viewport.peek(), *row_height.peek());
+                        let spacers = (total.peek().saturating_sub(range.len())) as f64 * *row_height.peek();
+
+                        if !range.is_empty() {
+                            let measured = ((scroll.height - spacers) / range.len() as f64).clamp(24.0, 1200.0);
+
+                            if (measured - *row_height.peek()).abs() > 1.0 {
+                                row_height.set(measured);
+                            }
+                        }
+
+                        scroll_top.set(offset.y);
+                        viewport.set(rect.size.height);
+
+                        if offset.y < rect.size.height / 2.0 {
+                            spawn(load_older());
+                        }
                     }
                 }
             }
@@ -121,238 +327,865 @@ pub fn Chat(id: Uuid) -> Element {
         }
     };
 
-    let focus_chat_input = move || async move {
-        if let Some(chat_input) = chat_input.cloned() {
-            let _ = chat_input.set_focus(true).await;
+    // Follow new messages while scrolled to the bottom.
+    use_effect(move || {
+        total();
+
+        if !*show_scroll.peek() {
+            spawn(async move {
+                scroll_to_last(false).await;
+                update_viewer().await;
+            });
+        }
+    });
+
+    // A search result in this room was opened: mount the rows around it so it can scroll itself into view, which
+    // clears the focus.
+    use_effect(move || {
+        let Some(focus) = *FOCUS.read() else {
+            return;
+        };
+
+        if focus.room != room.id {
+            return;
         }
-    };
 
-    let send_message = move |content: String| async move {
-        let ts = Instant::now();
+        highlight.set(Some(focus.id));
 
-        {
-            let mut rooms = ROOMS.write();
+        // The message may be older than what has been loaded so far. Older pages are loaded the way scrolling up
+        // loads them, one at a time, and every page that arrives runs this again. A page that fails to load stops it.
+        total();
 
-            if let Some(room) = rooms.get_mut(&id) {
-                room.messages.insert(ts, Message {
-                    id: ts,
-                    author: local_user.id,
-                    content,
-                });
+        match room.messages.peek().keys().position(|id| *id == focus.id) {
+            Some(index) => scroll_top.set(index as f64 * *row_height.peek()),
+            None => {
+                spawn(load_older());
             }
         }
+    });
+
+    let range = visible_range(total(), scroll_top(), viewport(), row_height());
+    let top_spacer = range.start as f64 * row_height();
+    let bottom_spacer = (total() - range.end) as f64 * row_height();
+
+    // One message on each side of the range decides whether the first and last rows continue a group.
+    let messages: Vec<_> = {
+        let today = today();
+        let seen = seen.read();
+        let messages = room.messages.read();
+        let first = range.start.saturating_sub(1);
+        let window: Vec<_> = messages.values().skip(first).take(range.end + 1 - first).collect();
+
+        (range.start..range.end).map(|index| {
+            let message = window[index - first];
+            let previous = index.checked_sub(1).and_then(|index| window.get(index - first));
+            let next = window.get(index + 1 - first);
+
+            let separator = time_format::separator(previous.map(|previous| previous.sent), message.sent, today);
+
+            let seen_by = seen.as_ref()
+                .filter(|(id, _)| *id == message.id)
+                .map(|(_, readers)| readers.clone())
+                .unwrap_or_default();
+
+            (
+                message.clone(),
+                separator,
+                previous.is_some_and(|previous| continues_group(previous, message)),
+                next.is_some_and(|next| continues_group(message, next)),
+                seen_by,
+            )
+        }).collect()
     };
 
-    let input_handler = move |evt: Event<KeyboardData>| async move {
-        focus_chat_input().await;
+    rsx! {
+        div {
+            class: "sr-only",
+            role: "status",
+            aria_live: "polite",
+            "{announcement}"
+        }
+        div {
+            id: "{viewer_id}",
+            role: "feed",
+            aria_label: t!("messages-in", name = name()),
+            aria_busy: loading(),
+            // Focusable so that the messages can be scrolled with the keyboard.
+            tabindex: 0,
+            onmounted: move |evt| async move {
+                message_viewer.set(Some(evt.data()));
 
-        let mut idx = input_line.write();
-        let mut lines = input_lines.write();
-        let mut cursor = input_cursor.write();
+                if *total.peek() == 0 {
+                    load_older().await;
+                }
 
-        match evt.key() {
-            Key::Enter if evt.modifiers().shift() => {
-                evt.prevent_default();
-                // split at cursor
-                let rest = lines[*idx].split_off(*cursor);
-                
-                lines.insert(*idx + 1, rest);
+                if !FOCUS.peek().is_some_and(|focus| focus.room == room.id) {
+                    scroll_to_last(false).await;
+                }
 
-                *idx += 1;
-                *cursor = 0;
+                update_viewer().await;
+            },
+            onresize: move |_| async move {
+                update_viewer().await;
+            },
+            onscroll: move |_| async move {
+                update_viewer().await;
+            },
+            class: "flex flex-col card gap-1 p-4 pe-8 grow text-wrap overflow-y-auto justify-end",
+            if show_scroll() && last_message().is_some() {
+                button {
+                    class: "flex flex-col btn btn-ghost hover:btn-outline fixed bottom-20 left-[50%] w-24 right-[50%]",
+                    title: t!("scroll-to-latest"),
+                    aria_label: t!("scroll-to-latest"),
+                    onclick: move |_| async move {
+                        scroll_to_last(true).await;
+                        onscrolled.call(());
+                    },
+                    Icon {
+                        class: "stroke-slate-500 dark:stroke-slate-300 opacity-25 group-hover:opacity-100",
+                        width: 20,
+                        height: 20,
+                        icon: LdMoveDown,
+                    }
+                }
             }
-            Key::Enter => {
-                send_message(lines.join("\n")).await;
+            if complete() {
+                div {
+                    class: "self-center text-sm opacity-50",
+                    {t!("history-beginning", name = name())}
+                }
+            } else if loading() {
+                for _ in 0..3 {
+                    div {
+                        class: "chat chat-start gap-2",
+                        div { class: "chat-image skeleton w-10 h-10 rounded-full shrink-0" }
+                        div { class: "chat-bubble skeleton w-48 h-12 bg-base-300" }
+                    }
+                }
+            }
+            if top_spacer > 0.0 {
+                div {
+                    class: "shrink-0",
+                    style: "height: {top_spacer}px",
+                }
+            }
+            for (message, separator, grouped, continued, seen_by) in messages {
+                MessageBubble {
+                    key: "{message.id:?}",
+                    room: room,
+                    highlighted: highlight() == Some(message.id),
+                    message: message,
+                    separator: separator,
+                    grouped: grouped,
+                    continued: continued,
+                    seen_by: seen_by,
+                }
+            }
+            if bottom_spacer > 0.0 {
+                div {
+                    class: "shrink-0",
+                    style: "height: {bottom_spacer}px",
+                }
+            }
+            div {
+                class: "h-4",
+                onmounted: move |evt| last_message.set(Some(evt.data())),
+            }
+        }
+    }
+}
+
+/// The range of messages to mount for a viewer scrolled to `scroll_top` showing `viewport` pixels, when rows are
+/// `row_height` pixels high. Before the viewer has been measured the newest messages are mounted.
+fn visible_range(total: usize, scroll_top: f64, viewport: f64, row_height: f64) -> std::ops::Range<usize> {
+    if viewport <= 0.0 || row_height <= 0.0 {
+        return total.saturating_sub(INITIAL_WINDOW)..total;
+    }
+
+    let first = ((scroll_top.max(0.0) / row_height) as usize).saturating_sub(OVERSCAN);
+    let count = (viewport / row_height).ceil() as usize + 2 * OVERSCAN;
+
+    let start = first.min(total.saturating_sub(count));
+
+    start..(start + count).min(total)
+}
+
+/// Consecutive messages from the same author sent within this long of each other are shown as one group.
+const GROUP_WINDOW: TimeDelta = TimeDelta::minutes(5);
+
+/// Whether `next`, the message after `message`, is shown in the same group: only the first message of a group has
+/// the avatar and name and only the last one the footer.
+fn continues_group(message: &Message, next: &Message) -> bool {
+    message.kind == MessageKind::Text
+        && next.kind == MessageKind::Text
+        && message.author == next.author
+        && next.sent - message.sent < GROUP_WINDOW
+        && local_day(message) == local_day(next)
+}
+
+fn local_day(message: &Message) -> NaiveDate {
+    time_format::local_day(message.sent)
+}
+
+/// A single message. Its props compare equal as long as the message is unchanged, so posting to a room only renders
+/// the new bubble.
+#[component]
+fn MessageBubble(
+    room: Room,
+    message: Message,
+    /// The label of the date separator shown above the message, if it is the first message of its day.
+    separator: Option<String>,
+    /// Whether the message continues the group of the message before it.
+    grouped: bool,
+    /// Whether the message after it continues its group.
+    continued: bool,
+    /// The other members who have read up to this message, for the newest message of the user.
+    seen_by: Vec<Uuid>,
+    /// Whether the message was opened from a search result.
+    highlighted: bool,
+) -> Element {
+    render_stats::record("MessageBubble");
+
+    let user = use_context::<Signal<Author>>();
+    let settings = use_settings();
+    let compact = settings.read().compact;
+
+    let local_user = user.read();
+    let authors = AUTHORS.read();
+    let msg = &message;
+    let unknown = t!("unknown-user");
 
-                lines.clear();
-                lines.push(String::new());
+    let (side_class, name, avatar_url) = if msg.author == local_user.id {
+        (
+            "chat chat-end",
+            local_user.username.as_str(),
+            local_user.avatar.as_deref()
+        )
+    } else {
+        authors
+            .get(&msg.author)
+            .map(|author| (
+                "chat chat-start",
+                author.username.as_str(),
+                author.avatar.as_deref()
+            ))
+            .unwrap_or((
+                "chat chat-start",
+                unknown.as_str(),
+                None
+            ))
+    };
+
+    let separator = separator.map(|label| rsx! {
+        div {
+            class: "divider text-xs opacity-50 mt-6",
+            role: "separator",
+            "{label}"
+        }
+    });
+
+    // Groups are set apart from each other, the messages within a group sit close together.
+    let spacing = match (grouped || separator.is_some(), compact) {
+        (true, _) => "",
+        (false, true) => "mt-2",
+        (false, false) => "mt-6",
+    };
 
-                *idx = 0;
-                *cursor = 0;
+    match msg.kind {
+        MessageKind::Emote => return rsx! {
+            {separator}
+            div {
+                class: "self-center text-sm italic opacity-75 {spacing}",
+                "* {name} {msg.content}"
+            }
+        },
+        MessageKind::Notice => return rsx! {
+            {separator}
+            div {
+                class: "self-center text-xs opacity-50 {spacing}",
+                {msg.text()}
             }
-            Key::Backspace => {
-                if *cursor > 0 {
-                    let pos = *cursor - 1;
+        },
+        MessageKind::Text => {}
+    }
 
-                    lines[*idx].remove(pos);
-                    *cursor = pos;
-                } else if *idx > 0 {
-                    // merge up
-                    let tail = lines.remove(*idx);
+    let msg_id = msg.id;
+    let is_own = msg.author == local_user.id;
+    let preview_url = (previews::SUPPORTED && msg.link_previews)
+        .then(|| previews::find_urls(&msg.content).first().map(|url| url.to_string()))
+        .flatten();
+    let exact = time_format::exact(msg.sent);
+    let reactions = msg.reactions.summary(local_user.id);
+    // Only the last bubble of a group points at the avatar.
+    let tail = if continued { "before:hidden" } else { "" };
+
+    rsx! {
+        {separator}
+        div { 
+            class: if highlighted { "{side_class} {spacing} gap-x-2 rounded-box ring-2 ring-accent" } else { "{side_class} {spacing} gap-x-2" },
+            role: "article",
+            aria_label: "{name}, {exact}",
+            // Opened from a search result: bring the message into view, once.
+            onmounted: move |evt| async move {
+                if FOCUS.peek().is_some_and(|focus| focus.room == room.id && focus.id == msg_id) {
+                    *FOCUS.write() = None;
+                    let _ = evt.data().scroll_to(ScrollBehavior::Smooth).await;
+                }
+            },
+            if !compact {
+                div { 
+                    class: "chat-image avatar",
                     
-                    *idx -= 1;
-                    lines[*idx - 1].push_str(&tail);
-                    *cursor = 0;
+                    div { 
+                        class: "w-10 rounded-full",
+                        if let Some(avatar_url) = avatar_url.filter(|_| !grouped) {
+                            img {
+                                alt: "{name}",
+                                src: "{avatar_url}"
+                            }
+                        }
+                    }
+                    if !grouped {
+                        PresenceDot { user: msg.author }
+                    }
                 }
             }
-            Key::Delete => {
-                if *cursor < lines[*idx].len() {
-                    lines[*idx].remove(*cursor);
-                } else if *idx + 1 < lines.len() {
-                    let next = lines.remove(*idx + 1);
-                    lines[*idx].push_str(&next);
-                    *cursor = 0;
+            div { 
+                class: if compact { "chat-bubble {tail} min-h-0 py-1" } else { "chat-bubble {tail}" }, 
+                title: "{exact}",
+                if !msg.content.is_empty() {
+                    for line in msg.content.split('\n') {
+                        p {
+                            span {
+                                "{line}"
+                            }
+                        }
+                    }
                 }
-            }
-            Key::Character(ch) => {
-                if ch == "v" && (evt.modifiers().ctrl() || evt.modifiers().meta()) {
-                    evt.prevent_default();
-                } else {
-                    lines[*idx].insert_str(*cursor, ch.as_str());
-                    *cursor += ch.len();
+                if let Some(edited) = msg.edited {
+                    span {
+                        class: "text-xs opacity-50",
+                        title: "{time_format::exact(edited.time())}",
+                        {t!("message-edited")}
+                    }
+                }
+                for attachment in msg.attachments.iter() {
+                    AttachmentView {
+                        key: "{attachment.blob.0}",
+                        attachment: attachment.clone(),
+                    }
+                }
+                if let Some(url) = preview_url {
+                    LinkPreviewCard {
+                        url: url,
+                        fetch: is_own || settings.read().auto_previews,
+                        onhide: is_own.then_some(EventHandler::new(move |_| {
+                            room.hide_previews(user.peek().id, msg_id);
+                        })),
+                    }
+                }
+                if !reactions.is_empty() {
+                    div {
+                        class: "flex flex-row flex-wrap gap-1 mt-1",
+                        for (emoji, count, mine) in reactions {
+                            ReactionChip {
+                                key: "{emoji}",
+                                room: room,
+                                id: msg_id,
+                                emoji: emoji,
+                                count: count,
+                                mine: mine,
+                            }
+                        }
+                    }
                 }
             }
-            Key::ArrowLeft => {
-                if *cursor > 0 {
-                    *cursor -= 1;
-                } else if *idx > 0 {
-                    *idx -= 1;
-                    *cursor = 0;
+            if !grouped {
+                div { 
+                    class: "chat-header",
+                    Link { to: Route::Profile { id: msg.author }, class: "link link-hover", "{name}" }
                 }
             }
-            Key::ArrowRight => {
-                if *cursor < lines[*idx].len() {
-                    *cursor += 1;
-                } else if *idx + 1 < lines.len() {
-                    *idx += 1;
-                    *cursor = 0;
+            // Failed messages always show why, wherever they are in their group.
+            if !continued || !seen_by.is_empty() || matches!(msg.delivery, Delivery::Failed(_)) {
+                div {
+                    class: "chat-footer opacity-50 flex flex-row items-center gap-1",
+                    if !continued {
+                        Timestamp { time: msg.sent }
+                    }
+                    if is_own {
+                        DeliveryState { room: room, id: msg_id, delivery: msg.delivery.clone() }
+                    }
+                    if !seen_by.is_empty() {
+                        SeenBy { readers: seen_by }
+                    }
                 }
             }
-            Key::ArrowUp if *idx > 0 => {
-                *idx -= 1;
-                *cursor = 0;
+        }
+    }
+}
+
+/// An emoji reacted with and how many reacted with it. Clicking it adds or takes back the user's own reaction.
+#[component]
+fn ReactionChip(room: Room, id: Stamp, emoji: String, count: usize, mine: bool) -> Element {
+    let user = use_context::<Signal<Author>>();
+    let label = t!(if mine { "reaction-remove" } else { "reaction-add" }, emoji = emoji.clone());
+    let toggle = emoji.clone();
+
+    rsx! {
+        button {
+            class: if mine { "btn btn-xs btn-primary" } else { "btn btn-xs" },
+            title: "{label}",
+            aria_label: "{label}",
+            aria_pressed: mine,
+            onclick: move |_| room.react(id, user.peek().id, toggle.clone(), !mine),
+            "{emoji} {count}"
+        }
+    }
+}
+
+/// How far a message of the user got, with a button to send it again if it failed.
+#[component]
+fn DeliveryState(room: Room, id: Stamp, delivery: Delivery) -> Element {
+    let (icon, label) = match &delivery {
+        Delivery::Pending if !CONNECTED() => (rsx! { Icon { width: 12, height: 12, icon: LdClock } }, t!("delivery-queued")),
+        Delivery::Pending => (rsx! { Icon { width: 12, height: 12, icon: LdClock } }, t!("delivery-pending")),
+        Delivery::Sent => (rsx! { Icon { width: 12, height: 12, icon: LdCheck } }, t!("delivery-sent")),
+        Delivery::Delivered => (rsx! { Icon { width: 12, height: 12, icon: LdCheckCheck } }, t!("delivery-delivered")),
+        Delivery::Failed(error) => return rsx! {
+            span {
+                class: "flex flex-row items-center gap-1 text-error opacity-100",
+                role: "alert",
+                title: "{error}",
+                Icon { width: 12, height: 12, icon: LdCircleAlert }
+                {t!("delivery-failed")}
+                button {
+                    class: "btn btn-xs btn-ghost",
+                    onclick: move |_| transport::retry(room, id),
+                    {t!("delivery-retry")}
+                }
             }
-            Key::ArrowDown if *idx + 1 < lines.len() => {
-                *idx += 1;
-                *cursor = 0;
+        },
+    };
+
+    rsx! {
+        span {
+            class: "text-xs",
+            title: "{label}",
+            aria_label: "{label}",
+            role: "img",
+            {icon}
+        }
+    }
+}
+
+/// The other members who have read a message, by name when there are only a few of them.
+#[component]
+fn SeenBy(readers: Vec<Uuid>) -> Element {
+    let text = if readers.len() > 3 {
+        t!("seen-by-count", count = readers.len())
+    } else {
+        let authors = AUTHORS.read();
+        let names: Vec<String> = readers.iter()
+            .map(|reader| authors.get(reader).map(|author| author.username.clone()).unwrap_or_else(|| t!("unknown-user")))
+            .collect();
+
+        t!("seen-by", names = names.join(", "))
+    };
+
+    rsx! {
+        span { class: "text-xs", "{text}" }
+    }
+}
+
+/// When a message was sent, relative to now or as an exact time depending on the settings. Only this component
+/// re-renders as the time passes, and only when the text changes.
+#[component]
+fn Timestamp(time: DateTime<Utc>) -> Element {
+    let settings = use_settings();
+    let text = use_memo(move || match settings.read().timestamps {
+        TimestampStyle::Relative => time_format::relative(time, NOW()),
+        TimestampStyle::Exact => time_format::absolute(time, NOW()),
+    });
+
+    rsx! {
+        time { 
+            class: "text-xs text-secondary opacity-50", 
+            datetime: "{time.to_rfc3339()}",
+            title: "{time_format::exact(time)}",
+            "{text}" 
+        }
+    }
+}
+
+/// The message composer of a room. Whatever is typed is kept as the room's [`Draft`] until it is sent, so leaving the
+/// room or restarting the app does not lose it.
+///
+/// The composer is a real `textarea` so that the platform does the text editing: IME composition, dead keys, soft
+/// keyboards and pasting all work the same way they do in any other text field.
+///
+/// Files dropped onto the composer, picked with the paperclip button or pasted as images are attached to the next
+/// message.
+#[component]
+pub fn ChatInput(room: Uuid, onsend: EventHandler<(String, Vec<Attachment>)>, onmounted: EventHandler<MountedEvent>) -> Element {
+    render_stats::record("ChatInput");
+
+    let mut input_text: Signal<String> = use_signal(|| DRAFTS.peek()
+        .get(&room)
+        .map(|draft| draft.text.clone())
+        .unwrap_or_default());
+    let mut composing = use_signal(|| false);
+    let mut input_error: Signal<Option<String>> = use_signal(|| None);
+    let mut pending: Signal<Vec<Attachment>> = use_signal(Vec::new);
+    // The text from before a long paste, to go back to if the paste is undone.
+    let mut long_paste: Signal<Option<String>> = use_signal(|| None);
+    // The message being edited, if the composer holds an edit instead of a new message.
+    let mut editing: Signal<Option<Stamp>> = use_signal(|| None);
+    let user = use_context::<Signal<Author>>();
+    let settings = use_settings();
+
+    use_effect(move || {
+        // An edit is not a new message, and editing only starts from an empty composer, so there is no draft to keep.
+        if editing().is_some() {
+            return;
+        }
+
+        let draft = Draft {
+            text: input_text(),
+        };
+
+        let unchanged = match DRAFTS.peek().get(&room) {
+            Some(saved) => saved == &draft,
+            None => draft.is_empty(),
+        };
+
+        if unchanged {
+            return;
+        }
+
+        let mut drafts = DRAFTS.write();
+
+        if draft.is_empty() {
+            drafts.remove(&room);
+        } else {
+            drafts.insert(room, draft);
+        }
+
+        storage::save_secret("drafts", &*drafts);
+    });
+
+    use_drop(move || typing::stopped(room, user.peek().id));
+
+    let suggestions = COMMANDS.read().suggestions(&CommandContext { room, user }, &input_text.read());
+
+    let mut submit = move || {
+        // Sending waits until a long paste has been kept or undone.
+        if long_paste.peek().is_some() {
+            return;
+        }
+
+        let text = input_text.peek().trim().to_string();
+
+        if text.is_empty() && pending.peek().is_empty() {
+            return;
+        }
+
+        if text.chars().count() > MAX_MESSAGE_LENGTH {
+            input_error.set(Some(t!("composer-too-long", max = MAX_MESSAGE_LENGTH)));
+            return;
+        }
+
+        if let Some(id) = editing.take() {
+            if let Some(target) = ROOMS.peek().get(&room).copied() {
+                if !text.is_empty() {
+                    target.edit(user.peek().id, id, text);
+                }
             }
-            Key::Home => {
-                *cursor = 0;
+
+            input_error.set(None);
+            input_text.set(String::new());
+            typing::stopped(room, user.peek().id);
+            return;
+        }
+
+        if commands::parse(&text).is_some() {
+            match COMMANDS.read().dispatch(&CommandContext { room, user }, &text) {
+                Ok(outcome) => {
+                    if let Outcome::Navigate(route) = outcome {
+                        navigator().push(route);
+                    }
+                }
+                Err(err) => {
+                    input_error.set(Some(err));
+                    return;
+                }
             }
-            Key::End => {
-                *cursor = lines[*idx].len();
+        } else if let Some(literal) = text.strip_prefix("//") {
+            onsend.call((format!("/{literal}"), pending.take()));
+        } else {
+            onsend.call((text, pending.take()));
+        }
+
+        input_error.set(None);
+        input_text.set(String::new());
+        typing::stopped(room, user.peek().id);
+    };
+
+    let mut attach = move |name: &str, mime: Option<&str>, bytes: &[u8]| {
+        match attachments::attach(name, mime, bytes) {
+            Ok(attachment) => pending.write().push(attachment),
+            Err(err) => input_error.set(Some(err)),
+        }
+    };
+
+    attachments::use_pasted_files(move |(name, mime, bytes)| attach(&name, Some(&mime), &bytes));
+
+    let attach_files = move |files: Option<Arc<dyn FileEngine>>| async move {
+        let Some(files) = files else {
+            return;
+        };
+
+        for name in files.files() {
+            if let Some(bytes) = files.read_file(&name).await {
+                // Platforms report the full path of dropped or picked files, only the file name is shared.
+                let file_name = name.rsplit(['/', '\\']).next().unwrap_or(&name);
+
+                attach(file_name, None, &bytes);
             }
-            _ => {}
         }
     };
 
-    let input_paste = move |evt: Event<ClipboardData>| async move {
-        evt.stop_propagation();
+    let mut cancel_edit = move || {
+        editing.set(None);
+        input_text.set(String::new());
+    };
+
+    let input_handler = move |evt: Event<KeyboardData>| {
+        // While an IME composition is active, Enter commits the composed text instead of sending the message.
+        if composing() || evt.is_composing() {
+            return;
+        }
 
-        let data = evt.data();
-        
-        if let Some(content) = data.downcast::<String>() {
-            let mut lines = input_lines.write();
-            let mut cursor = input_cursor.write();
-            let mut line_idx = input_line();
+        let shortcuts = settings.peek().shortcuts.clone();
 
-            // keep the tail of the current line
-            let rest = lines[line_idx].split_off(*cursor);
+        if editing.peek().is_some() && shortcuts.binding(Action::CloseDialog).matches(&evt) {
+            evt.prevent_default();
+            cancel_edit();
+            return;
+        }
 
-            // iterate over each chunk, splitting on '\n'
-            let mut iter = content.split('\n').peekable();
+        // Only an empty composer edits, so the key keeps moving the cursor in text that is being written.
+        if input_text.peek().is_empty() && pending.peek().is_empty() && shortcuts.binding(Action::EditLastMessage).matches(&evt) {
+            let author = user.peek().id;
+            let last = ROOMS.peek().get(&room).and_then(|room| room.messages.peek()
+                .values()
+                .rev()
+                .find(|message| message.author == author && message.kind == MessageKind::Text)
+                .map(|message| (message.id, message.content.clone())));
 
-            // first chunk: always append into current line
-            if let Some(first) = iter.next() {
-                lines[line_idx].push_str(first);
-                *cursor += first.len();
+            if let Some((id, content)) = last {
+                evt.prevent_default();
+                editing.set(Some(id));
+                input_text.set(content);
             }
 
-            // for all following chunks...
-            while let Some(chunk) = iter.next() {
-                if iter.peek().is_some() {
-                    // intermediate line: insert a new empty line
-                    lines.insert(line_idx + 1, chunk.to_string());
-                    line_idx += 1;
-                    input_line.set(line_idx);
-                    *cursor = chunk.len();
-                } else {
-                    // last chunk: create the new line and re-append the old remainder
-                    lines.insert(line_idx + 1, format!("{chunk}{rest}"));
-                    input_line.set(line_idx + 1);
-                    *cursor = chunk.len();
+            return;
+        }
+
+        match evt.key() {
+            Key::Enter => {
+                let modifiers = evt.modifiers();
+
+                // Ctrl+Enter and Cmd+Enter send on every platform. Plain Enter sends unless the setting asks for
+                // Ctrl+Enter or the send button, the other keys insert a new line, which the textarea does by itself.
+                let send = modifiers.ctrl() || modifiers.meta() || match settings.peek().send_key {
+                    SendKey::Enter => !modifiers.shift(),
+                    SendKey::CtrlEnter | SendKey::Button => false,
+                };
+
+                if send {
+                    evt.prevent_default();
+                    submit();
                 }
             }
+            Key::Tab => {
+                let completion = COMMANDS.read()
+                    .suggestions(&CommandContext { room, user }, &input_text.read())
+                    .into_iter()
+                    .next();
 
-            // if there was no '\n' at all, re-append the remainder
-            if content.find('\n').is_none() {
-                lines[line_idx].push_str(&rest);
+                if let Some(suggestion) = completion {
+                    evt.prevent_default();
+                    input_text.set(suggestion.completion);
+                }
             }
+            _ => {}
         }
     };
 
+    let room_name = ROOMS.peek().get(&room).map(|room| room.info.peek().name.clone()).unwrap_or_default();
+    let rows = input_text.read().split('\n').count().clamp(1, 8);
+    let length = input_text.read().chars().count();
+
     rsx! {
         div {
-            onresize: move |_| async move {
-                update_viewer().await;
-            },
-            onvisible: move |_| async move {
-                update_viewer().await;
-            },
-            onmounted: move |_| async move {
-                update_viewer().await;
-                focus_chat_input().await;
+            class: "relative flex flex-col items-start mt-4",
+            ondragover: move |evt| evt.prevent_default(),
+            ondrop: move |evt| async move {
+                evt.prevent_default();
+                attach_files(evt.files()).await;
             },
-            class: "flex flex-col w-full gap-4 p-2 h-full", 
-            div {
-                onmounted: move |evt| async move {
-                    message_viewer.set(Some(evt.data()));
-                    update_viewer().await;
-                },
-                onscroll: move |_| async move {
-                    update_viewer().await;
-                },
-                class: "flex flex-col card gap-8 p-4 pr-8 grow text-wrap overflow-y-auto justify-end",
-                if show_scroll() && last_message().is_some() {
-                    div {
-                        class: "flex flex-col btn btn-ghost hover:btn-outline fixed bottom-20 left-[50%] w-24 right-[50%]",
-                        button {  
-                            onclick: move |_| async move {
-                                scroll_to_last(true).await;
-                                focus_chat_input().await;
-                            },
-                            Icon {
-                                class: "stroke-slate-500 dark:stroke-slate-300 opacity-25 group-hover:opacity-100",
-                                width: 20,
-                                height: 20,
-                                icon: LdMoveDown,
+            if !suggestions.is_empty() {
+                ul {
+                    class: "menu absolute bottom-full mb-2 w-full max-h-64 flex-nowrap overflow-y-auto rounded-box bg-base-200 shadow",
+                    for suggestion in suggestions {
+                        li {
+                            button {
+                                class: "flex flex-row justify-between",
+                                onclick: move |_| input_text.set(suggestion.completion.clone()),
+                                span { class: "font-medium", "{suggestion.label}" }
+                                span { class: "text-xs opacity-50", "{suggestion.detail}" }
                             }
                         }
                     }
                 }
-                for message in messages {
-                    {message}
+            }
+            if let Some(err) = input_error() {
+                p {
+                    class: "px-4 pb-2 text-sm text-error",
+                    role: "alert",
+                    "{err}"
+                }
+            }
+            if editing().is_some() {
+                div {
+                    class: "flex flex-row items-center gap-2 px-4 pb-2 text-sm",
+                    span { {t!("composer-editing")} }
+                    button {
+                        class: "btn btn-xs btn-ghost",
+                        onclick: move |_| cancel_edit(),
+                        {t!("composer-cancel-edit")}
+                    }
+                }
+            }
+            if let Some(before) = long_paste() {
+                div {
+                    class: "flex flex-row flex-wrap items-center gap-2 px-4 pb-2 text-sm",
+                    span { {t!("composer-long-paste", count = length.saturating_sub(before.chars().count()))} }
+                    button {
+                        class: "btn btn-xs btn-accent",
+                        onclick: move |_| long_paste.set(None),
+                        {t!("composer-keep")}
+                    }
+                    button {
+                        class: "btn btn-xs btn-ghost",
+                        onclick: move |_| {
+                            input_text.set(before.clone());
+                            long_paste.set(None);
+                        },
+                        {t!("composer-undo")}
+                    }
+                }
+            }
+            if length > MAX_MESSAGE_LENGTH * 3 / 4 {
+                p {
+                    class: if length > MAX_MESSAGE_LENGTH { "self-end px-4 pb-2 text-xs text-error" } else { "self-end px-4 pb-2 text-xs opacity-50" },
+                    "{length}/{MAX_MESSAGE_LENGTH}"
                 }
+            }
+            if !pending.read().is_empty() {
                 div {
-                    class: "h-4",
-                    onmounted: move |evt| last_message.set(Some(evt.data())),
+                    class: "flex flex-row flex-wrap gap-2 px-4 pb-2",
+                    for (index, attachment) in pending().into_iter().enumerate() {
+                        div {
+                            key: "{attachment.blob.0}",
+                            class: "badge badge-outline gap-2",
+                            "{attachment.name} ({attachments::format_size(attachment.size)})"
+                            button {
+                                class: "cursor-pointer",
+                                aria_label: t!("remove-attachment", name = attachment.name.clone()),
+                                onclick: move |_| {
+                                    attachments::discard(&pending.write().remove(index));
+                                },
+                                Icon {
+                                    class: "stroke-current",
+                                    width: 12,
+                                    height: 12,
+                                    icon: LdX,
+                                }
+                            }
+                        }
+                    }
                 }
             }
             div {
-                class: "flex flex-col items-start mt-4",
+                class: "flex flex-row gap-2 w-full items-center",
                 label {
-                    tabindex: 0,
-                    autofocus: true,
-                    onkeydown: input_handler,
-                    onpaste: input_paste,
-                    onmounted: move |evt| chat_input.set(Some(evt.data())),
                     class: "flex flex-row gap-4 w-full group border-1 items-center border-accent outline-accent ring-accent rounded-2xl outline-0 p-4",
+                    button {
+                        class: "cursor-pointer",
+                        title: t!("send-message"),
+                        aria_label: t!("send-message"),
+                        onclick: move |evt| {
+                            evt.prevent_default();
+                            submit();
+                        },
+                        Icon {
+                            class: "stroke-slate-700 dark:stroke-slate-400 opacity-25 group-hover:opacity-100",
+                            width: 20,
+                            height: 20,
+                            icon: LdSendHorizontal,
+                        }
+                    }
+                    textarea {
+                        class: "grow resize-none bg-transparent outline-none",
+                        "data-composer": "true",
+                        aria_label: t!("composer-label", room = room_name.clone()),
+                        placeholder: t!("composer-label", room = room_name),
+                        autofocus: true,
+                        rows: "{rows}",
+                        value: "{input_text}",
+                        oninput: move |evt| {
+                            let value = evt.value();
+                            let inserted = value.chars().count().saturating_sub(input_text.peek().chars().count());
+
+                            if inserted >= LONG_PASTE_LENGTH && settings.peek().confirm_long_pastes && long_paste.peek().is_none() {
+                                long_paste.set(Some(input_text()));
+                            }
+
+                            if value.trim().is_empty() {
+                                typing::stopped(room, user.peek().id);
+                            } else {
+                                typing::typing(room, user.peek().id);
+                            }
+
+                            input_error.set(None);
+                            input_text.set(value);
+                        },
+                        onkeydown: input_handler,
+                        oncompositionstart: move |_| composing.set(true),
+                        oncompositionend: move |_| composing.set(false),
+                        onmounted: move |evt| onmounted.call(evt),
+                    }
+                }
+                label {
+                    class: "btn btn-ghost btn-circle focus-within:outline-2",
+                    title: t!("attach-files"),
+                    // Visually hidden but not `hidden`, so that the file picker can be reached with the keyboard.
+                    input {
+                        class: "sr-only",
+                        aria_label: t!("attach-files"),
+                        r#type: "file",
+                        multiple: true,
+                        onchange: move |evt| async move {
+                            attach_files(evt.files()).await;
+                        },
+                    }
                     Icon {
-                        class: "stroke-slate-700 dark:stroke-slate-400 opacity-25 group-hover:opacity-100",
+                        class: "stroke-slate-700 dark:stroke-slate-400",
                         width: 20,
                         height: 20,
-                        icon: LdSendHorizontal,
-                    }
-                    div {
-                        class: "flex flex-col",
-                        for (index, line) in input_lines().iter().enumerate() {
-                            ChatInputLine {
-                                index: index,
-                                line: line,
-                                input_line: input_line,
-                                input_cursor: input_cursor,
-                            }   
-                        }
+                        icon: LdPaperclip,
                     }
                 }
             }
@@ -360,39 +1193,201 @@ pub fn Chat(id: Uuid) -> Element {
     }
 }
 
+/// A card with the title, description and image of a linked page. `onhide` lets the author of the message turn the
+/// preview off. Unless `fetch` is set, the page is only fetched once the user asks for it.
 #[component]
-pub fn ChatInputLine(index: usize, line: String, mut input_line: Signal<usize>, input_cursor: Signal<usize>) -> Element {
-    if index == input_line() {
-        let pos = input_cursor();
-        let (before, after) = line.split_at(pos);
-        
-        rsx! {
-            p {
-                span { 
-                    "{before}" 
+fn LinkPreviewCard(url: String, fetch: bool, onhide: Option<EventHandler<()>>) -> Element {
+    let request_url = url.clone();
+
+    // Props are not signals, the effect runs again when they change through `use_reactive`.
+    use_effect(use_reactive!(|(request_url, fetch)| {
+        if fetch {
+            previews::request(&request_url);
+        }
+    }));
+
+    let state = PREVIEWS.read().get(&url).cloned();
+
+    if state.is_none() && !fetch {
+        let host = url::Url::parse(&url).ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+
+        return rsx! {
+            button {
+                class: "btn btn-ghost btn-xs mt-1",
+                onclick: move |_| previews::request(&url),
+                {t!("show-preview", host = host)}
+            }
+        };
+    }
+
+    let Some(PreviewState::Ready(preview)) = state else {
+        return rsx! {};
+    };
+
+    if preview.is_empty() {
+        return rsx! {};
+    }
+
+    rsx! {
+        div {
+            class: "relative flex flex-row gap-3 mt-2 p-2 max-w-md rounded-lg border-s-4 border-accent bg-base-100/10",
+            if let Some(image) = &preview.image {
+                img {
+                    class: "w-20 h-20 object-cover rounded",
+                    alt: "",
+                    src: "{image}",
                 }
-                span { 
-                    class: "animate-ping font-medium text-xl", 
-                    "|" 
+            }
+            div {
+                class: "flex flex-col gap-1 min-w-0",
+                if let Some(site_name) = &preview.site_name {
+                    span { class: "text-xs opacity-50", "{site_name}" }
                 }
-                span { 
-                    "{after}"
+                a {
+                    class: "link font-medium truncate",
+                    href: "{preview.url}",
+                    target: "_blank",
+                    {preview.title.clone().unwrap_or_else(|| preview.url.clone())}
+                }
+                if let Some(description) = &preview.description {
+                    p { class: "text-sm opacity-75 line-clamp-3", "{description}" }
+                }
+            }
+            if let Some(onhide) = onhide {
+                button {
+                    class: "absolute top-1 end-1 btn btn-ghost btn-xs btn-circle",
+                    title: t!("remove-preview"),
+                    aria_label: t!("remove-preview"),
+                    onclick: move |_| onhide.call(()),
+                    Icon {
+                        class: "stroke-current",
+                        width: 12,
+                        height: 12,
+                        icon: LdX,
+                    }
                 }
             }
         }
-    } else {
-        rsx! {
+    }
+}
+
+/// An attachment inside a message bubble: images are shown inline, other files as a download link.
+#[component]
+fn AttachmentView(attachment: Attachment) -> Element {
+    let url = use_hook(|| attachments::data_url(&attachment));
+    let size = attachments::format_size(attachment.size);
+
+    let Some(url) = url else {
+        return rsx! {
             p {
-                onclick: move |evt| {
-                    dbg!(evt);
+                class: "text-sm italic opacity-75",
+                {t!("attachment-unavailable", name = attachment.name.clone())}
+            }
+        }
+    };
 
-                    input_line.set(index);
-                    input_cursor.set(0);
-                },
-                span {
-                    "{line}"
+    rsx! {
+        div {
+            class: "flex flex-col gap-1 py-1",
+            if attachment.is_image() {
+                img {
+                    class: "max-w-64 max-h-64 rounded-lg",
+                    alt: "{attachment.name}",
+                    src: "{url}",
                 }
             }
+            a {
+                class: "link text-sm",
+                href: "{url}",
+                download: "{attachment.name}",
+                "{attachment.name} ({size})"
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc, time::Duration};
+
+    use dioxus::prelude::*;
+
+    use crate::crdt::Stamp;
+    use crate::history::{HistorySource, PageFuture, HISTORY, PAGE_SIZE};
+    use crate::previews::{FetchFuture, PreviewFetcher, FETCHER};
+    use crate::search::{MessageRef, FOCUS};
+    use crate::settings::Settings;
+    use crate::{room_id, testing, Message, MessageKind, Route, AUTHORS, ROOMS};
+
+    /// Counts the pages fetched from the history it serves.
+    struct Counting {
+        history: Rc<dyn HistorySource>,
+        fetches: Rc<Cell<usize>>,
+    }
+
+    impl HistorySource for Counting {
+        fn fetch(&self, room: uuid::Uuid, before: Option<Stamp>, limit: usize) -> PageFuture {
+            self.fetches.set(self.fetches.get() + 1);
+            self.history.fetch(room, before, limit)
+        }
+    }
+
+    #[test]
+    fn search_hits_in_older_pages_are_loaded_to() {
+        let mut app = testing::app(Route::Chat { id: room_id("Starwars") });
+        let room = room_id("Starwars");
+        let history = app.run(|| HISTORY.peek().clone());
+        let messages = app.block_on(history.fetch(room, None, 1000)).unwrap().messages;
+        let oldest = messages.iter().map(|message| message.id).min().unwrap();
+        let fetches = Rc::new(Cell::new(0));
+
+        app.run(|| {
+            *HISTORY.write() = Rc::new(Counting { history, fetches: fetches.clone() });
+            *FOCUS.write() = Some(MessageRef { room, id: oldest });
+        });
+        app.advance(Duration::from_secs(1));
+
+        // One page at a time, each of them once, until the oldest message is there.
+        assert_eq!(fetches.get(), messages.len().div_ceil(PAGE_SIZE));
+        app.run(|| assert!(ROOMS.peek()[&room].messages.peek().get(&oldest).is_some()));
+        assert!(app.html().contains("ring-accent"), "the search hit is not highlighted");
+    }
+
+    /// Serves the same page for every link, and counts the fetches.
+    struct Stub(Rc<Cell<usize>>);
+
+    impl PreviewFetcher for Stub {
+        fn fetch(&self, _url: &str) -> FetchFuture {
+            self.0.set(self.0.get() + 1);
+
+            Box::pin(async { Ok("<title>Sand</title>".to_string()) })
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn previews_follow_the_setting() {
+        let mut app = testing::app(Route::Chat { id: room_id("Starwars") });
+        let fetches = Rc::new(Cell::new(0));
+        let mut settings = app.run(|| {
+            *FETCHER.write() = Rc::new(Stub(fetches.clone()));
+
+            let someone = *AUTHORS.peek().keys().next().unwrap();
+            let message = Message::new(someone, "see https://example.com".into(), MessageKind::Text);
+            ROOMS.peek()[&room_id("Starwars")].post(message);
+
+            ScopeId::APP.consume_context::<Signal<Settings>>().unwrap()
+        });
+        app.advance(Duration::from_secs(1));
+
+        // The links of others wait to be asked for.
+        assert_eq!(fetches.get(), 0);
+
+        app.run(|| settings.write().auto_previews = true);
+        app.advance(Duration::from_secs(1));
+
+        assert_eq!(fetches.get(), 1);
+        assert!(app.html().contains("Sand"));
+    }
+}
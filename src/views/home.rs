@@ -1,24 +1,29 @@
 use dioxus::prelude::*;
 
-use crate::AUTHORS;
+use crate::components::PresenceDot;
+use crate::{presence, Route, AUTHORS};
 
 #[component]
 pub fn Home() -> Element {
     let authors = AUTHORS.read();
 
-    let authors_list = authors.iter()
-        .map(|(_, author)| (
+    let authors_list = authors.values()
+        .map(|author| (
             "chat chat-start",
+            author.id,
             author.username.as_str(),
-            author.avatar.as_ref()
-                .and_then(|url| Some(url.as_str())),
-            author.bio.as_ref()
-                .and_then(|bio| Some(bio.as_str()))
+            author.avatar.as_deref(),
+            author.bio.as_deref()
         ))
-        .map(|(side_class, name, avatar_url, bio)| {
+        .map(|(side_class, id, name, avatar_url, bio)| {
+            let status = presence::of(id).text;
+
             rsx! {
                 button {
                     class: "btn btn-outline btn-accent p-12",
+                    onclick: move |_| {
+                        navigator().push(Route::Profile { id });
+                    },
                     div { 
                         class: "{side_class} gap-2",
                         div { 
@@ -32,11 +37,18 @@ pub fn Home() -> Element {
                                     }
                                 }
                             }
+                            PresenceDot { user: id }
                         }
                         div { 
                             class: "chat-header",
                             "{name}",
                         }
+                        if let Some(status) = status {
+                            div {
+                                class: "!text-xs",
+                                "{status}"
+                            }
+                        }
                         if let Some(bio) = bio {
                             div {
                                 class: "!text-xs opacity-50",
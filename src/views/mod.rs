@@ -16,3 +16,162 @@ pub use chat::Chat;
 
 mod navbar;
 pub use navbar::Navbar;
+
+mod profile;
+pub use profile::Profile;
+
+mod search;
+pub use search::{Search, SearchInput};
+
+mod settings;
+pub use settings::Settings;
+
+mod shortcuts;
+pub use shortcuts::{Dialog, KeyboardShortcuts, DIALOG};
+
+/// Checks on the rendered HTML that what screen readers rely on is there: every control has a name, and the landmarks,
+/// live region and dialogs have their roles and labels.
+#[cfg(test)]
+mod tests {
+    use super::{Dialog, DIALOG};
+    use crate::{room_id, testing, Route};
+
+    /// The elements that never have children.
+    const VOID: [&str; 6] = ["br", "hr", "img", "input", "link", "meta"];
+
+    /// An element whose end tag has not been reached yet.
+    struct Open<'a> {
+        tag: &'a str,
+        attrs: &'a str,
+        text: String,
+        fields: usize,
+    }
+
+    /// The controls in `html` that have no accessible name: buttons and links without text or a label, and form fields
+    /// without `aria-label` that are not inside a `<label>` with text.
+    fn unlabelled(html: &str) -> Vec<String> {
+        let mut stack: Vec<Open> = Vec::new();
+        let mut found = Vec::new();
+        let mut rest = html;
+
+        let labelled = |attrs: &str| attrs.contains("aria-label=") || attrs.contains("aria-labelledby=");
+
+        while let Some(start) = rest.find('<') {
+            for open in &mut stack {
+                open.text.push_str(rest[..start].trim());
+            }
+
+            // The end of the tag, skipping `>` in attribute values.
+            let mut quoted = false;
+            let end = start + rest[start..].char_indices()
+                .find(|&(_, c)| {
+                    quoted ^= c == '"';
+                    c == '>' && !quoted
+                })
+                .map(|(index, _)| index)
+                .expect("unterminated tag");
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+
+            if tag.starts_with('/') {
+                let open = stack.pop().expect("end tag without a start tag");
+                let control = matches!(open.tag, "a" | "button") || open.attrs.contains(r#"role="button""#);
+
+                let named = match open.tag {
+                    "label" => open.fields == 0 || !open.text.is_empty(),
+                    "select" | "textarea" => labelled(open.attrs) || in_label(&mut stack),
+                    _ if control => labelled(open.attrs) || !open.text.is_empty(),
+                    _ => true,
+                };
+
+                if !named {
+                    found.push(format!("<{} {}>", open.tag, open.attrs));
+                }
+
+                continue;
+            }
+
+            let (name, attrs) = tag.trim_end_matches('/').split_once(' ').unwrap_or((tag, ""));
+
+            if name == "input" && !labelled(attrs) && !attrs.contains(r#"type="hidden""#) && !in_label(&mut stack) {
+                found.push(format!("<{name} {attrs}>"));
+            }
+
+            if !tag.ends_with('/') && !VOID.contains(&name) {
+                stack.push(Open { tag: name, attrs, text: String::new(), fields: 0 });
+            }
+        }
+
+        found
+    }
+
+    /// Whether a form field whose start tag was just read is inside a label, which then has to have text.
+    fn in_label(stack: &mut [Open]) -> bool {
+        match stack.iter_mut().rev().find(|open| open.tag == "label") {
+            Some(label) => {
+                label.fields += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[test]
+    fn chat_is_labelled() {
+        let app = testing::app(Route::Chat { id: room_id("Starwars") });
+        let html = app.html();
+
+        assert_eq!(unlabelled(&html), Vec::<String>::new());
+        assert!(html.contains(r##"href="#main""##), "no link to skip to the content");
+        assert!(html.contains(r#"<main id="main""#));
+        assert!(html.contains("<nav ") && html.contains(r#"aria-label="Rooms""#));
+        assert!(html.contains(r#"role="status" aria-live="polite""#), "new messages are not announced");
+        assert!(html.contains(r#"role="feed" aria-label="Messages in #Starwars""#));
+        assert!(html.contains(r#"<textarea"#) && html.contains(r#"aria-label="Message #Starwars""#));
+    }
+
+    #[test]
+    fn settings_are_labelled() {
+        let app = testing::app(Route::Settings {});
+        let html = app.html();
+
+        assert_eq!(unlabelled(&html), Vec::<String>::new());
+        assert!(html.contains(r#"tabindex=-1>Settings</h2>"#), "the heading cannot take the focus");
+    }
+
+    #[test]
+    fn dialogs_are_labelled() {
+        let mut app = testing::app(Route::Chat { id: room_id("Starwars") });
+
+        for (dialog, label) in [(Dialog::QuickSwitcher, "Switch to a room"), (Dialog::Help, "Keyboard shortcuts")] {
+            app.run(|| *DIALOG.write() = Some(dialog));
+            app.render();
+            let html = app.html();
+
+            assert_eq!(unlabelled(&html), Vec::<String>::new(), "in {dialog:?}");
+            assert!(html.contains(&format!(r#"aria-label="{label}""#)), "{dialog:?} has no label");
+            assert_eq!(html.matches("<dialog").count(), 1);
+
+            if dialog == Dialog::QuickSwitcher {
+                assert!(html.contains(r#"role="combobox" aria-controls="quick-switcher-rooms""#));
+                assert!(html.contains(r#"<ul id="quick-switcher-rooms""#) && html.contains(r#"role="listbox""#));
+                assert!(html.contains(r#"role="option" aria-selected=true"#));
+            }
+        }
+    }
+
+    #[test]
+    fn unlabelled_controls_are_found() {
+        let html = concat!(
+            r#"<div><button aria-label="Send"><svg></svg></button><button>Send</button><button><svg></svg></button>"#,
+            r#"<label>Name<input type="text"/></label><label><select></select></label><textarea></textarea>"#,
+            r#"<input aria-label="Search" title="a > b"/></div>"#,
+        );
+
+        assert_eq!(unlabelled(html), [
+            "<button >",
+            "<label >",
+            "<textarea >",
+        ]);
+    }
+}
@@ -1,9 +1,17 @@
-use crate::{Route, ROOMS};
+use crate::i18n::{t, LANGUAGE};
+use crate::presence::{self, ClearAfter, Status, MAX_STATUS_LENGTH};
+use crate::{notifications, push, time_format};
+use crate::shortcuts::Action;
+use crate::settings::use_settings;
+use crate::views::{Dialog, KeyboardShortcuts, SearchInput, DIALOG};
+use crate::{render_stats, Author, Room, Route, DRAFTS, ROOMS};
+use chrono::Utc;
 use dioxus::prelude::*;
 
 use dioxus_free_icons::icons::fa_brands_icons::FaRust;
-use dioxus_free_icons::icons::ld_icons::{LdHash, LdSettings2};
+use dioxus_free_icons::icons::ld_icons::{LdHash, LdKeyboard, LdPencil, LdSettings2};
 use dioxus_free_icons::Icon;
+use uuid::Uuid;
 
 /// The Navbar component that will be rendered on all pages of our app since every page is under the layout.
 ///
@@ -12,107 +20,249 @@ use dioxus_free_icons::Icon;
 /// routes will be rendered under the outlet inside this component
 #[component]
 pub fn Navbar() -> Element {
-    let mut open_settings = use_signal(|| false);
+    render_stats::record("Navbar");
 
-    let rooms = ROOMS.read();
+    let user = use_context::<Signal<Author>>();
+    let user_id = user.read().id;
+    // Only the map of rooms is read here, each room link subscribes to its own room.
+    let rooms: Vec<Room> = ROOMS.read().values().copied().collect();
+    let language = LANGUAGE();
+    let settings = use_settings();
+    notifications::use_notifications();
+    notifications::use_unread_badge();
+    push::use_push();
 
-    let rooms = rooms.iter().map(|(id, room)| {
-        rsx! {
+    let help = settings.read().shortcuts.binding(Action::ShowHelp);
+
+    rsx! {
+        // The direction of the language flips the layout, including which side chat bubbles are on.
+        div {
+            class: "contents",
+            dir: language.dir(),
+            lang: language.tag(),
+            a {
+                class: "sr-only focus:not-sr-only focus:absolute focus:z-10 focus:p-2 focus:bg-base-100",
+                href: "#main",
+                {t!("skip-to-content")}
+            }
             div {
-                class: "flex flex-row group items-center gap-2 p-2 justify-between",
-                Link {
-                    to: Route::Chat { id: *id },
-                    class: "flex flex-row group items-center rounded-md hover:bg-secondary w-full p-2",
+                class: "flex flex-row w-full  m-0 h-screen max-h-screen",
+                div {
+                    class: "flex flex-col items-start p-2 gap-4 min-w-[200px] max-w-1/4",
+                    SearchInput { value: String::new() }
+                    nav {
+                        class: "flex flex-col gap-2 p-2 grow overflow-auto border-e-1 border-slate-800 dark:border-slate-500",
+                        aria_label: t!("rooms"),
+                        for room in rooms {
+                            RoomLink {
+                                key: "{room.id}",
+                                room: room,
+                                user: user_id,
+                            }
+                        }
+                    }
                     div {
-                        class: "flex flex-row items-center gap-2",
-                        Icon {
-                            class: "stroke-slate-800 dark:stroke-slate-500 opacity-50",
-                            width: 18,
-                            height: 18,
-                            icon: LdHash,
+                        class: "flex flex-col w-full",
+                        StatusMenu { user: user_id }
+                        div {
+                            class: "flex flex-row justify-between items-center gap-2 p-2",
+                            button {
+                                class: "btn btn-md btn-ghost btn-square",
+                                title: t!("shortcuts-with-key", key = help.to_string()),
+                                aria_label: t!("shortcuts"),
+                                aria_keyshortcuts: "{help}",
+                                onclick: move |_| *DIALOG.write() = Some(Dialog::Help),
+                                Icon {
+                                    class: "stroke-slate-700 dark:stroke-slate-400",
+                                    width: 24,
+                                    height: 24,
+                                    icon: LdKeyboard,
+                                }
+                            }
+                            button {
+                                class: "flex flex-col btn btn-md btn-ghost hover:btn-outline btn-accent grow",
+                                title: t!("settings"),
+                                aria_label: t!("settings"),
+                                onclick: move |_| {
+                                    navigator().push(Route::Settings {});
+                                },
+                                Icon {
+                                    class: "fill-slate-700 dark:fill-slate-400",
+                                    width: 30,
+                                    height: 30,
+                                    icon: FaRust,
+                                }
+                            }
                         }
-                        "{room.name.as_str()}"
-                    },
-                },
-                button {
-                    class: "flex flex-row opacity-0 group-hover:opacity-100 btn btn-xs btn-ghost hover:btn-outline group",
-                    onclick: move |evt| {
-                        evt.prevent_default();
-                        open_settings.set(true);
-                    },
-                    Icon {
-                        class: "stroke-slate-700 dark:stroke-slate-400 opacity-25 group-hover:opacity-100",
-                        width: 16,
-                        height: 16,
-                        icon: LdSettings2,
                     }
                 }
-            }
+                main {
+                    id: "main",
+                    class: "flex flex-col border-red-500 p-2 grow overflow-auto",
+                    Outlet::<Route> {}
+                }
+                KeyboardShortcuts {}
+            }        
         }
-    });
+    }
+}
+
+/// A room in the sidebar, if `user` is a member of it. Re-renders only when the room's info, unread count or draft
+/// state changes.
+#[component]
+fn RoomLink(room: Room, user: Uuid) -> Element {
+    render_stats::record("RoomLink");
+
+    let id = room.id;
+    let member = use_memo(move || room.info.read().members.contains(&user));
+    let name = use_memo(move || room.info.read().name.clone());
+    let has_draft = use_memo(move || DRAFTS.read().contains_key(&id));
+    let unread = room.unread;
+
+    if !member() {
+        return rsx! {};
+    }
 
     rsx! {
-        dialog {
-            open: open_settings,
-            class: "modal",
-            div { 
-                class: "modal-box",
+        div {
+            class: "flex flex-row group items-center gap-2 p-2 justify-between",
+            Link {
+                to: Route::Chat { id },
+                class: "flex flex-row group items-center rounded-md hover:bg-secondary w-full p-2",
                 div {
-                    class: "absolute top-4 right-4",
-                    button {
-                        onclick: move |_| open_settings.set(false),
-                        class: "btn dark:text-white", 
-                        "X"
+                    class: "flex flex-row items-center gap-2",
+                    Icon {
+                        class: "stroke-slate-800 dark:stroke-slate-500 opacity-50",
+                        width: 18,
+                        height: 18,
+                        icon: LdHash,
                     }
-                }
-                h3 { class: "text-lg font-bold", "Hello!" }
-                p { class: "py-4", "Press ESC key or click outside to close" }
-                button {
-                    onclick: move |_| open_settings.set(false),
-                    class: "btn",
-                    "Close"
-                }
-            }
-            form {
-                onsubmit: move |_| open_settings.set(false),
-                method: "dialog",
-                class: "modal-backdrop",
-                button {
-                    class: "w-full h-full",    
+                    "{name}"
+                    if has_draft() {
+                        Icon {
+                            class: "stroke-slate-800 dark:stroke-slate-500 opacity-50",
+                            width: 14,
+                            height: 14,
+                            title: t!("unsent-draft"),
+                            icon: LdPencil,
+                        }
+                    }
+                    if unread() > 0 {
+                        span {
+                            class: "badge badge-sm badge-accent",
+                            title: t!("unread-messages", count = unread()),
+                            aria_label: t!("unread-messages", count = unread()),
+                            "{unread}"
+                        }
+                    }
+                },
+            },
+            button {
+                class: "flex flex-row opacity-0 group-hover:opacity-100 focus:opacity-100 btn btn-xs btn-ghost hover:btn-outline group",
+                title: t!("settings"),
+                aria_label: t!("settings"),
+                onclick: move |evt| {
+                    evt.prevent_default();
+                    navigator().push(Route::Settings {});
+                },
+                Icon {
+                    class: "stroke-slate-700 dark:stroke-slate-400 opacity-25 group-hover:opacity-100",
+                    width: 16,
+                    height: 16,
+                    icon: LdSettings2,
                 }
             }
         }
+    }
+}
+
+/// The user's own status, with a menu to pick another one and to set a custom status.
+#[component]
+fn StatusMenu(user: Uuid) -> Element {
+    let mut settings = use_settings();
+    let presence = use_memo(move || presence::of(user));
+    let chosen = settings.read().status;
+    let text = settings.read().status_text.clone();
+    let until = settings.read().status_until;
+    let shown = presence.read().status;
+
+    rsx! {
         div {
-            class: "flex flex-row w-full  m-0 h-screen max-h-screen",
+            class: "dropdown dropdown-top w-full px-2",
             div {
-                class: "flex flex-col items-start p-2 gap-4 min-w-[200px] max-w-1/4",
-                div {
-                    class: "flex flex-col gap-2 p-2 grow overflow-auto border-r-1 border-slate-800 dark:border-slate-500",
-                    for room in rooms {
-                        {room}
+                class: "btn btn-ghost btn-sm w-full justify-start",
+                tabindex: 0,
+                role: "button",
+                aria_label: t!("status-change"),
+                span { class: "w-3 h-3 rounded-full shrink-0 {shown.color()}" }
+                span {
+                    class: "truncate",
+                    if text.is_empty() {
+                        {t!(shown.label())}
+                    } else {
+                        "{text}"
                     }
                 }
-                div {
-                    class: "flex flex-col w-full",
-                    div {
-                        class: "flex flex-row justify-between items-center p-2",
-                        button {
-                            class: "flex flex-col btn btn-md btn-ghost hover:btn-outline btn-accent w-full",
-                            onclick: move |_| open_settings.set(true),
-                            Icon {
-                                class: "fill-slate-700 dark:fill-slate-400",
-                                width: 30,
-                                height: 30,
-                                icon: FaRust,
+            }
+            div {
+                class: "dropdown-content z-10 flex flex-col gap-1 p-2 shadow bg-base-200 rounded-box w-64",
+                tabindex: 0,
+                for status in Status::ALL {
+                    button {
+                        class: if status == chosen { "btn btn-sm btn-active justify-start" } else { "btn btn-sm btn-ghost justify-start" },
+                        aria_pressed: status == chosen,
+                        onclick: move |_| settings.write().status = status,
+                        span { class: "w-3 h-3 rounded-full {status.color()}" }
+                        if status == Status::Offline {
+                            {t!("status-appear-offline")}
+                        } else {
+                            {t!(status.label())}
+                        }
+                    }
+                }
+                input {
+                    class: "input input-sm w-full",
+                    aria_label: t!("status-text"),
+                    placeholder: t!("status-text"),
+                    maxlength: MAX_STATUS_LENGTH as i64,
+                    value: "{text}",
+                    oninput: move |evt| settings.write().status_text = evt.value(),
+                }
+                if !text.is_empty() {
+                    select {
+                        class: "select w-full",
+                        aria_label: t!("status-clear-after"),
+                        onchange: move |evt| {
+                            if let Some(after) = evt.value().parse().ok().and_then(|i: usize| ClearAfter::ALL.get(i)) {
+                                settings.write().status_until = after.until(Utc::now());
+                            }
+                        },
+                        if let Some(until) = until {
+                            option {
+                                selected: true,
+                                disabled: true,
+                                {t!("status-until", time = time_format::absolute(until, Utc::now()))}
                             }
                         }
+                        for (i, after) in ClearAfter::ALL.into_iter().enumerate() {
+                            option {
+                                value: "{i}",
+                                selected: until.is_none() && after == ClearAfter::Never,
+                                {t!(after.label())}
+                            }
+                        }
+                    }
+                    button {
+                        class: "btn btn-xs btn-ghost self-end",
+                        onclick: move |_| {
+                            let mut settings = settings.write();
+                            settings.status_text.clear();
+                            settings.status_until = None;
+                        },
+                        {t!("status-clear")}
                     }
                 }
             }
-            div {
-                class: "flex flex-col border-red-500 p-2 grow overflow-auto",
-                Outlet::<Route> {}
-            }
-        }        
+        }
     }
 }
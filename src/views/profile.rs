@@ -0,0 +1,135 @@
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::components::PresenceDot;
+use crate::crypto::{self, Device, Trust, DEVICE};
+use crate::i18n::t;
+use crate::{presence, Author, AUTHORS};
+
+/// A user, with the devices they have and how far the user trusts each of them. Devices are verified here by
+/// comparing fingerprints.
+#[component]
+pub fn Profile(id: Uuid) -> Element {
+    let user = use_context::<Signal<Author>>();
+
+    let author = if user.read().id == id {
+        Some(user.read().clone())
+    } else {
+        AUTHORS.read().get(&id).cloned()
+    };
+    let name = author.as_ref().map_or_else(|| t!("unknown-user"), |author| author.username.clone());
+    let avatar = author.as_ref().and_then(|author| author.avatar.clone());
+    let bio = author.as_ref().and_then(|author| author.bio.clone());
+    let status = presence::of(id).text;
+
+    let devices = crypto::devices(id);
+
+    rsx! {
+        div {
+            class: "flex flex-col gap-6 p-4 max-w-2xl",
+            div {
+                class: "flex flex-row items-center gap-4",
+                div {
+                    class: "avatar relative",
+                    div {
+                        class: "w-16 rounded-full",
+                        if let Some(avatar) = avatar {
+                            img { alt: "{name}", src: "{avatar}" }
+                        }
+                    }
+                    PresenceDot { user: id }
+                }
+                div {
+                    class: "flex flex-col gap-1",
+                    h2 { class: "text-xl font-bold", "{name}" }
+                    if let Some(status) = status {
+                        p { class: "text-sm", "{status}" }
+                    }
+                    if let Some(bio) = bio {
+                        p { class: "text-sm opacity-50", "{bio}" }
+                    }
+                }
+            }
+            section {
+                class: "flex flex-col gap-3",
+                h3 { class: "text-lg font-semibold", {t!("profile-devices")} }
+                p { class: "text-sm opacity-70", {t!("profile-verify-help")} }
+                if devices.is_empty() {
+                    p { class: "text-sm opacity-50", {t!("profile-no-devices")} }
+                }
+                for device in devices {
+                    DeviceCard { key: "{device.id}", device }
+                }
+            }
+        }
+    }
+}
+
+/// A device with its fingerprint, and buttons to verify it unless it is this one.
+#[component]
+fn DeviceCard(device: Device) -> Element {
+    let own = device.id == DEVICE.read().device;
+    let trust = crypto::trust(&device);
+    let fingerprint = device.fingerprint();
+    let short_id: String = device.id.to_string().chars().take(8).collect();
+
+    let (badge_class, badge) = match trust {
+        Trust::Verified => ("badge-success", t!("trust-verified")),
+        Trust::Unverified => ("badge-ghost", t!("trust-unverified")),
+        Trust::Changed => ("badge-warning", t!("trust-changed")),
+    };
+
+    let verified = device.clone();
+    let unverified = device.clone();
+
+    rsx! {
+        div {
+            class: "flex flex-col gap-2 p-3 rounded-md border border-base-300",
+            div {
+                class: "flex flex-row items-center gap-2",
+                span {
+                    class: "font-medium",
+                    if own {
+                        {t!("profile-this-device")}
+                    } else {
+                        {t!("profile-device", id = short_id)}
+                    }
+                }
+                if !own {
+                    span { class: "badge badge-sm {badge_class}", "{badge}" }
+                }
+            }
+            code {
+                class: "font-mono text-lg tracking-wider",
+                aria_label: t!("profile-fingerprint"),
+                "{fingerprint}"
+            }
+            if trust == Trust::Changed {
+                div {
+                    class: "alert alert-warning text-sm",
+                    role: "alert",
+                    {t!("profile-key-changed")}
+                }
+            }
+            if !own {
+                div {
+                    class: "flex flex-row gap-2",
+                    if trust != Trust::Verified {
+                        button {
+                            class: "btn btn-sm btn-primary",
+                            onclick: move |_| crypto::set_verified(&verified, true),
+                            {t!("profile-verify")}
+                        }
+                    }
+                    if trust != Trust::Unverified {
+                        button {
+                            class: "btn btn-sm btn-ghost",
+                            onclick: move |_| crypto::set_verified(&unverified, false),
+                            {t!("profile-unverify")}
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
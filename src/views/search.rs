@@ -0,0 +1,103 @@
+use dioxus::prelude::*;
+
+use crate::i18n::t;
+use crate::search::{Query, FOCUS, SEARCH};
+use crate::time_format;
+use crate::{Author, Route, AUTHORS, ROOMS};
+
+/// At most this many results are shown, the newest ones.
+const MAX_RESULTS: usize = 100;
+
+/// The search results for `query`. Picking a result opens its room scrolled to the message.
+#[component]
+pub fn Search(query: String) -> Element {
+    let user = use_context::<Signal<Author>>();
+
+    let rooms = ROOMS.read();
+    let authors = AUTHORS.read();
+    let local_user = user.read();
+
+    let names: Vec<_> = authors.values()
+        .chain(std::iter::once(&*local_user))
+        .map(|author| (author.id, author.username.clone()))
+        .collect();
+
+    // Results come from the index rather than the rooms, which only hold the pages of history loaded so far.
+    let index = SEARCH.read();
+    let results: Vec<_> = index.search(&Query::parse(&query, &names, &rooms))
+        .into_iter()
+        .filter_map(|key| Some((key, rooms.get(&key.room)?, index.document(&key)?)))
+        .collect();
+    let count = results.len();
+    let shown = count.min(MAX_RESULTS);
+
+    let results = results.into_iter().take(MAX_RESULTS).map(|(key, room, document)| {
+        let room_name = room.info.read().name.clone();
+
+        let author = names.iter()
+            .find(|(id, _)| *id == document.author)
+            .map(|(_, name)| name.as_str())
+            .map_or_else(|| t!("unknown-user"), str::to_string);
+
+        let sent = time_format::exact(document.sent);
+        let snippet: String = document.content.chars().take(200).collect();
+
+        rsx! {
+            button {
+                key: "{key.room}-{key.id:?}",
+                class: "flex flex-col items-start gap-1 p-3 rounded-md text-left hover:bg-secondary/20",
+                onclick: move |_| {
+                    *FOCUS.write() = Some(key);
+                    navigator().push(Route::Chat { id: key.room });
+                },
+                div {
+                    class: "flex flex-row gap-2 text-xs opacity-50",
+                    span { "#{room_name}" }
+                    span { "{author}" }
+                    time { "{sent}" }
+                }
+                p { "{snippet}" }
+            }
+        }
+    });
+
+    rsx! {
+        div {
+            class: "flex flex-col gap-2 p-4",
+            SearchInput { value: query.clone() }
+            p {
+                class: "text-sm opacity-50",
+                {t!("search-results", count = count)}
+                if shown < count {
+                    " "
+                    {t!("search-showing", shown = shown)}
+                }
+            }
+            for result in results {
+                {result}
+            }
+        }
+    }
+}
+
+/// A search box that opens [`Route::Search`] for what was typed when Enter is pressed.
+#[component]
+pub fn SearchInput(value: String) -> Element {
+    let mut text = use_signal(|| value.clone());
+
+    rsx! {
+        input {
+            class: "input input-sm w-full",
+            r#type: "search",
+            aria_label: t!("search-label"),
+            placeholder: t!("search-placeholder"),
+            value: "{text}",
+            oninput: move |evt| text.set(evt.value()),
+            onkeydown: move |evt| {
+                if evt.key() == Key::Enter && !text.read().trim().is_empty() {
+                    navigator().push(Route::Search { query: text() });
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,526 @@
+use std::fmt::Debug;
+
+use dioxus::prelude::*;
+
+use crate::i18n::{t, Language};
+use crate::notifications::Level;
+use crate::previews;
+use crate::push::{self, Permission};
+use crate::settings::{use_settings, FontSize, SendKey, Settings as Preferences, TimestampStyle};
+use crate::shortcuts::{Action, Binding};
+use crate::theme::{CustomTheme, Theme};
+use crate::time_format::Clock;
+use crate::{Author, ROOMS};
+use chrono::NaiveTime;
+use uuid::Uuid;
+
+/// The application settings, grouped into sections. Changes apply and are saved right away.
+#[component]
+pub fn Settings() -> Element {
+    let mut settings = use_settings();
+    let current = settings();
+
+    let themes: Vec<Theme> = Theme::BUILT_IN.into_iter()
+        .chain(current.custom_themes.iter().map(|theme| Theme::Custom(theme.id)))
+        .collect();
+    let theme_options = options(&themes, |theme| match theme {
+        Theme::System => t!("theme-system"),
+        Theme::Light => t!("theme-light"),
+        Theme::Dark => t!("theme-dark"),
+        Theme::Custom(id) => current.custom_themes.iter()
+            .find(|custom| custom.id == id)
+            .map(|custom| custom.name.clone())
+            .unwrap_or_default(),
+    });
+
+    let user_id = use_context::<Signal<Author>>().read().id;
+    let rooms: Vec<(Uuid, String)> = ROOMS.read().values()
+        .filter(|room| room.info.read().members.contains(&user_id))
+        .map(|room| (room.id, room.info.read().name.clone()))
+        .collect();
+    let level_options = options(&Level::ALL, |level| t!(match level {
+        Level::Default => "level-default",
+        Level::All => "level-all",
+        Level::Mentions => "level-mentions",
+        Level::Muted => "level-muted",
+    }));
+
+    let sections = [
+        ("appearance", t!("settings-appearance")),
+        ("composer", t!("settings-composer")),
+        ("notifications", t!("settings-notifications")),
+        ("language-time", t!("settings-language-time")),
+        ("shortcuts", t!("shortcuts")),
+    ];
+
+    rsx! {
+        div {
+            class: "flex flex-col gap-6 p-4 max-w-2xl",
+            // The heading takes the focus when the page opens, so that screen readers start reading from it.
+            h2 {
+                class: "text-xl font-bold outline-none",
+                tabindex: -1,
+                onmounted: move |evt| async move {
+                    let _ = evt.data().set_focus(true).await;
+                },
+                {t!("settings")}
+            }
+            nav {
+                class: "flex flex-row flex-wrap gap-4",
+                for (id, title) in sections {
+                    a { class: "link link-hover", href: "#{id}", "{title}" }
+                }
+            }
+            Section {
+                id: "appearance",
+                title: t!("settings-appearance"),
+                SelectField {
+                    label: t!("settings-theme"),
+                    value: key(current.theme),
+                    options: theme_options,
+                    onchange: move |value: String| {
+                        if let Some(theme) = parse(&themes, &value) {
+                            settings.write().theme = theme;
+                        }
+                    },
+                }
+                div {
+                    class: "flex flex-col gap-2",
+                    span { class: "text-sm", {t!("theme-custom-themes")} }
+                    for custom in current.custom_themes.iter().cloned() {
+                        CustomThemeEditor {
+                            key: "{custom.id}",
+                            theme: custom,
+                        }
+                    }
+                    button {
+                        class: "btn btn-sm btn-outline self-start",
+                        onclick: move |_| {
+                            let custom = CustomTheme::new(t!("theme-default-name"));
+                            let mut settings = settings.write();
+
+                            settings.theme = Theme::Custom(custom.id);
+                            settings.custom_themes.push(custom);
+                        },
+                        {t!("theme-new")}
+                    }
+                }
+                SelectField {
+                    label: t!("settings-font-size"),
+                    value: key(current.font_size),
+                    options: options(&FontSize::ALL, |size| t!(match size {
+                        FontSize::Small => "font-size-small",
+                        FontSize::Medium => "font-size-medium",
+                        FontSize::Large => "font-size-large",
+                    })),
+                    onchange: move |value: String| {
+                        if let Some(size) = parse(&FontSize::ALL, &value) {
+                            settings.write().font_size = size;
+                        }
+                    },
+                }
+                ToggleField {
+                    label: t!("settings-compact"),
+                    checked: current.compact,
+                    onchange: move |checked| settings.write().compact = checked,
+                }
+            }
+            Section {
+                id: "composer",
+                title: t!("settings-composer"),
+                SelectField {
+                    label: t!("settings-send-key"),
+                    value: key(current.send_key),
+                    options: options(&SendKey::ALL, |key| t!(match key {
+                        SendKey::Enter => "send-key-enter",
+                        SendKey::CtrlEnter => "send-key-ctrl-enter",
+                        SendKey::Button => "send-key-button",
+                    })),
+                    onchange: move |value: String| {
+                        if let Some(send_key) = parse(&SendKey::ALL, &value) {
+                            settings.write().send_key = send_key;
+                        }
+                    },
+                }
+                ToggleField {
+                    label: t!("settings-confirm-paste"),
+                    checked: current.confirm_long_pastes,
+                    onchange: move |checked| settings.write().confirm_long_pastes = checked,
+                }
+                ToggleField {
+                    label: t!("settings-read-receipts"),
+                    checked: current.read_receipts,
+                    onchange: move |checked| settings.write().read_receipts = checked,
+                }
+                if previews::SUPPORTED {
+                    ToggleField {
+                        label: t!("settings-auto-previews"),
+                        checked: current.auto_previews,
+                        onchange: move |checked| settings.write().auto_previews = checked,
+                    }
+                }
+            }
+            Section {
+                id: "notifications",
+                title: t!("settings-notifications"),
+                ToggleField {
+                    label: t!("settings-notifications-enabled"),
+                    checked: current.notifications.enabled,
+                    onchange: move |checked| settings.write().notifications.enabled = checked,
+                }
+                BrowserNotifications {}
+                ToggleField {
+                    label: t!("settings-notifications-sound"),
+                    checked: current.notifications.sound,
+                    disabled: !current.notifications.enabled,
+                    onchange: move |checked| settings.write().notifications.sound = checked,
+                }
+                ToggleField {
+                    label: t!("settings-notifications-mentions"),
+                    checked: current.notifications.mentions_only,
+                    disabled: !current.notifications.enabled,
+                    onchange: move |checked| settings.write().notifications.mentions_only = checked,
+                }
+                ToggleField {
+                    label: t!("settings-quiet-hours"),
+                    checked: current.notifications.quiet_hours.enabled,
+                    disabled: !current.notifications.enabled,
+                    onchange: move |checked| settings.write().notifications.quiet_hours.enabled = checked,
+                }
+                div {
+                    class: "flex flex-row flex-wrap gap-4",
+                    TimeField {
+                        label: t!("settings-quiet-from"),
+                        value: current.notifications.quiet_hours.start,
+                        disabled: !current.notifications.quiet_hours.enabled,
+                        onchange: move |time| settings.write().notifications.quiet_hours.start = time,
+                    }
+                    TimeField {
+                        label: t!("settings-quiet-until"),
+                        value: current.notifications.quiet_hours.end,
+                        disabled: !current.notifications.quiet_hours.enabled,
+                        onchange: move |time| settings.write().notifications.quiet_hours.end = time,
+                    }
+                }
+                span { class: "text-sm", {t!("settings-room-levels")} }
+                for (room, name) in rooms {
+                    SelectField {
+                        key: "{room}",
+                        label: "# {name}",
+                        value: key(current.notifications.levels.get(&room).copied().unwrap_or_default()),
+                        options: level_options.clone(),
+                        onchange: move |value: String| {
+                            if let Some(level) = parse(&Level::ALL, &value) {
+                                settings.write().notifications.set_level(room, level);
+                            }
+                        },
+                    }
+                }
+            }
+            Section {
+                id: "language-time",
+                title: t!("settings-language-time"),
+                SelectField {
+                    label: t!("settings-language"),
+                    value: key(current.language),
+                    options: options(&Language::ALL, |language| language.name().to_string()),
+                    onchange: move |value: String| {
+                        if let Some(language) = parse(&Language::ALL, &value) {
+                            settings.write().language = language;
+                        }
+                    },
+                }
+                SelectField {
+                    label: t!("settings-timestamps"),
+                    value: key(current.timestamps),
+                    options: options(&TimestampStyle::ALL, |style| t!(match style {
+                        TimestampStyle::Relative => "timestamps-relative",
+                        TimestampStyle::Exact => "timestamps-exact",
+                    })),
+                    onchange: move |value: String| {
+                        if let Some(style) = parse(&TimestampStyle::ALL, &value) {
+                            settings.write().timestamps = style;
+                        }
+                    },
+                }
+                SelectField {
+                    label: t!("settings-clock"),
+                    value: key(current.clock),
+                    options: options(&Clock::ALL, |clock| t!(match clock {
+                        Clock::Auto => "clock-auto",
+                        Clock::H12 => "clock-12",
+                        Clock::H24 => "clock-24",
+                    })),
+                    onchange: move |value: String| {
+                        if let Some(clock) = parse(&Clock::ALL, &value) {
+                            settings.write().clock = clock;
+                        }
+                    },
+                }
+            }
+            Section {
+                id: "shortcuts",
+                title: t!("shortcuts"),
+                for action in Action::ALL {
+                    ShortcutField {
+                        action: action,
+                        binding: current.shortcuts.binding(action),
+                        changed: current.shortcuts.is_changed(action),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Edits one custom theme. Changes show right away when the theme is the current one.
+#[component]
+fn CustomThemeEditor(theme: CustomTheme) -> Element {
+    let mut settings = use_settings();
+    let id = theme.id;
+
+    rsx! {
+        div {
+            class: "flex flex-col gap-2 p-3 rounded-box bg-base-200",
+            input {
+                class: "input input-sm w-full",
+                aria_label: t!("theme-name"),
+                value: "{theme.name}",
+                oninput: move |evt| update_theme(settings, id, |theme| theme.name = evt.value()),
+            }
+            ToggleField {
+                label: t!("theme-dark-base"),
+                checked: theme.dark,
+                onchange: move |dark| update_theme(settings, id, |theme| theme.dark = dark),
+            }
+            div {
+                class: "flex flex-row flex-wrap gap-4",
+                ColorField {
+                    label: t!("theme-accent"),
+                    value: theme.accent.clone(),
+                    onchange: move |color| update_theme(settings, id, |theme| theme.accent = color),
+                }
+                ColorField {
+                    label: t!("theme-own-bubble"),
+                    value: theme.own_bubble.clone(),
+                    onchange: move |color| update_theme(settings, id, |theme| theme.own_bubble = color),
+                }
+                ColorField {
+                    label: t!("theme-other-bubble"),
+                    value: theme.other_bubble.clone(),
+                    onchange: move |color| update_theme(settings, id, |theme| theme.other_bubble = color),
+                }
+            }
+            button {
+                class: "btn btn-sm btn-ghost text-error self-end",
+                onclick: move |_| {
+                    let mut settings = settings.write();
+
+                    settings.custom_themes.retain(|theme| theme.id != id);
+
+                    if settings.theme == Theme::Custom(id) {
+                        settings.theme = Theme::System;
+                    }
+                },
+                {t!("theme-delete")}
+            }
+        }
+    }
+}
+
+fn update_theme(mut settings: Signal<Preferences>, id: Uuid, change: impl FnOnce(&mut CustomTheme)) {
+    if let Some(theme) = settings.write().custom_themes.iter_mut().find(|theme| theme.id == id) {
+        change(theme);
+    }
+}
+
+/// Shows the binding of a shortcut. Clicking it records the next key press as the new binding.
+#[component]
+fn ShortcutField(action: Action, binding: Binding, changed: bool) -> Element {
+    let mut settings = use_settings();
+    let mut recording = use_signal(|| false);
+
+    rsx! {
+        div {
+            class: "flex flex-row items-center justify-between gap-4",
+            span { class: "text-sm", {t!(action.label())} }
+            div {
+                class: "flex flex-row items-center gap-2",
+                if changed {
+                    button {
+                        class: "btn btn-xs btn-ghost",
+                        onclick: move |_| settings.write().shortcuts.reset(action),
+                        {t!("shortcut-reset")}
+                    }
+                }
+                button {
+                    class: if recording() { "btn btn-sm btn-accent" } else { "btn btn-sm btn-outline" },
+                    "data-recording-shortcut": recording().then_some("true"),
+                    aria_label: "{t!(action.label())}: {binding}",
+                    onclick: move |_| recording.toggle(),
+                    onblur: move |_| recording.set(false),
+                    onkeydown: move |evt| {
+                        if !recording() {
+                            return;
+                        }
+
+                        if let Some(binding) = Binding::from_event(&evt) {
+                            evt.prevent_default();
+                            settings.write().shortcuts.set(action, binding);
+                            recording.set(false);
+                        }
+                    },
+                    if recording() {
+                        {t!("shortcut-record")}
+                    } else {
+                        kbd { class: "kbd kbd-sm", "{binding}" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn ColorField(label: String, value: String, onchange: EventHandler<String>) -> Element {
+    rsx! {
+        label {
+            class: "flex flex-row items-center gap-2 text-sm",
+            input {
+                class: "w-8 h-8 cursor-pointer",
+                r#type: "color",
+                value: "{value}",
+                oninput: move |evt| onchange.call(evt.value()),
+            }
+            "{label}"
+        }
+    }
+}
+
+/// Whether the browser may show notifications, with a button to ask for it. Only shown on the web.
+#[component]
+fn BrowserNotifications() -> Element {
+    let mut permission = use_resource(push::permission);
+
+    let Some(current) = permission() else {
+        return rsx! {};
+    };
+
+    let status = match current {
+        Permission::Default => t!("browser-notifications-default"),
+        Permission::Granted => t!("browser-notifications-granted"),
+        Permission::Denied => t!("browser-notifications-denied"),
+        Permission::Unsupported => return rsx! {},
+    };
+
+    rsx! {
+        div {
+            class: "flex flex-row flex-wrap items-center justify-between gap-2",
+            span { class: "text-sm", "{status}" }
+            if current == Permission::Default {
+                button {
+                    class: "btn btn-sm btn-accent",
+                    onclick: move |_| async move {
+                        push::request_permission().await;
+                        permission.restart();
+                    },
+                    {t!("browser-notifications-allow")}
+                }
+            }
+            if current == Permission::Granted {
+                button {
+                    class: "btn btn-sm btn-outline",
+                    onclick: move |_| push::test_push(None, t!("push-test-title"), t!("push-test-body")),
+                    {t!("browser-notifications-test")}
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn TimeField(label: String, value: NaiveTime, disabled: bool, onchange: EventHandler<NaiveTime>) -> Element {
+    let value = value.format("%H:%M").to_string();
+
+    rsx! {
+        label {
+            class: "flex flex-row items-center gap-2 text-sm",
+            "{label}"
+            input {
+                class: "input input-sm w-32",
+                r#type: "time",
+                value: "{value}",
+                disabled: disabled,
+                onchange: move |evt| {
+                    if let Ok(time) = NaiveTime::parse_from_str(&evt.value(), "%H:%M") {
+                        onchange.call(time);
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[component]
+fn Section(id: String, title: String, children: Element) -> Element {
+    rsx! {
+        fieldset {
+            id: "{id}",
+            class: "fieldset flex flex-col gap-3 p-4 rounded-box border border-base-300",
+            legend { class: "fieldset-legend text-base", "{title}" }
+            {children}
+        }
+    }
+}
+
+/// A drop-down for one setting. `options` are pairs of a value and its label.
+#[component]
+fn SelectField(label: String, value: String, options: Vec<(String, String)>, onchange: EventHandler<String>) -> Element {
+    rsx! {
+        label {
+            class: "flex flex-col gap-1",
+            span { class: "text-sm", "{label}" }
+            select {
+                class: "select w-full",
+                onchange: move |evt| onchange.call(evt.value()),
+                for (option, text) in options {
+                    option {
+                        selected: option == value,
+                        value: "{option}",
+                        "{text}"
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn ToggleField(label: String, checked: bool, #[props(default)] disabled: bool, onchange: EventHandler<bool>) -> Element {
+    rsx! {
+        label {
+            class: "flex flex-row items-center justify-between gap-4",
+            span { class: "text-sm", "{label}" }
+            input {
+                class: "toggle toggle-accent",
+                r#type: "checkbox",
+                checked: checked,
+                disabled: disabled,
+                onchange: move |evt| onchange.call(evt.checked()),
+            }
+        }
+    }
+}
+
+/// The value of a setting in a drop-down.
+fn key(value: impl Debug) -> String {
+    format!("{value:?}")
+}
+
+fn options<T: Copy + Debug>(all: &[T], label: impl Fn(T) -> String) -> Vec<(String, String)> {
+    all.iter().map(|value| (key(value), label(*value))).collect()
+}
+
+fn parse<T: Copy + Debug>(all: &[T], value: &str) -> Option<T> {
+    all.iter().copied().find(|option| key(option) == value)
+}
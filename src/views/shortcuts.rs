@@ -0,0 +1,235 @@
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::i18n::t;
+use crate::settings::use_settings;
+use crate::shortcuts::{self, Action};
+use crate::{Author, Route, ROOMS};
+
+/// The dialogs opened with keyboard shortcuts. Only one is open at a time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dialog {
+    QuickSwitcher,
+    Help,
+}
+
+pub static DIALOG: GlobalSignal<Option<Dialog>> = Global::new(|| None);
+
+/// Runs the global keyboard shortcuts and shows the dialog they opened. Rendered once, by the layout, so that the
+/// layout itself does not re-render when a dialog opens or closes.
+#[component]
+pub fn KeyboardShortcuts() -> Element {
+    let user = use_context::<Signal<Author>>();
+
+    // Escape is only taken while there is a dialog to close.
+    let available = |action: Action| action != Action::CloseDialog || DIALOG.read().is_some();
+
+    shortcuts::use_shortcuts(available, move |action| {
+        let step = match action {
+            Action::QuickSwitcher => {
+                toggle(Dialog::QuickSwitcher);
+                return;
+            }
+            Action::ShowHelp => {
+                toggle(Dialog::Help);
+                return;
+            }
+            Action::CloseDialog => {
+                *DIALOG.write() = None;
+                return;
+            }
+            Action::PreviousRoom => -1,
+            Action::NextRoom => 1,
+            // Handled by the composer.
+            Action::EditLastMessage => return,
+        };
+
+        if let Some(id) = shortcuts::adjacent_room(user.peek().id, step) {
+            navigator().push(Route::Chat { id });
+        }
+    });
+
+    match DIALOG() {
+        Some(Dialog::QuickSwitcher) => rsx! { QuickSwitcher {} },
+        Some(Dialog::Help) => rsx! { ShortcutHelp {} },
+        None => rsx! {},
+    }
+}
+
+fn toggle(dialog: Dialog) {
+    let mut open = DIALOG.write();
+
+    *open = if *open == Some(dialog) { None } else { Some(dialog) };
+}
+
+/// Opens a dialog as a modal, which keeps the focus inside of it, and remembers what had the focus before.
+const OPEN_JS: &str = r#"
+    const dialog = document.getElementById(await dioxus.recv());
+
+    window.dxReturnFocus = document.activeElement;
+
+    // Esc is a shortcut that can be changed, the dialog closes through it rather than by itself.
+    dialog?.addEventListener("cancel", (event) => event.preventDefault());
+    dialog?.showModal();
+"#;
+
+/// Gives the focus back to what had it before the dialog opened.
+const RESTORE_FOCUS_JS: &str = r#"
+    window.dxReturnFocus?.focus();
+    window.dxReturnFocus = null;
+"#;
+
+/// A modal that closes when the backdrop is clicked. While it is open the rest of the page is inert, and when it
+/// closes the focus goes back where it was.
+#[component]
+fn Modal(label: String, children: Element) -> Element {
+    use_drop(|| {
+        document::eval(RESTORE_FOCUS_JS);
+    });
+
+    rsx! {
+        dialog {
+            id: "dialog",
+            class: "modal",
+            aria_label: "{label}",
+            onmounted: move |_| {
+                let _ = document::eval(OPEN_JS).send("dialog");
+            },
+            div {
+                class: "modal-box flex flex-col gap-3",
+                {children}
+            }
+            div {
+                class: "modal-backdrop",
+                onclick: move |_| *DIALOG.write() = None,
+            }
+        }
+    }
+}
+
+/// Jump to a room by typing part of its name. Up and Down pick a room, Enter opens it.
+#[component]
+fn QuickSwitcher() -> Element {
+    let user = use_context::<Signal<Author>>();
+    let mut query = use_signal(String::new);
+    let mut selected = use_signal(|| 0);
+
+    let user_id = user.read().id;
+    let search = query.read().trim().to_lowercase();
+    let matches: Vec<(Uuid, String)> = ROOMS.read().values()
+        .filter(|room| room.info.read().members.contains(&user_id))
+        .map(|room| (room.id, room.info.read().name.clone()))
+        .filter(|(_, name)| name.to_lowercase().contains(&search))
+        .collect();
+    let current = selected().min(matches.len().saturating_sub(1));
+
+    let open = move |id: Uuid| {
+        *DIALOG.write() = None;
+        navigator().push(Route::Chat { id });
+    };
+
+    let choices = matches.clone();
+
+    rsx! {
+        Modal {
+            label: t!("shortcut-quick-switcher"),
+            input {
+                class: "input w-full",
+                r#type: "search",
+                aria_label: t!("quick-switcher-placeholder"),
+                placeholder: t!("quick-switcher-placeholder"),
+                role: "combobox",
+                aria_controls: "quick-switcher-rooms",
+                aria_expanded: "true",
+                value: "{query}",
+                onmounted: move |evt| async move {
+                    let _ = evt.data().set_focus(true).await;
+                },
+                oninput: move |evt| {
+                    query.set(evt.value());
+                    selected.set(0);
+                },
+                onkeydown: move |evt| match evt.key() {
+                    Key::ArrowDown => {
+                        evt.prevent_default();
+                        selected.set((current + 1).min(choices.len().saturating_sub(1)));
+                    }
+                    Key::ArrowUp => {
+                        evt.prevent_default();
+                        selected.set(current.saturating_sub(1));
+                    }
+                    Key::Enter => {
+                        if let Some((id, _)) = choices.get(current) {
+                            open(*id);
+                        }
+                    }
+                    _ => {}
+                },
+            }
+            if matches.is_empty() {
+                p { class: "text-sm opacity-50", {t!("quick-switcher-empty")} }
+            }
+            ul {
+                id: "quick-switcher-rooms",
+                class: "menu w-full p-0",
+                role: "listbox",
+                for (index, (id, name)) in matches.into_iter().enumerate() {
+                    li {
+                        key: "{id}",
+                        role: "option",
+                        aria_selected: index == current,
+                        button {
+                            class: if index == current { "menu-active" } else { "" },
+                            onclick: move |_| open(id),
+                            "# {name}"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Lists the keyboard shortcuts and their current bindings.
+#[component]
+fn ShortcutHelp() -> Element {
+    let settings = use_settings();
+    let shortcuts = settings.read().shortcuts.clone();
+
+    rsx! {
+        Modal {
+            label: t!("shortcuts"),
+            h3 { class: "text-lg font-bold", {t!("shortcuts")} }
+            table {
+                class: "table table-sm",
+                tbody {
+                    for action in Action::ALL {
+                        tr {
+                            td { {t!(action.label())} }
+                            td {
+                                class: "text-end",
+                                kbd { class: "kbd kbd-sm", "{shortcuts.binding(action)}" }
+                            }
+                        }
+                    }
+                }
+            }
+            div {
+                class: "modal-action",
+                button {
+                    class: "btn btn-sm btn-ghost",
+                    onclick: move |_| {
+                        *DIALOG.write() = None;
+                        navigator().push(Route::Settings {});
+                    },
+                    {t!("shortcuts-customize")}
+                }
+                button {
+                    class: "btn btn-sm",
+                    onclick: move |_| *DIALOG.write() = None,
+                    {t!("close")}
+                }
+            }
+        }
+    }
+}